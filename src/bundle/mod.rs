@@ -0,0 +1,3 @@
+pub mod offline;
+
+pub use offline::{install_bundle, OfflineBundle};
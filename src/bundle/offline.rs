@@ -0,0 +1,568 @@
+use crate::config::{ShranDefault, Upstream};
+use crate::error::ShranError;
+use crate::sources::depends::{parse_packages, DependsSource};
+use crate::sources::DependsFetcher;
+use crate::strategies::bitcoin::BuildStrategy;
+use crate::utils::downloader::sha256_hex;
+use crate::utils::fs_manager::is_plain_name;
+use crate::utils::{
+    Archiver, BlockchainKind, Downloader, FileSystemManager, ManifestKey, TapeArchive,
+};
+use crate::verify::ThresholdVerifier;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Name of the single top level directory inside of a bundle
+const BUNDLE_ROOT: &str = "shran-bundle";
+
+/// Describes the contents of a bundle, stored as `bundle.yaml` at its root
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct BundleManifest {
+    pub blockchain: String,
+    pub tag: String,
+    pub published_date: String,
+    /// File name of the source archive inside of `source/`
+    pub source: String,
+    /// Hex encoded sha256 digest of the source archive
+    pub sha256: String,
+    /// The build profile, or the name of the build file, `build.yaml` was made from
+    pub profile: String,
+    /// Files inside of `signatures/`, empty if they could not be downloaded
+    #[serde(default)]
+    pub signatures: Vec<String>,
+    /// The depends sources the release asks for, inside of `depends/`
+    #[serde(default)]
+    pub depends: Vec<BundledFile>,
+}
+
+/// A file inside of a bundle, with the digest it is checked against on install
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct BundledFile {
+    pub name: String,
+    /// Hex encoded sha256 digest of the file
+    pub sha256: String,
+}
+
+impl BundleManifest {
+    /// The tag and file names of a bundle are joined into paths of the
+    /// cache, a crafted bundle.yaml must not reach outside of it
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::FileSystemError naming the first name that is not
+    /// a plain file name
+    fn check_names(&self) -> Result<(), ShranError<'static>> {
+        let mut names = std::iter::once(&self.tag)
+            .chain(std::iter::once(&self.source))
+            .chain(&self.signatures)
+            .chain(self.depends.iter().map(|file| &file.name));
+        match names.find(|name| !is_plain_name(name)) {
+            Some(name) => Err(ShranError::FileSystemError {
+                msg: format!(
+                    "the bundle names {:?}, which is not a plain file name",
+                    name
+                ),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Everything needed to build a release on a machine without network access:
+/// the source archive, its SHA256SUMS and builder signatures, the depends
+/// sources its packages ask for, and a build.yaml.
+pub struct OfflineBundle {
+    tag: String,
+    upstream: Upstream,
+    profile: String,
+    expected_sha256: Option<String>,
+    published_date: Option<String>,
+}
+
+impl OfflineBundle {
+    /// Build a new OfflineBundle
+    ///
+    /// # Params
+    ///
+    /// 1. tag: The release to bundle
+    /// 2. upstream: Where the source archive is downloaded from
+    /// 3. profile: A build profile name [e.g.] rpc, or the path of a build.yaml
+    pub fn new(tag: String, upstream: Upstream, profile: String) -> Self {
+        Self {
+            tag,
+            upstream,
+            profile,
+            expected_sha256: None,
+            published_date: None,
+        }
+    }
+
+    /// Pin the expected sha256 digest of the source archive
+    pub fn with_checksum(mut self, sha256: String) -> Self {
+        self.expected_sha256 = Some(sha256);
+        self
+    }
+
+    /// The release date recorded in the receiving side's manifest
+    pub fn with_published_date(mut self, published_date: String) -> Self {
+        self.published_date = Some(published_date);
+        self
+    }
+
+    /// Collect everything into a gzipped tarball at `output`
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::DownloadError if the source archive or one of its
+    /// depends sources can not be downloaded, ShranError::BuildFileError for
+    /// an unknown profile, and io errors if the bundle can not be written.
+    /// Missing signatures only produce a warning.
+    pub fn create(&self, output: &str) -> Result<BundleManifest, Box<dyn Error>> {
+        self.create_from(&FileSystemManager::new()?, output)
+    }
+
+    /// Same as OfflineBundle::create, the depends sources are downloaded into
+    /// and taken from the cache of `fs_manager`
+    pub fn create_from(
+        &self,
+        fs_manager: &FileSystemManager,
+        output: &str,
+    ) -> Result<BundleManifest, Box<dyn Error>> {
+        let staging = staging_dir("create")?;
+        let result = self.stage(fs_manager, &staging).and_then(|manifest| {
+            TapeArchive::new(output, &staging).pack(BUNDLE_ROOT)?;
+            Ok(manifest)
+        });
+        let _ = fs::remove_dir_all(&staging);
+        result
+    }
+
+    fn stage(
+        &self,
+        fs_manager: &FileSystemManager,
+        staging: &str,
+    ) -> Result<BundleManifest, Box<dyn Error>> {
+        let source = format!("{}{}", self.tag, ShranDefault::FILE_EXTENSION);
+        // the tarball next to SHA256SUMS is the only archive its signatures
+        // cover, so it is preferred over the github archive
+        let mut downloader = Downloader::new(
+            self.upstream.signed_candidate_urls(&self.tag),
+            self.expected_sha256.clone(),
+        );
+        let download = downloader.fetch()?;
+        println!("{}", download);
+        fs::create_dir_all(format!("{}/source", staging))?;
+        let archive = format!("{}/source/{}", staging, source);
+        fs::write(&archive, &download.bytes)?;
+
+        let signatures = self.stage_signatures(staging)?;
+        let depends = stage_depends(&fs_manager.depends_sources_dir(), &archive, staging)?;
+
+        let build_file = if Path::new(&self.profile).is_file() {
+            fs::read_to_string(&self.profile)?
+        } else {
+            BuildStrategy::from_profile(&self.profile)?.to_yaml()?
+        };
+        fs::write(
+            format!("{}/{}", staging, ShranDefault::BUILD_CONFIG_FILENAME),
+            build_file,
+        )?;
+
+        let manifest = BundleManifest {
+            blockchain: ShranDefault::SUPPORTED_BLOCKCHAINS[0].to_string(),
+            tag: self.tag.clone(),
+            published_date: self
+                .published_date
+                .clone()
+                .unwrap_or_else(|| Utc::now().to_string()),
+            source,
+            sha256: download.sha256,
+            profile: self.profile.clone(),
+            signatures,
+            depends,
+        };
+        fs::write(
+            format!("{}/{}", staging, ShranDefault::BUNDLE_MANIFEST_FILENAME),
+            serde_yaml::to_string(&manifest)?,
+        )?;
+        Ok(manifest)
+    }
+
+    /// SHA256SUMS and its signatures, from the binaries url of the upstream
+    fn stage_signatures(&self, staging: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let base = self.upstream.binaries_url(&self.tag);
+
+        let mut signatures: Vec<String> = Vec::new();
+        fs::create_dir_all(format!("{}/signatures", staging))?;
+        for filename in [
+            ShranDefault::SHA256SUMS_FILENAME,
+            ShranDefault::SHA256SUMS_SIGNATURE_FILENAME,
+        ] {
+            let mut downloader = Downloader::new(vec![format!("{}/{}", base, filename)], None);
+            match downloader.fetch() {
+                Ok(download) => {
+                    fs::write(
+                        format!("{}/signatures/{}", staging, filename),
+                        download.bytes,
+                    )?;
+                    signatures.push(filename.to_string());
+                }
+                Err(_) => eprintln!("Warning: {} is not included in the bundle", filename),
+            }
+        }
+        Ok(signatures)
+    }
+}
+
+/// Copy the depends sources the packages of the source `archive` ask for
+/// into the bundle, downloading the ones missing from `depends_dir` first
+fn stage_depends(
+    depends_dir: &str,
+    archive: &str,
+    staging: &str,
+) -> Result<Vec<BundledFile>, Box<dyn Error>> {
+    let tree_dir = staging_dir("tree")?;
+    let required = required_depends(depends_dir, archive, &tree_dir);
+    let _ = fs::remove_dir_all(&tree_dir);
+
+    fs::create_dir_all(format!("{}/depends", staging))?;
+    let mut depends: Vec<BundledFile> = Vec::new();
+    for source in required? {
+        let name = source.file_name;
+        if depends.iter().any(|file| file.name == name) {
+            continue;
+        }
+        fs::copy(
+            format!("{}/{}", depends_dir, name),
+            format!("{}/depends/{}", staging, name),
+        )?;
+        depends.push(BundledFile {
+            name,
+            sha256: source.sha256,
+        });
+    }
+    depends.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(depends)
+}
+
+/// Extract the source `archive` into `tree_dir` and fetch everything its
+/// `depends/packages` ask for into `depends_dir`
+///
+/// # Errors
+///
+/// Returns ShranError::DownloadError if a depends source can not be fetched,
+/// the bundle would not be enough to build the release without network
+fn required_depends(
+    depends_dir: &str,
+    archive: &str,
+    tree_dir: &str,
+) -> Result<Vec<DependsSource>, Box<dyn Error>> {
+    let tarball = TapeArchive::new(archive, tree_dir);
+    let tree = match tarball.root_dir()? {
+        Some(root) => format!("{}/{}", tree_dir, root),
+        None => tree_dir.to_string(),
+    };
+    tarball.unpack()?;
+    let report = DependsFetcher::new(tree.clone(), depends_dir.to_string()).prefetch()?;
+    println!("{}", report);
+    if !report.failed.is_empty() {
+        return Err(Box::new(ShranError::DownloadError {
+            msg: format!(
+                "{} depends sources could not be fetched",
+                report.failed.len()
+            ),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+    Ok(parse_packages(&format!("{}/depends/packages", tree))?)
+}
+
+/// A bundle that has been installed into the cache
+pub struct InstalledBundle {
+    pub manifest: BundleManifest,
+    /// The directory the sources were extracted to
+    pub location: String,
+}
+
+/// Unpack a bundle made by OfflineBundle::create into the cache: the sources
+/// are extracted next to downloaded ones, signatures and depends sources go to
+/// their usual cache directories, and build.yaml is placed inside the sources.
+///
+/// # Errors
+///
+/// Returns ShranError::DownloadError if the source archive does not match the
+/// checksum recorded in the bundle, or a depends source does not match its
+/// checksum, and ShranError::SignatureThresholdError if a
+/// verifier is given and the bundled signatures do not meet its threshold, or
+/// do not cover the bundled source archive. Sources installed before are only replaced if `force` is given.
+pub fn install_bundle(
+    bundle: &str,
+    verifier: Option<&ThresholdVerifier>,
+    force: bool,
+) -> Result<InstalledBundle, Box<dyn Error>> {
+    install_bundle_into(
+        &FileSystemManager::new()?.with_force(force),
+        bundle,
+        verifier,
+    )
+}
+
+/// Same as install_bundle, installing into the cache of `fs_manager`
+pub fn install_bundle_into(
+    fs_manager: &FileSystemManager,
+    bundle: &str,
+    verifier: Option<&ThresholdVerifier>,
+) -> Result<InstalledBundle, Box<dyn Error>> {
+    let staging = staging_dir("install")?;
+    let result = install_staged(fs_manager, bundle, &staging, verifier);
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn install_staged(
+    fs_manager: &FileSystemManager,
+    bundle: &str,
+    staging: &str,
+    verifier: Option<&ThresholdVerifier>,
+) -> Result<InstalledBundle, Box<dyn Error>> {
+    TapeArchive::new(bundle, staging).unpack()?;
+    let root = format!("{}/{}", staging, BUNDLE_ROOT);
+    let manifest: BundleManifest = serde_yaml::from_str(&fs::read_to_string(format!(
+        "{}/{}",
+        root,
+        ShranDefault::BUNDLE_MANIFEST_FILENAME
+    ))?)?;
+    manifest.check_names()?;
+
+    let bytes = fs::read(format!("{}/source/{}", root, manifest.source))?;
+    let sha256 = sha256_hex(&bytes);
+    if sha256 != manifest.sha256.to_lowercase() {
+        return Err(Box::new(ShranError::DownloadError {
+            msg: format!(
+                "{} does not match the bundled checksum ({})",
+                manifest.source, sha256
+            ),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+
+    let mut signature_paths: Vec<String> = Vec::new();
+    for filename in &manifest.signatures {
+        let bytes = fs::read(format!("{}/signatures/{}", root, filename))?;
+        signature_paths.push(fs_manager.write_signature_file(&manifest.tag, filename, bytes)?);
+    }
+    if let Some(verifier) = verifier {
+        if signature_paths.len() != 2 {
+            return Err(Box::new(ShranError::SignatureThresholdError {
+                msg: format!("{} was bundled without signatures", manifest.tag),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }));
+        }
+        let report =
+            verifier.verify_files(&manifest.tag, &signature_paths[0], &signature_paths[1])?;
+        println!("{}", report);
+        // the bundled archive has to be the tarball the builders signed
+        report.bind_source_sha256(Some(&sha256))?;
+    }
+
+    // every depends source is checked before anything is installed
+    let mut depends: Vec<(&BundledFile, Vec<u8>)> = Vec::new();
+    for file in &manifest.depends {
+        let bytes = fs::read(format!("{}/depends/{}", root, file.name))?;
+        if sha256_hex(&bytes) != file.sha256.to_lowercase() {
+            return Err(Box::new(ShranError::DownloadError {
+                msg: format!("{} does not match its bundled checksum", file.name),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }));
+        }
+        depends.push((file, bytes));
+    }
+
+    let location = fs_manager.write_and_extract_blockchain_archive(
+        &manifest.source,
+        bytes,
+        BlockchainKind::Bitcoin,
+        &ManifestKey::new(ShranDefault::SUPPORTED_BLOCKCHAINS[0], &manifest.tag),
+    )?;
+    fs::copy(
+        format!("{}/{}", root, ShranDefault::BUILD_CONFIG_FILENAME),
+        format!("{}/{}", location, ShranDefault::BUILD_CONFIG_FILENAME),
+    )?;
+
+    let depends_dir = fs_manager.depends_sources_dir();
+    fs::create_dir_all(&depends_dir)?;
+    for (file, bytes) in depends {
+        fs::write(format!("{}/{}", depends_dir, file.name), bytes)?;
+    }
+
+    Ok(InstalledBundle { manifest, location })
+}
+
+fn staging_dir(purpose: &str) -> Result<String, Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!(
+        "shran-bundle-{}-{}-{}",
+        purpose,
+        std::process::id(),
+        Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ));
+    fs::create_dir_all(&dir)?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{install_bundle_into, BundleManifest, BundledFile, OfflineBundle};
+    use crate::config::{ShranDefault, Upstream};
+    use crate::utils::downloader::sha256_hex;
+    use crate::utils::mock_archive::gzip_tarball;
+    use crate::utils::FileSystemManager;
+    use std::env;
+    use std::fs;
+
+    fn source_tarball(root: &str) -> Vec<u8> {
+        let boost = format!(
+            "package=boost\n\
+             $(package)_version=1.81.0\n\
+             $(package)_download_path=file:///nonexistent\n\
+             $(package)_file_name=boost_1_81_0.tar.bz2\n\
+             $(package)_sha256_hash={}\n",
+            sha256_hex(b"boost")
+        );
+        gzip_tarball(&[
+            (&format!("{}/main.cpp", root), b"int main() {}"),
+            (
+                &format!("{}/depends/packages/boost.mk", root),
+                boost.as_bytes(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_bundle_create_and_install() {
+        let id = std::process::id();
+        let tag = format!("v0.{}", id);
+        let version = tag.trim_start_matches('v');
+        let root = format!("bitcoin-bundle-{}", id);
+        let mirror = env::temp_dir().join(format!("shran-bundle-mirror-{}", id));
+        let sender = FileSystemManager::in_dir(mirror.join("sender").to_str().unwrap()).unwrap();
+        let receiver =
+            FileSystemManager::in_dir(mirror.join("receiver").to_str().unwrap()).unwrap();
+        fs::create_dir_all(sender.depends_sources_dir()).unwrap();
+        fs::write(
+            format!("{}/boost_1_81_0.tar.bz2", sender.depends_sources_dir()),
+            "boost",
+        )
+        .unwrap();
+        // other trees share the cache, their sources do not belong in the bundle
+        fs::write(
+            format!("{}/qt-everywhere.tar.xz", sender.depends_sources_dir()),
+            "qt",
+        )
+        .unwrap();
+        let release_dir = mirror.join(format!("bitcoin-core-{}", version));
+        fs::create_dir_all(&release_dir).unwrap();
+        fs::write(
+            release_dir.join(format!("bitcoin-{}.tar.gz", version)),
+            source_tarball(&root),
+        )
+        .unwrap();
+        fs::write(release_dir.join("SHA256SUMS"), "abc  bitcoin.tar.gz\n").unwrap();
+
+        let upstream = Upstream {
+            archive_url_template: "file:///nonexistent/{tag}.tar.gz".to_string(),
+            binaries_url: format!("file://{}", mirror.display()),
+            ..Upstream::default()
+        };
+        let output = mirror.join("bundle.tar.gz").to_string_lossy().to_string();
+        let manifest = OfflineBundle::new(tag.clone(), upstream, "rpc".to_string())
+            .create_from(&sender, &output)
+            .unwrap();
+        assert_eq!(manifest.signatures, vec!["SHA256SUMS"]);
+        assert_eq!(
+            manifest.depends,
+            vec![BundledFile {
+                name: "boost_1_81_0.tar.bz2".to_string(),
+                sha256: sha256_hex(b"boost"),
+            }]
+        );
+
+        let installed = install_bundle_into(&receiver, &output, None).unwrap();
+        assert!(installed
+            .location
+            .starts_with(&format!("{}/receiver/cache", mirror.display())));
+        assert_eq!(
+            fs::read_to_string(format!(
+                "{}/boost_1_81_0.tar.bz2",
+                receiver.depends_sources_dir()
+            ))
+            .unwrap(),
+            "boost"
+        );
+        assert_eq!(installed.manifest, manifest);
+        assert!(installed.location.ends_with(&root));
+        let build_file = fs::read_to_string(format!(
+            "{}/{}",
+            installed.location,
+            ShranDefault::BUILD_CONFIG_FILENAME
+        ))
+        .unwrap();
+        assert!(build_file.contains("wallet"));
+
+        let _ = fs::remove_dir_all(&mirror);
+    }
+
+    #[test]
+    fn test_bundle_names_stay_inside_the_cache() {
+        let manifest = BundleManifest {
+            blockchain: "bitcoin".to_string(),
+            tag: "v25.0".to_string(),
+            published_date: "2023-05-26 10:00:00 UTC".to_string(),
+            source: "v25.0.tar.gz".to_string(),
+            sha256: "abc".to_string(),
+            profile: "default".to_string(),
+            signatures: vec!["SHA256SUMS".to_string()],
+            depends: vec![BundledFile {
+                name: "boost_1_81_0.tar.bz2".to_string(),
+                sha256: "abc".to_string(),
+            }],
+        };
+        assert!(manifest.check_names().is_ok());
+        for crafted in [
+            BundleManifest {
+                depends: vec![BundledFile {
+                    name: "../../../.bashrc".to_string(),
+                    sha256: "abc".to_string(),
+                }],
+                ..manifest.clone()
+            },
+            BundleManifest {
+                signatures: vec!["/etc/passwd".to_string()],
+                ..manifest.clone()
+            },
+            BundleManifest {
+                source: "..".to_string(),
+                ..manifest.clone()
+            },
+            BundleManifest {
+                tag: "../v25.0".to_string(),
+                ..manifest.clone()
+            },
+        ] {
+            assert!(crafted.check_names().is_err());
+        }
+    }
+}
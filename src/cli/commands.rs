@@ -6,7 +6,7 @@ use std::fmt;
 /// # Details
 ///
 /// * auth
-///     - currently only supports a github token
+///     - currently only supports a github token, and reporting the remaining api quota
 ///
 /// * build
 ///     - requires a path to a relavant build template
@@ -17,6 +17,27 @@ use std::fmt;
 ///
 /// * generate
 ///     - create a build template which conforms to bitcoins automake build system
+///
+/// * bundle
+///     - create and install offline bundles for air-gapped build hosts
+///
+/// * ls
+///     - list installed versions, filtered and sorted
+///
+/// * remove
+///     - delete installed versions and their build artifacts from the cache
+///
+/// * manifest
+///     - maintain the manifest of installed versions, [e.g.] rebuild it from the cache
+///
+/// * verify
+///     - check that installed source trees still hold the files they were extracted with
+///
+/// * cache
+///     - report the disk usage of the cache, and garbage collect old versions
+///
+/// * config
+///     - show and change the settings, and where their values come from
 #[derive(Debug)]
 pub struct SubCommandName;
 
@@ -25,6 +46,14 @@ impl<'c> SubCommandName {
     pub const BUILD: &'c str = "build";
     pub const AUTH: &'c str = "auth";
     pub const FETCH: &'c str = "fetch";
+    pub const BUNDLE: &'c str = "bundle";
+    pub const NOTES: &'c str = "notes";
+    pub const LS: &'c str = "ls";
+    pub const REMOVE: &'c str = "remove";
+    pub const MANIFEST: &'c str = "manifest";
+    pub const VERIFY: &'c str = "verify";
+    pub const CACHE: &'c str = "cache";
+    pub const CONFIG: &'c str = "config";
 }
 
 /// Each subcommand will have associated arguments that go with it
@@ -38,11 +67,78 @@ impl<'c> ArgName {
     pub const STRATEGY: &'c str = "strategy";
     // Args for SubCommandName::AUTH
     pub const TOKEN: &'c str = "token";
+    pub const STATUS: &'c str = "status";
+    pub const ENCRYPT: &'c str = "encrypt";
     // Args for SubCommandName::FETCH
     pub const LIST_REMOTE: &'c str = "list_remote";
     pub const LIST_LOCAL: &'c str = "list_local";
     pub const LATEST: &'c str = "latest";
     pub const TAG: &'c str = "tag";
+    pub const VERSIONS: &'c str = "versions";
+    pub const JOBS: &'c str = "jobs";
+    pub const NOTES: &'c str = "notes";
+    pub const REF: &'c str = "ref";
+    pub const PR: &'c str = "pr";
+    pub const SOURCE: &'c str = "source";
+    pub const FROM_FILE: &'c str = "from_file";
+    pub const AS: &'c str = "as";
+    pub const DEPENDS: &'c str = "depends";
+    pub const PREBUILT: &'c str = "prebuilt";
+    pub const PLATFORM: &'c str = "platform";
+    pub const KEYRING: &'c str = "keyring";
+    pub const THRESHOLD: &'c str = "threshold";
+    pub const SHA256: &'c str = "sha256";
+    pub const SUMS: &'c str = "sums";
+    pub const SIGNATURE: &'c str = "signature";
+    pub const GIT: &'c str = "git";
+    pub const SHALLOW: &'c str = "shallow";
+    pub const OFFLINE: &'c str = "offline";
+    pub const REFRESH: &'c str = "refresh";
+    pub const FORCE: &'c str = "force";
+    // Args for SubCommandName::BUNDLE
+    pub const CREATE: &'c str = "create";
+    pub const INSTALL: &'c str = "install";
+    pub const PROFILE: &'c str = "profile";
+    pub const OUTPUT: &'c str = "output";
+    pub const BUNDLE_FILE: &'c str = "bundle_file";
+    // Args for SubCommandName::NOTES
+    pub const RANGE: &'c str = "range";
+    pub const MARKDOWN: &'c str = "markdown";
+    pub const SUMMARY: &'c str = "summary";
+    // Args for SubCommandName::LS
+    pub const BLOCKCHAIN: &'c str = "blockchain";
+    pub const SOURCE_TYPE: &'c str = "source_type";
+    pub const BUILT: &'c str = "built";
+    pub const NOT_BUILT: &'c str = "not_built";
+    pub const SORT: &'c str = "sort";
+    pub const REVERSE: &'c str = "reverse";
+    // Args for SubCommandName::REMOVE
+    pub const SELECTOR: &'c str = "selector";
+    pub const DRY_RUN: &'c str = "dry_run";
+    pub const YES: &'c str = "yes";
+    // Args for SubCommandName::MANIFEST
+    pub const RESCAN: &'c str = "rescan";
+    pub const PRUNE: &'c str = "prune";
+    // Args for SubCommandName::VERIFY
+    pub const VERSION: &'c str = "version";
+    // Args for SubCommandName::CACHE
+    pub const GC: &'c str = "gc";
+    pub const DU: &'c str = "du";
+    pub const PIN: &'c str = "pin";
+    pub const UNPIN: &'c str = "unpin";
+    pub const KEEP_LAST: &'c str = "keep_last";
+    pub const MAX_SIZE: &'c str = "max_size";
+    pub const MAX_AGE: &'c str = "max_age";
+    // Args for SubCommandName::CONFIG
+    pub const LIST: &'c str = "list";
+    pub const GET: &'c str = "get";
+    pub const SET: &'c str = "set";
+    pub const KEY: &'c str = "key";
+    pub const VALUE: &'c str = "value";
+    // Args accepted by every subcommand
+    pub const CACHE_DIR: &'c str = "cache_dir";
+    pub const INSTALL_PREFIX: &'c str = "install_prefix";
+    pub const COLOR: &'c str = "color";
 }
 
 /// Helps distinguish betweem arguments that have values,
@@ -77,6 +173,7 @@ impl fmt::Display for Argument {
 pub struct ActiveCommand {
     sub_command: String,
     arg: Argument,
+    modifiers: Vec<Argument>,
 }
 
 impl fmt::Display for ActiveCommand {
@@ -90,9 +187,17 @@ impl ActiveCommand {
         Self {
             sub_command: String::from(sub_command),
             arg,
+            modifiers: Vec::new(),
         }
     }
 
+    /// Attach optional arguments which alter the behaviour of the
+    /// main argument, e.g. `--keyring` on `fetch --tag`
+    pub fn with_modifiers(mut self, modifiers: Vec<Argument>) -> Self {
+        self.modifiers.extend(modifiers);
+        self
+    }
+
     pub fn sub_command(&self) -> &String {
         &self.sub_command
     }
@@ -100,4 +205,8 @@ impl ActiveCommand {
     pub fn arg(&self) -> Argument {
         self.arg.clone()
     }
+
+    pub fn modifier(&self, name: &str) -> Option<Argument> {
+        self.modifiers.iter().find(|m| m.name == name).cloned()
+    }
 }
@@ -34,6 +34,30 @@ impl<'e> Cli {
             .version(crate_version!())
             .about(crate_description!())
             .subcommand_required(true)
+            .arg(
+                Arg::new(ArgName::CACHE_DIR)
+                    .long("cache-dir")
+                    .help("Where sources, binaries and metadata are cached, overrides SHRAN_CACHE_DIR and the config file")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .global(true),
+            )
+            .arg(
+                Arg::new(ArgName::INSTALL_PREFIX)
+                    .long("install-prefix")
+                    .help("Where prebuilt binaries are installed, overrides SHRAN_INSTALL_PREFIX and the config file")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .global(true),
+            )
+            .arg(
+                Arg::new(ArgName::COLOR)
+                    .long("color")
+                    .help("When output is colored, overrides SHRAN_COLORS and the config file")
+                    .takes_value(true)
+                    .possible_values(["auto", "always", "never"])
+                    .global(true),
+            )
             .subcommand(
                 Command::new(SubCommandName::AUTH)
                     .arg_required_else_help(true)
@@ -42,8 +66,22 @@ impl<'e> Cli {
                     .arg(
                         Arg::new(ArgName::TOKEN)
                             .long("token")
-                            .help("The github token")
+                            .help("The github token, SHRAN_GITHUB_TOKEN or GITHUB_TOKEN take precedence over the stored token")
                             .takes_value(true),
+                    )
+                    .arg(
+                        Arg::new(ArgName::ENCRYPT)
+                            .long("encrypt")
+                            .help("Store the token encrypted with a passphrase, taken from SHRAN_TOKEN_PASSPHRASE or asked for")
+                            .takes_value(false)
+                            .requires(ArgName::TOKEN),
+                    )
+                    .arg(
+                        Arg::new(ArgName::STATUS)
+                            .long("status")
+                            .help("Show the remaining github api quota and the scopes of the stored token")
+                            .takes_value(false)
+                            .conflicts_with(ArgName::TOKEN),
                     ),
             )
             .subcommand(
@@ -56,6 +94,13 @@ impl<'e> Cli {
                             .long("strategy")
                             .help("Path to a custom build.yaml strategy")
                             .takes_value(true),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SOURCE)
+                            .long("source")
+                            .help("Where to take the source code from, e.g. github:bitcoin/bitcoin@v25.0, file:///tmp/bitcoin-25.0.tar.gz, dir:../bitcoin or git:<url>@<ref>")
+                            .takes_value(true)
+                            .requires(ArgName::STRATEGY),
                     ),
             )
             .subcommand(
@@ -87,8 +132,487 @@ impl<'e> Cli {
                     .arg(
                         Arg::new(ArgName::TAG)
                             .long("tag")
-                            .help("Download a version specified by tag")
+                            .help("Download a version specified by tag, several comma separated tags are downloaded concurrently")
+                            .takes_value(true)
+                            .multiple_values(true)
+                            .use_value_delimiter(true),
+                    )
+                    .arg(
+                        Arg::new(ArgName::VERSIONS)
+                            .long("versions")
+                            .help("Download every release matching a version constraint concurrently, e.g. \">=23.0, <26\"")
+                            .takes_value(true)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::LATEST, ArgName::TAG, ArgName::SOURCE, ArgName::FROM_FILE, ArgName::DEPENDS, ArgName::REF, ArgName::PR, ArgName::GIT, ArgName::PREBUILT, ArgName::SHA256]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::NOTES)
+                            .long("notes")
+                            .help("Print the release notes of a version instead of downloading it")
+                            .takes_value(true)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::LATEST, ArgName::TAG, ArgName::VERSIONS, ArgName::SOURCE, ArgName::FROM_FILE, ArgName::DEPENDS, ArgName::REF, ArgName::PR, ArgName::GIT, ArgName::PREBUILT, ArgName::KEYRING, ArgName::SHA256]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::MARKDOWN)
+                            .long("markdown")
+                            .help("Print the release notes as markdown instead of formatting them for the terminal")
+                            .takes_value(false)
+                            .requires(ArgName::NOTES),
+                    )
+                    .arg(
+                        Arg::new(ArgName::JOBS)
+                            .long("jobs")
+                            .short('j')
+                            .help("How many versions are downloaded at the same time, overrides SHRAN_JOBS and the config file (default: 4)")
+                            .takes_value(true)
+                            .validator(|s| s.parse::<usize>()),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SOURCE)
+                            .long("source")
+                            .help("Fetch from a source spec, e.g. github:bitcoin/bitcoin@v25.0, https://host/bitcoin-25.0.tar.gz, file:///tmp/bitcoin-25.0.tar.gz, dir:../bitcoin or git:<url>@<ref>")
+                            .takes_value(true)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::LATEST, ArgName::TAG, ArgName::REF, ArgName::PR, ArgName::GIT, ArgName::OFFLINE]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::FROM_FILE)
+                            .long("from-file")
+                            .help("Import a source tarball from the local file system, e.g. on an air-gapped machine")
+                            .takes_value(true)
+                            .requires(ArgName::AS)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::LATEST, ArgName::TAG, ArgName::SOURCE, ArgName::REF, ArgName::PR, ArgName::GIT, ArgName::OFFLINE, ArgName::REFRESH]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::AS)
+                            .long("as")
+                            .help("The release tag an imported tarball is registered as, e.g. v25.0")
+                            .takes_value(true)
+                            .requires(ArgName::FROM_FILE),
+                    )
+                    .arg(
+                        Arg::new(ArgName::DEPENDS)
+                            .long("depends")
+                            .help("Download the depends sources of an extracted source tree, given by path or fetched version, into the shared cache")
+                            .takes_value(true)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::LATEST, ArgName::TAG, ArgName::SOURCE, ArgName::FROM_FILE, ArgName::REF, ArgName::PR, ArgName::KEYRING, ArgName::SHA256, ArgName::GIT, ArgName::OFFLINE, ArgName::REFRESH]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::REF)
+                            .long("ref")
+                            .help("Download a commit or the head of a branch, pinned to the commit it resolves to")
+                            .takes_value(true)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::LATEST, ArgName::TAG, ArgName::PR, ArgName::KEYRING, ArgName::OFFLINE]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::PR)
+                            .long("pr")
+                            .help("Download the head commit of an upstream pull request")
+                            .takes_value(true)
+                            .validator(|s| s.parse::<u64>())
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::LATEST, ArgName::TAG, ArgName::KEYRING, ArgName::OFFLINE]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::KEYRING)
+                            .long("keyring")
+                            .help("Keyring of trusted builder keys, the release must be signed by enough of them before it is downloaded")
+                            .takes_value(true)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::THRESHOLD)
+                            .long("threshold")
+                            .help("Minimum number of distinct trusted builder signatures (default: 3)")
+                            .takes_value(true)
+                            .requires(ArgName::KEYRING)
+                            .validator(|s| s.parse::<usize>()),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SHA256)
+                            .long("sha256")
+                            .help("Expected sha256 digest of the source archive, mirrors are tried until one matches")
+                            .takes_value(true)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SUMS)
+                            .long("sums")
+                            .help("A local SHA256SUMS file, verified with --signature instead of downloading it")
+                            .takes_value(true)
+                            .requires_all(&[ArgName::KEYRING, ArgName::SIGNATURE]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SIGNATURE)
+                            .long("signature")
+                            .help("A local SHA256SUMS.asc file holding the builder signatures of --sums")
+                            .takes_value(true)
+                            .requires_all(&[ArgName::KEYRING, ArgName::SUMS]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::GIT)
+                            .long("git")
+                            .help("Check out the version from a local mirror clone of the repository instead of downloading an archive")
+                            .takes_value(false)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::LATEST, ArgName::KEYRING, ArgName::SHA256, ArgName::OFFLINE]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SHALLOW)
+                            .long("shallow")
+                            .help("Make a shallow checkout without history instead of a worktree of the mirror, with --git or a git: source")
+                            .takes_value(false),
+                    )
+                    .arg(
+                        Arg::new(ArgName::PREBUILT)
+                            .long("prebuilt")
+                            .help("Install the official release binaries of --tag or --latest for this machine instead of the source code")
+                            .takes_value(false)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::SOURCE, ArgName::FROM_FILE, ArgName::DEPENDS, ArgName::REF, ArgName::PR, ArgName::GIT, ArgName::SHA256]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::PLATFORM)
+                            .long("platform")
+                            .help("Platform of the release binaries, e.g. aarch64-linux-gnu (default: this machine)")
+                            .takes_value(true)
+                            .requires(ArgName::PREBUILT),
+                    )
+                    .arg(
+                        Arg::new(ArgName::OFFLINE)
+                            .long("offline")
+                            .help("Answer from the cached release metadata only, without contacting github")
+                            .takes_value(false)
+                            .conflicts_with_all(&[ArgName::REFRESH, ArgName::LIST_LOCAL]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::REFRESH)
+                            .long("refresh")
+                            .help("Update the cached release metadata from github, even if it has not expired yet")
+                            .takes_value(false)
+                            .conflicts_with_all(&[ArgName::LIST_LOCAL]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::FORCE)
+                            .long("force")
+                            .help("Replace the source tree of a version fetched before, discarding its builds and local changes")
+                            .takes_value(false)
+                            .conflicts_with_all(&[ArgName::LIST_REMOTE, ArgName::LIST_LOCAL, ArgName::NOTES, ArgName::DEPENDS]),
+                    ),
+            )
+            .subcommand(
+                Command::new(SubCommandName::BUNDLE)
+                    .arg_required_else_help(true)
+                    .subcommand_required(true)
+                    .about("Move everything a build needs onto a machine without network access")
+                    .subcommand(
+                        Command::new(ArgName::CREATE)
+                            .about("Collect the sources, signatures, depends sources and a build.yaml into a bundle")
+                            .arg(
+                                Arg::new(ArgName::TAG)
+                                    .long("tag")
+                                    .help("The release to bundle")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new(ArgName::PROFILE)
+                                    .long("profile")
+                                    .help("Build profile (default, rpc) or path of a build.yaml to include, overrides SHRAN_PROFILE and the config file")
+                                    .takes_value(true),
+                            )
+                            .arg(
+                                Arg::new(ArgName::OUTPUT)
+                                    .short('o')
+                                    .long("output")
+                                    .help("Where to write the bundle")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new(ArgName::SHA256)
+                                    .long("sha256")
+                                    .help("Expected sha256 digest of the source archive")
+                                    .takes_value(true),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new(ArgName::INSTALL)
+                            .about("Install a bundle into the cache and the manifest")
+                            .arg(
+                                Arg::new(ArgName::BUNDLE_FILE)
+                                    .help("Path of the bundle")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new(ArgName::KEYRING)
+                                    .long("keyring")
+                                    .help("Keyring of trusted builder keys the bundled signatures are checked against")
+                                    .takes_value(true),
+                            )
+                            .arg(
+                                Arg::new(ArgName::THRESHOLD)
+                                    .long("threshold")
+                                    .help("Minimum number of distinct trusted builder signatures (default: 3)")
+                                    .takes_value(true)
+                                    .requires(ArgName::KEYRING)
+                                    .validator(|s| s.parse::<usize>()),
+                            )
+                            .arg(
+                                Arg::new(ArgName::FORCE)
+                                    .long("force")
+                                    .help("Replace the source tree of the version if it was installed before")
+                                    .takes_value(false),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new(SubCommandName::NOTES)
+                    .arg_required_else_help(true)
+                    .about("Print the release notes of every release in a range, to plan an upgrade")
+                    .short_flag('N')
+                    .arg(
+                        Arg::new(ArgName::RANGE)
+                            .help("The releases after the first version up to and including the second, e.g. v24.0..v26.0")
+                            .takes_value(true)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new(ArgName::MARKDOWN)
+                            .long("markdown")
+                            .help("Emit markdown instead of formatting the notes for the terminal")
+                            .takes_value(false),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SUMMARY)
+                            .long("summary")
+                            .help("Only list the section headings of every release")
+                            .takes_value(false),
+                    )
+                    .arg(
+                        Arg::new(ArgName::OFFLINE)
+                            .long("offline")
+                            .help("Answer from the cached release metadata only, without contacting github")
+                            .takes_value(false)
+                            .conflicts_with(ArgName::REFRESH),
+                    )
+                    .arg(
+                        Arg::new(ArgName::REFRESH)
+                            .long("refresh")
+                            .help("Update the cached release metadata from github, even if it has not expired yet")
+                            .takes_value(false),
+                    ),
+            )
+            .subcommand(
+                Command::new(SubCommandName::LS)
+                    .about("List the installed versions, with their size on disk and build state")
+                    .arg(
+                        Arg::new(ArgName::BLOCKCHAIN)
+                            .long("blockchain")
+                            .help("Only list versions of this blockchain, e.g. bitcoin")
                             .takes_value(true),
+                    )
+                    .arg(
+                        Arg::new(ArgName::VERSIONS)
+                            .long("versions")
+                            .help("Only list versions matching a constraint, e.g. \">=24.0,<26.0\"")
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SOURCE_TYPE)
+                            .long("source")
+                            .help("Only list versions from one kind of source")
+                            .takes_value(true)
+                            .possible_values(["archive", "git", "dir", "prebuilt"]),
+                    )
+                    .arg(
+                        Arg::new(ArgName::BUILT)
+                            .long("built")
+                            .help("Only list versions that have been built")
+                            .takes_value(false)
+                            .conflicts_with(ArgName::NOT_BUILT),
+                    )
+                    .arg(
+                        Arg::new(ArgName::NOT_BUILT)
+                            .long("not-built")
+                            .help("Only list versions that have not been built yet")
+                            .takes_value(false),
+                    )
+                    .arg(
+                        Arg::new(ArgName::SORT)
+                            .long("sort")
+                            .help("What to order the versions by")
+                            .takes_value(true)
+                            .possible_values(["version", "published", "size"])
+                            .default_value("version"),
+                    )
+                    .arg(
+                        Arg::new(ArgName::REVERSE)
+                            .short('r')
+                            .long("reverse")
+                            .help("List in reverse order")
+                            .takes_value(false),
+                    ),
+            )
+            .subcommand(
+                Command::new(SubCommandName::REMOVE)
+                    .arg_required_else_help(true)
+                    .about("Remove installed versions along with their build directories and artifacts")
+                    .after_help(
+                        "The active version, the one whose tree contains the current working \
+                         directory, is never removed. Only version directories inside of the \
+                         cache or the install prefix are deleted.",
+                    )
+                    .short_flag('R')
+                    .arg(
+                        Arg::new(ArgName::SELECTOR)
+                            .help("A version, manifest entry, or version constraint, e.g. v23.0 or \"<24.0\"")
+                            .takes_value(true)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new(ArgName::DRY_RUN)
+                            .long("dry-run")
+                            .help("Show what would be removed without deleting anything")
+                            .takes_value(false),
+                    )
+                    .arg(
+                        Arg::new(ArgName::YES)
+                            .long("yes")
+                            .short('y')
+                            .help("Do not ask for confirmation before deleting")
+                            .takes_value(false),
+                    ),
+            )
+            .subcommand(
+                Command::new(SubCommandName::MANIFEST)
+                    .arg_required_else_help(true)
+                    .subcommand_required(true)
+                    .about("Maintain the manifest of installed versions")
+                    .subcommand(
+                        Command::new(ArgName::RESCAN)
+                            .about("Rebuild the manifest from the source trees and binaries found in the cache")
+                            .arg(
+                                Arg::new(ArgName::DRY_RUN)
+                                    .long("dry-run")
+                                    .help("Only report the differences, without changing the manifest")
+                                    .takes_value(false),
+                            )
+                            .arg(
+                                Arg::new(ArgName::PRUNE)
+                                    .long("prune")
+                                    .help("Drop the entries whose directory no longer exists")
+                                    .takes_value(false),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new(SubCommandName::VERIFY)
+                    .about("Check that installed trees still hold exactly the files they were extracted with")
+                    .arg(
+                        Arg::new(ArgName::VERSION)
+                            .help("A version, manifest entry, or version constraint, every installed version if omitted")
+                            .takes_value(true),
+                    ),
+            )
+            .subcommand(
+                Command::new(SubCommandName::CACHE)
+                    .arg_required_else_help(true)
+                    .subcommand_required(true)
+                    .about("Report and reclaim the disk space taken by the cache")
+                    .subcommand(
+                        Command::new(ArgName::GC)
+                            .about("Delete versions beyond the given limits, pinned and active versions are kept")
+                            .arg(
+                                Arg::new(ArgName::KEEP_LAST)
+                                    .long("keep-last")
+                                    .help("Keep the N newest versions of every blockchain")
+                                    .takes_value(true)
+                                    .value_name("N"),
+                            )
+                            .arg(
+                                Arg::new(ArgName::MAX_SIZE)
+                                    .long("max-size")
+                                    .help("Delete the least recently modified versions until the cache fits, e.g. 20G")
+                                    .takes_value(true)
+                                    .value_name("SIZE"),
+                            )
+                            .arg(
+                                Arg::new(ArgName::MAX_AGE)
+                                    .long("max-age")
+                                    .help("Delete versions not modified for longer than this, e.g. 90d, 2w or 12h")
+                                    .takes_value(true)
+                                    .value_name("AGE"),
+                            )
+                            .arg(
+                                Arg::new(ArgName::DRY_RUN)
+                                    .long("dry-run")
+                                    .help("Show what would be deleted without deleting anything")
+                                    .takes_value(false),
+                            )
+                            .arg(
+                                Arg::new(ArgName::YES)
+                                    .long("yes")
+                                    .short('y')
+                                    .help("Do not ask for confirmation before deleting")
+                                    .takes_value(false),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new(ArgName::DU)
+                            .about("Show the disk usage of the cache per category and per version"),
+                    )
+                    .subcommand(
+                        Command::new(ArgName::PIN)
+                            .about("Never garbage collect the selected versions")
+                            .arg(
+                                Arg::new(ArgName::SELECTOR)
+                                    .help("A version, manifest entry, or version constraint")
+                                    .takes_value(true)
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new(ArgName::UNPIN)
+                            .about("Allow the selected versions to be garbage collected again")
+                            .arg(
+                                Arg::new(ArgName::SELECTOR)
+                                    .help("A version, manifest entry, or version constraint")
+                                    .takes_value(true)
+                                    .required(true),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new(SubCommandName::CONFIG)
+                    .arg_required_else_help(true)
+                    .subcommand_required(true)
+                    .about("Show and change the settings kept in config.yaml")
+                    .subcommand(
+                        Command::new(ArgName::LIST)
+                            .about("Show every setting, its value and where the value comes from"),
+                    )
+                    .subcommand(
+                        Command::new(ArgName::GET)
+                            .about("Show a single setting and where its value comes from")
+                            .arg(
+                                Arg::new(ArgName::KEY)
+                                    .help("The setting, e.g. jobs or upstream.bitcoin.owner")
+                                    .takes_value(true)
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new(ArgName::SET)
+                            .about("Store a setting in config.yaml")
+                            .arg(
+                                Arg::new(ArgName::KEY)
+                                    .help("The setting, e.g. jobs or upstream.bitcoin.owner")
+                                    .takes_value(true)
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new(ArgName::VALUE)
+                                    .help("The new value")
+                                    .takes_value(true)
+                                    .required(true),
+                            ),
                     ),
             )
             .subcommand(
@@ -115,7 +639,12 @@ impl<'e> Cli {
                             ),
             )
             .get_matches();
-        let active_command: ActiveCommand = Self::get_active_command(&m)?;
+        // global arguments are propagated back up to the top level matches
+        let globals = Self::get_modifiers(
+            &m,
+            &[ArgName::CACHE_DIR, ArgName::INSTALL_PREFIX, ArgName::COLOR],
+        );
+        let active_command: ActiveCommand = Self::get_active_command(&m)?.with_modifiers(globals);
 
         Ok(Self { active_command })
     }
@@ -123,14 +652,25 @@ impl<'e> Cli {
     fn get_active_command(matches: &ArgMatches) -> Result<ActiveCommand, Box<dyn Error>> {
         match matches.subcommand() {
             Some((SubCommandName::AUTH, auth_matches)) => {
+                if auth_matches.is_present(ArgName::STATUS) {
+                    return Ok(ActiveCommand::new(
+                        SubCommandName::AUTH,
+                        Argument {
+                            value: None,
+                            name: ArgName::STATUS.to_string(),
+                        },
+                    ));
+                }
                 let arg = auth_matches.value_of(ArgName::TOKEN).unwrap();
+                let modifiers = Self::get_modifiers(auth_matches, &[ArgName::ENCRYPT]);
                 Ok(ActiveCommand::new(
                     SubCommandName::AUTH,
                     Argument {
                         value: Some(String::from(arg)),
                         name: ArgName::TOKEN.to_string(),
                     },
-                ))
+                )
+                .with_modifiers(modifiers))
             }
             Some((SubCommandName::BUILD, build_matches)) => {
                 let arg = build_matches.value_of(ArgName::STRATEGY).unwrap();
@@ -142,13 +682,15 @@ impl<'e> Cli {
                         column: column!(),
                     }));
                 }
+                let modifiers = Self::get_modifiers(build_matches, &[ArgName::SOURCE]);
                 Ok(ActiveCommand::new(
                     SubCommandName::BUILD,
                     Argument {
                         value: Some(String::from(arg)),
                         name: ArgName::STRATEGY.to_string(),
                     },
-                ))
+                )
+                .with_modifiers(modifiers))
             }
             Some((SubCommandName::FETCH, fetch_matches)) => {
                 let mut active_arg: Argument = Default::default();
@@ -158,14 +700,241 @@ impl<'e> Cli {
                     active_arg.name = ArgName::LIST_LOCAL.to_string();
                 } else if fetch_matches.is_present(ArgName::LATEST) {
                     active_arg.name = ArgName::LATEST.to_string();
-                } else {
-                    let arg = fetch_matches.value_of(ArgName::TAG).unwrap();
+                } else if let Some(arg) = fetch_matches.value_of(ArgName::NOTES) {
+                    active_arg.value = Some(String::from(arg));
+                    active_arg.name = ArgName::NOTES.to_string();
+                } else if let Some(arg) = fetch_matches.value_of(ArgName::VERSIONS) {
+                    active_arg.value = Some(String::from(arg));
+                    active_arg.name = ArgName::VERSIONS.to_string();
+                } else if let Some(arg) = fetch_matches.value_of(ArgName::DEPENDS) {
+                    active_arg.value = Some(String::from(arg));
+                    active_arg.name = ArgName::DEPENDS.to_string();
+                } else if let Some(arg) = fetch_matches.value_of(ArgName::FROM_FILE) {
                     active_arg.value = Some(String::from(arg));
+                    active_arg.name = ArgName::FROM_FILE.to_string();
+                } else if let Some(arg) = fetch_matches.value_of(ArgName::SOURCE) {
+                    active_arg.value = Some(String::from(arg));
+                    active_arg.name = ArgName::SOURCE.to_string();
+                } else if let Some(arg) = fetch_matches.value_of(ArgName::REF) {
+                    active_arg.value = Some(String::from(arg));
+                    active_arg.name = ArgName::REF.to_string();
+                } else if let Some(arg) = fetch_matches.value_of(ArgName::PR) {
+                    active_arg.value = Some(String::from(arg));
+                    active_arg.name = ArgName::PR.to_string();
+                } else {
+                    // several tags are passed on as one comma separated value
+                    let tags: Vec<&str> = fetch_matches.values_of(ArgName::TAG).unwrap().collect();
+                    active_arg.value = Some(tags.join(","));
                     active_arg.name = ArgName::TAG.to_string();
                 }
-                Ok(ActiveCommand::new(SubCommandName::FETCH, active_arg))
+                let modifiers = Self::get_modifiers(
+                    fetch_matches,
+                    &[
+                        ArgName::KEYRING,
+                        ArgName::THRESHOLD,
+                        ArgName::SHA256,
+                        ArgName::SUMS,
+                        ArgName::SIGNATURE,
+                        ArgName::AS,
+                        ArgName::GIT,
+                        ArgName::SHALLOW,
+                        ArgName::PREBUILT,
+                        ArgName::PLATFORM,
+                        ArgName::JOBS,
+                        ArgName::MARKDOWN,
+                        ArgName::OFFLINE,
+                        ArgName::REFRESH,
+                        ArgName::FORCE,
+                    ],
+                );
+                Ok(ActiveCommand::new(SubCommandName::FETCH, active_arg).with_modifiers(modifiers))
             }
 
+            Some((SubCommandName::BUNDLE, bundle_matches)) => match bundle_matches.subcommand() {
+                Some((ArgName::CREATE, create_matches)) => {
+                    let modifiers = Self::get_modifiers(
+                        create_matches,
+                        &[
+                            ArgName::TAG,
+                            ArgName::PROFILE,
+                            ArgName::OUTPUT,
+                            ArgName::SHA256,
+                        ],
+                    );
+                    Ok(ActiveCommand::new(
+                        SubCommandName::BUNDLE,
+                        Argument {
+                            value: None,
+                            name: ArgName::CREATE.to_string(),
+                        },
+                    )
+                    .with_modifiers(modifiers))
+                }
+                Some((ArgName::INSTALL, install_matches)) => {
+                    let arg = install_matches.value_of(ArgName::BUNDLE_FILE).unwrap();
+                    let modifiers = Self::get_modifiers(
+                        install_matches,
+                        &[ArgName::KEYRING, ArgName::THRESHOLD, ArgName::FORCE],
+                    );
+                    Ok(ActiveCommand::new(
+                        SubCommandName::BUNDLE,
+                        Argument {
+                            value: Some(String::from(arg)),
+                            name: ArgName::INSTALL.to_string(),
+                        },
+                    )
+                    .with_modifiers(modifiers))
+                }
+                _ => unreachable!(),
+            },
+
+            Some((SubCommandName::NOTES, notes_matches)) => {
+                let arg = notes_matches.value_of(ArgName::RANGE).unwrap();
+                let modifiers = Self::get_modifiers(
+                    notes_matches,
+                    &[
+                        ArgName::MARKDOWN,
+                        ArgName::SUMMARY,
+                        ArgName::OFFLINE,
+                        ArgName::REFRESH,
+                    ],
+                );
+                Ok(ActiveCommand::new(
+                    SubCommandName::NOTES,
+                    Argument {
+                        value: Some(String::from(arg)),
+                        name: ArgName::RANGE.to_string(),
+                    },
+                )
+                .with_modifiers(modifiers))
+            }
+
+            Some((SubCommandName::LS, ls_matches)) => {
+                let modifiers = Self::get_modifiers(
+                    ls_matches,
+                    &[
+                        ArgName::BLOCKCHAIN,
+                        ArgName::VERSIONS,
+                        ArgName::SOURCE_TYPE,
+                        ArgName::BUILT,
+                        ArgName::NOT_BUILT,
+                        ArgName::SORT,
+                        ArgName::REVERSE,
+                    ],
+                );
+                Ok(ActiveCommand::new(SubCommandName::LS, Default::default())
+                    .with_modifiers(modifiers))
+            }
+
+            Some((SubCommandName::REMOVE, remove_matches)) => {
+                let arg = remove_matches.value_of(ArgName::SELECTOR).unwrap();
+                let modifiers =
+                    Self::get_modifiers(remove_matches, &[ArgName::DRY_RUN, ArgName::YES]);
+                Ok(ActiveCommand::new(
+                    SubCommandName::REMOVE,
+                    Argument {
+                        value: Some(String::from(arg)),
+                        name: ArgName::SELECTOR.to_string(),
+                    },
+                )
+                .with_modifiers(modifiers))
+            }
+
+            Some((SubCommandName::MANIFEST, manifest_matches)) => {
+                match manifest_matches.subcommand() {
+                    Some((ArgName::RESCAN, rescan_matches)) => {
+                        let modifiers = Self::get_modifiers(
+                            rescan_matches,
+                            &[ArgName::DRY_RUN, ArgName::PRUNE],
+                        );
+                        Ok(ActiveCommand::new(
+                            SubCommandName::MANIFEST,
+                            Argument {
+                                value: None,
+                                name: ArgName::RESCAN.to_string(),
+                            },
+                        )
+                        .with_modifiers(modifiers))
+                    }
+                    _ => unreachable!(),
+                }
+            }
+
+            Some((SubCommandName::VERIFY, verify_matches)) => Ok(ActiveCommand::new(
+                SubCommandName::VERIFY,
+                Argument {
+                    value: verify_matches.value_of(ArgName::VERSION).map(String::from),
+                    name: ArgName::VERSION.to_string(),
+                },
+            )),
+
+            Some((SubCommandName::CACHE, cache_matches)) => match cache_matches.subcommand() {
+                Some((ArgName::GC, gc_matches)) => {
+                    let modifiers = Self::get_modifiers(
+                        gc_matches,
+                        &[
+                            ArgName::KEEP_LAST,
+                            ArgName::MAX_SIZE,
+                            ArgName::MAX_AGE,
+                            ArgName::DRY_RUN,
+                            ArgName::YES,
+                        ],
+                    );
+                    Ok(ActiveCommand::new(
+                        SubCommandName::CACHE,
+                        Argument {
+                            value: None,
+                            name: ArgName::GC.to_string(),
+                        },
+                    )
+                    .with_modifiers(modifiers))
+                }
+                Some((ArgName::DU, _)) => Ok(ActiveCommand::new(
+                    SubCommandName::CACHE,
+                    Argument {
+                        value: None,
+                        name: ArgName::DU.to_string(),
+                    },
+                )),
+                Some((name, pin_matches)) => Ok(ActiveCommand::new(
+                    SubCommandName::CACHE,
+                    Argument {
+                        value: pin_matches.value_of(ArgName::SELECTOR).map(String::from),
+                        name: name.to_string(),
+                    },
+                )),
+                _ => unreachable!(),
+            },
+
+            Some((SubCommandName::CONFIG, config_matches)) => match config_matches.subcommand() {
+                Some((ArgName::LIST, _)) => Ok(ActiveCommand::new(
+                    SubCommandName::CONFIG,
+                    Argument {
+                        value: None,
+                        name: ArgName::LIST.to_string(),
+                    },
+                )),
+                Some((ArgName::GET, get_matches)) => Ok(ActiveCommand::new(
+                    SubCommandName::CONFIG,
+                    Argument {
+                        value: get_matches.value_of(ArgName::KEY).map(String::from),
+                        name: ArgName::GET.to_string(),
+                    },
+                )),
+                Some((ArgName::SET, set_matches)) => {
+                    let modifiers = Self::get_modifiers(set_matches, &[ArgName::VALUE]);
+                    Ok(ActiveCommand::new(
+                        SubCommandName::CONFIG,
+                        Argument {
+                            value: set_matches.value_of(ArgName::KEY).map(String::from),
+                            name: ArgName::SET.to_string(),
+                        },
+                    )
+                    .with_modifiers(modifiers))
+                }
+                _ => unreachable!(),
+            },
+
             Some((SubCommandName::GENERATE, generate_matches)) => {
                 let mut active_arg: Argument = Default::default();
                 if generate_matches.is_present(ArgName::BITCOIN) {
@@ -179,28 +948,86 @@ impl<'e> Cli {
         }
     }
 
+    fn get_modifiers(matches: &ArgMatches, names: &[&str]) -> Vec<Argument> {
+        let mut modifiers: Vec<Argument> = Vec::new();
+        for name in names {
+            if matches.is_present(name) {
+                modifiers.push(Argument {
+                    value: matches.value_of(name).map(String::from),
+                    name: name.to_string(),
+                });
+            }
+        }
+        modifiers
+    }
+
     #[inline(always)]
     pub fn subcommand_auth(&self) -> bool {
-        &self.active_command.sub_command() == &SubCommandName::AUTH
+        self.active_command.sub_command() == SubCommandName::AUTH
     }
 
     #[inline(always)]
     pub fn subcommand_build(&self) -> bool {
-        &self.active_command.sub_command() == &SubCommandName::BUILD
+        self.active_command.sub_command() == SubCommandName::BUILD
     }
 
     #[inline(always)]
     pub fn subcommand_fetch(&self) -> bool {
-        &self.active_command.sub_command() == &SubCommandName::FETCH
+        self.active_command.sub_command() == SubCommandName::FETCH
+    }
+
+    #[inline(always)]
+    pub fn subcommand_bundle(&self) -> bool {
+        self.active_command.sub_command() == SubCommandName::BUNDLE
+    }
+
+    #[inline(always)]
+    pub fn subcommand_notes(&self) -> bool {
+        self.active_command.sub_command() == SubCommandName::NOTES
+    }
+
+    #[inline(always)]
+    pub fn subcommand_ls(&self) -> bool {
+        self.active_command.sub_command() == SubCommandName::LS
+    }
+
+    #[inline(always)]
+    pub fn subcommand_remove(&self) -> bool {
+        self.active_command.sub_command() == SubCommandName::REMOVE
+    }
+
+    #[inline(always)]
+    pub fn subcommand_manifest(&self) -> bool {
+        self.active_command.sub_command() == SubCommandName::MANIFEST
+    }
+
+    #[inline(always)]
+    pub fn subcommand_verify(&self) -> bool {
+        self.active_command.sub_command() == SubCommandName::VERIFY
+    }
+
+    #[inline(always)]
+    pub fn subcommand_cache(&self) -> bool {
+        self.active_command.sub_command() == SubCommandName::CACHE
+    }
+
+    #[inline(always)]
+    pub fn subcommand_config(&self) -> bool {
+        self.active_command.sub_command() == SubCommandName::CONFIG
     }
 
     #[inline(always)]
     pub fn subcommand_generate(&self) -> bool {
-        &self.active_command.sub_command() == &SubCommandName::GENERATE
+        self.active_command.sub_command() == SubCommandName::GENERATE
     }
 
     #[inline(always)]
     pub fn args(&self) -> Argument {
         self.active_command.arg()
     }
+
+    #[inline(always)]
+    pub fn modifier(&self, name: &str) -> Option<Argument> {
+        self.active_command.modifier(name)
+    }
 }
@@ -5,6 +5,7 @@ pub enum ShranFile {
     BitcoinBuildLog,
     BitcoinBuildConfig,
     ManifestFile,
+    ConfigFile,
 }
 
 pub struct ShranDefault;
@@ -14,10 +15,35 @@ impl<'a> ShranDefault {
     pub const GH_TOKEN_FILENAME: &'a str = "gh.yaml";
     pub const BUILD_CONFIG_FILENAME: &'a str = "build.yaml";
     pub const BUILD_LOG_FILENAME: &'a str = "build.log";
-    pub const BITCOIN_BASE_URL: &'a str = "https://github.com/bitcoin/bitcoin/archive/refs/tags";
+    pub const CONFIG_FILENAME: &'a str = "config.yaml";
+    pub const BUNDLE_MANIFEST_FILENAME: &'a str = "bundle.yaml";
+    pub const GITHUB_API_URL: &'a str = "https://api.github.com";
+    pub const GITHUB_ARCHIVE_URL_TEMPLATE: &'a str =
+        "https://github.com/{owner}/{repo}/archive/refs/tags/{tag}.tar.gz";
+    pub const GITHUB_COMMIT_ARCHIVE_URL_TEMPLATE: &'a str =
+        "https://github.com/{owner}/{repo}/archive/{commit}.tar.gz";
+    pub const GITHUB_CLONE_URL_TEMPLATE: &'a str = "https://github.com/{owner}/{repo}.git";
+    pub const BITCOIN_RELEASE_URL: &'a str = "https://bitcoincore.org/bin";
+    pub const DEPENDS_FALLBACK_URL: &'a str = "https://bitcoincore.org/depends-sources";
+    pub const SHA256SUMS_FILENAME: &'a str = "SHA256SUMS";
+    pub const SHA256SUMS_SIGNATURE_FILENAME: &'a str = "SHA256SUMS.asc";
+    pub const GPG_VERIFY_PROGRAM: &'a str = "gpgv";
+    pub const DEFAULT_SIGNATURE_THRESHOLD: usize = 3;
+    pub const FETCH_CONCURRENCY: usize = 4;
+    pub const RATE_LIMIT_WARNING: u64 = 10;
+    pub const RELEASE_CACHE_TTL_SECS: i64 = 6 * 60 * 60;
+    pub const RATE_LIMIT_MAX_WAIT_SECS: i64 = 120;
+    pub const MIRROR_CONNECT_TIMEOUT_SECS: u64 = 15;
+    pub const MIRROR_LOW_SPEED_BYTES: u32 = 1024;
+    pub const MIRROR_LOW_SPEED_SECS: u64 = 30;
     pub const FILE_EXTENSION: &'a str = ".tar.gz";
     pub const DOWNLOAD_MANIFEST_FILENAME: &'a str = "manifest.yaml";
+    pub const MANIFEST_SCHEMA_VERSION: u32 = 2;
     pub const SUPPORTED_BLOCKCHAINS: &'a [&'a str] = &["bitcoin"];
+    pub const CACHE_DIR_ENV: &'a str = "SHRAN_CACHE_DIR";
+    pub const INSTALL_PREFIX_ENV: &'a str = "SHRAN_INSTALL_PREFIX";
+    pub const GITHUB_TOKEN_ENVS: &'a [&'a str] = &["SHRAN_GITHUB_TOKEN", "GITHUB_TOKEN"];
+    pub const TOKEN_PASSPHRASE_ENV: &'a str = "SHRAN_TOKEN_PASSPHRASE";
 
     #[inline(always)]
     pub fn config_dir() -> String {
@@ -27,11 +53,46 @@ impl<'a> ShranDefault {
         format!("{}/.config/{}", env::var("HOME").unwrap(), Self::PROGNAME)
     }
 
+    /// The cache, moved by `SHRAN_CACHE_DIR`, see Settings::apply
     #[inline(always)]
     pub fn cache_dir() -> String {
+        match env::var(Self::CACHE_DIR_ENV) {
+            Ok(dir) if !dir.is_empty() => dir,
+            _ => Self::default_cache_dir(),
+        }
+    }
+
+    /// The cache unless it was moved, inside of `XDG_CACHE_HOME` if it is set
+    #[inline(always)]
+    pub fn default_cache_dir() -> String {
+        if let Ok(xdg) = env::var("XDG_CACHE_HOME") {
+            return format!("{}/{}", xdg, Self::PROGNAME);
+        }
         format!("{}/.cache/{}", env::var("HOME").unwrap(), Self::PROGNAME)
     }
 
+    /// Source tarballs of the depends system, shared by every version
+    #[inline(always)]
+    pub fn depends_sources_dir() -> String {
+        format!("{}/depends/sources", Self::cache_dir())
+    }
+
+    /// File listings of extracted trees, named after their content hash
+    #[inline(always)]
+    pub fn tree_index_dir() -> String {
+        format!("{}/trees", Self::cache_dir())
+    }
+
+    /// Official release binaries, one versioned prefix per tag and platform,
+    /// moved by `SHRAN_INSTALL_PREFIX`
+    #[inline(always)]
+    pub fn prebuilt_dir() -> String {
+        match env::var(Self::INSTALL_PREFIX_ENV) {
+            Ok(dir) if !dir.is_empty() => dir,
+            _ => format!("{}/prebuilt", Self::cache_dir()),
+        }
+    }
+
     #[inline(always)]
     pub fn build_dir() -> String {
         if let Ok(here) = env::current_dir() {
@@ -59,6 +120,9 @@ impl<'a> ShranDefault {
                     Self::DOWNLOAD_MANIFEST_FILENAME
                 )
             }
+            ShranFile::ConfigFile => {
+                format!("{}/{}", Self::config_dir(), Self::CONFIG_FILENAME)
+            }
         }
     }
 }
@@ -1,3 +1,7 @@
 pub use default::{ShranDefault, ShranFile};
+pub use settings::{ColorMode, SettingSource, Settings};
+pub use shran_config::{ShranConfig, Upstream};
 
 pub mod default;
+pub mod settings;
+pub mod shran_config;
@@ -0,0 +1,528 @@
+use super::{ShranDefault, Upstream};
+use crate::error::ShranError;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+/// When output is styled with ansi colors
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    /// Only when writing to a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorMode::Auto => std::io::stdout().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+impl fmt::Display for ColorMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorMode::Auto => "auto",
+            ColorMode::Always => "always",
+            ColorMode::Never => "never",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = ShranError<'static>;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(config_error(not_a_color_mode(name))),
+        }
+    }
+}
+
+/// Where the value of a setting came from, every layer overrides the ones
+/// before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingSource {
+    Default,
+    /// `config.yaml` in the config directory
+    ConfigFile,
+    /// The environment variable holding the value
+    Environment(&'static str),
+    /// A flag of the command line
+    CommandLine,
+}
+
+impl fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingSource::Default => write!(f, "default"),
+            SettingSource::ConfigFile => write!(f, "config file"),
+            SettingSource::Environment(var) => write!(f, "environment {}", var),
+            SettingSource::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// The effective value of a single setting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+    pub source: SettingSource,
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {} ({})", self.key, self.value, self.source)
+    }
+}
+
+/// A setting that can be overridden from the environment and the command line
+struct Layered {
+    key: &'static str,
+    env: &'static str,
+    /// The default, given the settings resolved before this one
+    default: fn(&[Setting]) -> String,
+    /// Why the value does not fit the setting, if it does not
+    check: fn(&str) -> Result<(), String>,
+}
+
+const LAYERED: &[Layered] = &[
+    Layered {
+        key: Settings::JOBS,
+        env: "SHRAN_JOBS",
+        default: default_jobs,
+        check: check_jobs,
+    },
+    Layered {
+        key: Settings::CACHE_DIR,
+        env: ShranDefault::CACHE_DIR_ENV,
+        default: default_cache_dir,
+        check: check_dir,
+    },
+    Layered {
+        key: Settings::INSTALL_PREFIX,
+        env: ShranDefault::INSTALL_PREFIX_ENV,
+        default: default_install_prefix,
+        check: check_dir,
+    },
+    Layered {
+        key: Settings::PROFILE,
+        env: "SHRAN_PROFILE",
+        default: default_profile,
+        check: check_profile,
+    },
+    Layered {
+        key: Settings::COLORS,
+        env: "SHRAN_COLORS",
+        default: default_colors,
+        check: check_colors,
+    },
+];
+
+/// The scalar fields of an upstream, configurable as `upstream.<blockchain>.<field>`
+const UPSTREAM_FIELDS: &[&str] = &[
+    "owner",
+    "repo",
+    "api_base_url",
+    "archive_url_template",
+    "commit_archive_url_template",
+    "clone_url_template",
+    "binaries_url",
+];
+
+fn default_jobs(_: &[Setting]) -> String {
+    ShranDefault::FETCH_CONCURRENCY.to_string()
+}
+
+fn default_cache_dir(_: &[Setting]) -> String {
+    ShranDefault::default_cache_dir()
+}
+
+fn default_install_prefix(resolved: &[Setting]) -> String {
+    let cache_dir = resolved
+        .iter()
+        .find(|setting| setting.key == Settings::CACHE_DIR)
+        .map(|setting| setting.value.clone())
+        .unwrap_or_else(ShranDefault::default_cache_dir);
+    format!("{}/prebuilt", cache_dir)
+}
+
+fn default_profile(_: &[Setting]) -> String {
+    String::from("default")
+}
+
+fn default_colors(_: &[Setting]) -> String {
+    ColorMode::default().to_string()
+}
+
+fn check_jobs(value: &str) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(()),
+        _ => Err(format!(
+            "{} is not a number of jobs, expected a whole number of at least 1",
+            value
+        )),
+    }
+}
+
+fn check_dir(value: &str) -> Result<(), String> {
+    if value.starts_with('/') {
+        return Ok(());
+    }
+    Err(format!("{} is not an absolute path", value))
+}
+
+fn check_profile(value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Err("the profile can not be empty".to_string());
+    }
+    Ok(())
+}
+
+fn check_colors(value: &str) -> Result<(), String> {
+    match value.parse::<ColorMode>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(not_a_color_mode(value)),
+    }
+}
+
+fn not_a_color_mode(name: &str) -> String {
+    format!(
+        "{} is not a color mode, expected auto, always or never",
+        name
+    )
+}
+
+/// Every setting of shran together with where its value came from. A value
+/// is taken from the first of these that has one:
+///
+/// 1. a flag of the command line, [e.g.] `--jobs 8` or `--cache-dir /srv/shran`
+/// 2. an environment variable, [e.g.] `SHRAN_JOBS` or `SHRAN_CACHE_DIR`
+/// 3. `config.yaml` in the config directory
+/// 4. the default, the cache defaults to `$XDG_CACHE_HOME/shran`
+///
+/// The upstream repositories are only read from `config.yaml`.
+///
+/// # Example
+///
+/// ```ignore
+/// let settings = Settings::load(&config_file, |var| env::var(var).ok(), &flags)?;
+/// settings.apply();
+/// let jobs = settings.jobs();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    settings: Vec<Setting>,
+}
+
+impl<'a> Settings {
+    pub const JOBS: &'a str = "jobs";
+    pub const CACHE_DIR: &'a str = "cache_dir";
+    pub const INSTALL_PREFIX: &'a str = "install_prefix";
+    pub const PROFILE: &'a str = "profile";
+    pub const COLORS: &'a str = "colors";
+
+    /// Resolve every setting from the config file at `path`, a missing file
+    /// is an empty one
+    ///
+    /// # Params
+    /// 1. path: The config file
+    /// 2. env: Looks up an environment variable
+    /// 3. flags: The settings given on the command line, by key
+    ///
+    /// # Errors
+    /// Returns ShranError::ConfigError if the config file can not be read, or
+    /// a value does not fit its setting
+    pub fn load(
+        path: &str,
+        env: impl Fn(&str) -> Option<String>,
+        flags: &[(&str, String)],
+    ) -> Result<Self, ShranError<'static>> {
+        Self::resolve(&read_config_value(path)?, env, flags)
+    }
+
+    /// Same as Settings::load, taking the contents of the config file
+    pub fn resolve(
+        file: &Value,
+        env: impl Fn(&str) -> Option<String>,
+        flags: &[(&str, String)],
+    ) -> Result<Self, ShranError<'static>> {
+        let mut settings: Vec<Setting> = Vec::new();
+        for layered in LAYERED {
+            let mut setting = Setting {
+                key: layered.key.to_string(),
+                value: (layered.default)(&settings),
+                source: SettingSource::Default,
+            };
+            if let Some(value) = lookup(file, layered.key) {
+                setting.value = value;
+                setting.source = SettingSource::ConfigFile;
+            }
+            if let Some(value) = env(layered.env).filter(|value| !value.is_empty()) {
+                setting.value = value;
+                setting.source = SettingSource::Environment(layered.env);
+            }
+            if let Some((_, value)) = flags.iter().find(|(key, _)| *key == layered.key) {
+                setting.value = value.clone();
+                setting.source = SettingSource::CommandLine;
+            }
+            (layered.check)(&setting.value).map_err(|e| {
+                config_error(format!(
+                    "{} from the {}: {}",
+                    setting.key, setting.source, e
+                ))
+            })?;
+            settings.push(setting);
+        }
+
+        let defaults = serde_yaml::to_value(Upstream::default()).unwrap_or_default();
+        for blockchain in ShranDefault::SUPPORTED_BLOCKCHAINS {
+            for field in UPSTREAM_FIELDS {
+                let key = format!("upstream.{}.{}", blockchain, field);
+                let setting = match lookup(file, &key) {
+                    Some(value) => Setting {
+                        key,
+                        value,
+                        source: SettingSource::ConfigFile,
+                    },
+                    None => Setting {
+                        key,
+                        value: lookup(&defaults, field).unwrap_or_default(),
+                        source: SettingSource::Default,
+                    },
+                };
+                settings.push(setting);
+            }
+        }
+        Ok(Self { settings })
+    }
+
+    /// Every setting, in a fixed order
+    pub fn list(&self) -> &[Setting] {
+        &self.settings
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Setting> {
+        self.settings.iter().find(|setting| setting.key == key)
+    }
+
+    fn value(&self, key: &str) -> &str {
+        self.get(key)
+            .map(|setting| setting.value.as_str())
+            .unwrap_or_default()
+    }
+
+    /// How many versions are downloaded at the same time
+    pub fn jobs(&self) -> usize {
+        self.value(Self::JOBS)
+            .parse()
+            .unwrap_or(ShranDefault::FETCH_CONCURRENCY)
+    }
+
+    /// The build profile of new bundles
+    pub fn profile(&self) -> String {
+        self.value(Self::PROFILE).to_string()
+    }
+
+    pub fn colors(&self) -> ColorMode {
+        self.value(Self::COLORS).parse().unwrap_or_default()
+    }
+
+    /// Make the cache location and install prefix visible to
+    /// ShranDefault::cache_dir and ShranDefault::prebuilt_dir, has to be
+    /// called before anything is read from or written to the cache. Changing
+    /// the environment is not thread safe, so this has to run before any
+    /// other thread, [e.g.] of the async runtime, is started
+    pub fn apply(&self) {
+        for (key, var) in [
+            (Self::CACHE_DIR, ShranDefault::CACHE_DIR_ENV),
+            (Self::INSTALL_PREFIX, ShranDefault::INSTALL_PREFIX_ENV),
+        ] {
+            if let Some(setting) = self.get(key) {
+                if setting.source != SettingSource::Default {
+                    env::set_var(var, &setting.value);
+                }
+            }
+        }
+    }
+}
+
+/// Store `value` under the dotted `key` of a config file's contents. Values
+/// are checked against their setting, and numbers are stored as numbers.
+///
+/// # Errors
+/// Returns ShranError::ConfigError if the key is not a setting, or the value
+/// does not fit it
+pub fn set_config_value(
+    file: &mut Value,
+    key: &str,
+    value: &str,
+) -> Result<(), ShranError<'static>> {
+    let layered = LAYERED.iter().find(|layered| layered.key == key);
+    let upstream_key = match key.split('.').collect::<Vec<&str>>()[..] {
+        ["upstream", blockchain, field] => {
+            ShranDefault::SUPPORTED_BLOCKCHAINS.contains(&blockchain)
+                && UPSTREAM_FIELDS.contains(&field)
+        }
+        _ => false,
+    };
+    match layered {
+        Some(layered) => (layered.check)(value).map_err(config_error)?,
+        None if upstream_key => {}
+        None => return Err(config_error(format!("{} is not a setting", key))),
+    }
+    let scalar = match value.parse::<u64>() {
+        Ok(number) if key == Settings::JOBS => Value::from(number),
+        _ => Value::from(value),
+    };
+
+    if !matches!(file, Value::Mapping(_)) {
+        *file = Value::Mapping(Mapping::new());
+    }
+    let mut node = file;
+    let parts: Vec<&str> = key.split('.').collect();
+    for part in &parts[..parts.len() - 1] {
+        let mapping = match node {
+            Value::Mapping(mapping) => mapping,
+            _ => unreachable!(),
+        };
+        let child = mapping
+            .entry(Value::from(*part))
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if !matches!(child, Value::Mapping(_)) {
+            *child = Value::Mapping(Mapping::new());
+        }
+        node = child;
+    }
+    if let Value::Mapping(mapping) = node {
+        mapping.insert(Value::from(parts[parts.len() - 1]), scalar);
+    }
+    Ok(())
+}
+
+/// The contents of the config file at `path`, missing and empty files are
+/// an empty mapping
+///
+/// # Errors
+/// Returns ShranError::ConfigError if the file can not be read, or is not yaml
+pub fn read_config_value(path: &str) -> Result<Value, ShranError<'static>> {
+    let yaml = match fs::read_to_string(path) {
+        Ok(yaml) => yaml,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(config_error(format!("{}: {}", path, e))),
+    };
+    if yaml.trim().is_empty() {
+        return Ok(Value::Mapping(Mapping::new()));
+    }
+    serde_yaml::from_str(&yaml).map_err(|e| config_error(format!("{}: {}", path, e)))
+}
+
+/// The scalar at a dotted key [e.g.] `upstream.bitcoin.owner`
+fn lookup(value: &Value, key: &str) -> Option<String> {
+    let mut node = value;
+    for part in key.split('.') {
+        node = match node {
+            Value::Mapping(mapping) => mapping.get(&Value::from(part))?,
+            _ => return None,
+        };
+    }
+    match node {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn config_error(msg: String) -> ShranError<'static> {
+    ShranError::ConfigError {
+        msg,
+        file: file!(),
+        line: line!(),
+        column: column!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_config_value, ColorMode, SettingSource, Settings};
+    use crate::config::ShranConfig;
+    use serde_yaml::Value;
+
+    #[test]
+    fn test_settings_are_layered() {
+        let file: Value = serde_yaml::from_str(
+            "jobs: 2\ncache_dir: /srv/shran\nprofile: rpc\nupstream:\n  bitcoin:\n    owner: fork\n",
+        )
+        .unwrap();
+        let env = |var: &str| match var {
+            "SHRAN_JOBS" => Some("6".to_string()),
+            "SHRAN_COLORS" => Some("never".to_string()),
+            "SHRAN_PROFILE" => Some(String::new()),
+            _ => None,
+        };
+        let flags = [(Settings::JOBS, "8".to_string())];
+        let settings = Settings::resolve(&file, env, &flags).unwrap();
+
+        assert_eq!(settings.jobs(), 8);
+        assert_eq!(
+            settings.get(Settings::JOBS).unwrap().source,
+            SettingSource::CommandLine
+        );
+        assert_eq!(settings.colors(), ColorMode::Never);
+        assert_eq!(
+            settings.get(Settings::COLORS).unwrap().source,
+            SettingSource::Environment("SHRAN_COLORS")
+        );
+        // an empty variable does not override anything
+        assert_eq!(settings.profile(), "rpc");
+        // the install prefix follows the cache
+        let prefix = settings.get(Settings::INSTALL_PREFIX).unwrap();
+        assert_eq!(prefix.value, "/srv/shran/prebuilt");
+        assert_eq!(prefix.source, SettingSource::Default);
+        assert_eq!(
+            settings.get("upstream.bitcoin.owner").unwrap().to_string(),
+            "upstream.bitcoin.owner = fork (config file)"
+        );
+        assert_eq!(
+            settings.get("upstream.bitcoin.repo").unwrap().source,
+            SettingSource::Default
+        );
+
+        let bad = [(Settings::JOBS, "many".to_string())];
+        let error = Settings::resolve(&file, |_| None, &bad).unwrap_err();
+        assert!(error.to_string().contains("jobs from the command line"));
+    }
+
+    #[test]
+    fn test_set_config_value() {
+        let mut file: Value =
+            serde_yaml::from_str("upstream:\n  bitcoin:\n    mirrors: []\n").unwrap();
+        set_config_value(&mut file, Settings::JOBS, "3").unwrap();
+        set_config_value(&mut file, "upstream.bitcoin.owner", "fork").unwrap();
+        assert!(set_config_value(&mut file, "jobs", "0").is_err());
+        assert!(set_config_value(&mut file, "cache_dir", "relative/dir").is_err());
+        assert!(set_config_value(&mut file, "upstream.bitcoin.mirrors", "x").is_err());
+        assert!(set_config_value(&mut file, "color", "never").is_err());
+
+        let config: ShranConfig = serde_yaml::from_value(file).unwrap();
+        assert_eq!(config.jobs, Some(3));
+        assert_eq!(config.upstream("bitcoin").owner, "fork");
+        assert!(config.upstream("bitcoin").mirrors.is_empty());
+    }
+}
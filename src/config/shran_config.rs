@@ -0,0 +1,286 @@
+use super::{ColorMode, ShranDefault};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A location to fall back to when the upstream archive can not be downloaded.
+/// Every mirror has to serve the exact same file, as each candidate is checked
+/// against the same expected checksum.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Mirror {
+    /// Any url template, using the same placeholders as the archive url template
+    Http { url: String },
+    /// A local directory containing `<tag>.tar.gz` files
+    Directory { path: String },
+    /// The bitcoincore.org layout, `<url>/bitcoin-core-<version>/bitcoin-<version>.tar.gz`
+    Bitcoincore { url: String },
+}
+
+impl Mirror {
+    /// The url this mirror would serve a given release tag from
+    pub fn url(&self, upstream: &Upstream, tag: &str) -> String {
+        let version = tag.trim_start_matches('v');
+        match self {
+            Mirror::Http { url } => upstream.expand(url, tag),
+            Mirror::Directory { path } => {
+                let path = path.trim_start_matches("file://").trim_end_matches('/');
+                format!("file://{}/{}{}", path, tag, ShranDefault::FILE_EXTENSION)
+            }
+            Mirror::Bitcoincore { url } => format!(
+                "{}/bitcoin-core-{}/bitcoin-{}{}",
+                url.trim_end_matches('/'),
+                version,
+                version,
+                ShranDefault::FILE_EXTENSION
+            ),
+        }
+    }
+}
+
+/// Describes where the source code of a blockchain lives. Defaults to
+/// the official bitcoin repository on github.com, but can point to forks,
+/// internal mirrors, or a GitHub Enterprise instance.
+///
+/// The archive url template understands the placeholders
+/// `{owner}`, `{repo}`, `{tag}` and `{version}` (the tag without a leading `v`),
+/// the commit archive url template `{owner}`, `{repo}` and `{commit}`, and the
+/// clone url template `{owner}` and `{repo}`. Prebuilt binaries are taken
+/// from `binaries_url`, laid out like bitcoincore.org/bin.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Upstream {
+    pub owner: String,
+    pub repo: String,
+    pub api_base_url: String,
+    pub archive_url_template: String,
+    pub commit_archive_url_template: String,
+    pub clone_url_template: String,
+    pub binaries_url: String,
+    pub mirrors: Vec<Mirror>,
+}
+
+impl Default for Upstream {
+    fn default() -> Self {
+        Self {
+            owner: String::from("bitcoin"),
+            repo: String::from("bitcoin"),
+            api_base_url: ShranDefault::GITHUB_API_URL.to_string(),
+            archive_url_template: ShranDefault::GITHUB_ARCHIVE_URL_TEMPLATE.to_string(),
+            commit_archive_url_template: ShranDefault::GITHUB_COMMIT_ARCHIVE_URL_TEMPLATE
+                .to_string(),
+            clone_url_template: ShranDefault::GITHUB_CLONE_URL_TEMPLATE.to_string(),
+            binaries_url: ShranDefault::BITCOIN_RELEASE_URL.to_string(),
+            mirrors: Vec::new(),
+        }
+    }
+}
+
+impl Upstream {
+    /// Expand the archive url template for a given release tag
+    pub fn archive_url(&self, tag: &str) -> String {
+        self.expand(&self.archive_url_template, tag)
+    }
+
+    /// Expand the commit archive url template for a full commit sha
+    pub fn commit_archive_url(&self, sha: &str) -> String {
+        self.commit_archive_url_template
+            .replace("{owner}", &self.owner)
+            .replace("{repo}", &self.repo)
+            .replace("{commit}", sha)
+    }
+
+    /// The url the git backend clones its mirror from
+    pub fn clone_url(&self) -> String {
+        self.clone_url_template
+            .replace("{owner}", &self.owner)
+            .replace("{repo}", &self.repo)
+    }
+
+    /// The directory holding the binaries and SHA256SUMS of a release tag
+    pub fn binaries_url(&self, tag: &str) -> String {
+        format!(
+            "{}/bitcoin-core-{}",
+            self.binaries_url.trim_end_matches('/'),
+            tag.trim_start_matches('v')
+        )
+    }
+
+    /// Every url a release tag can be downloaded from, the upstream archive
+    /// first, followed by the mirrors in the order they were configured
+    pub fn candidate_urls(&self, tag: &str) -> Vec<String> {
+        let mut urls = vec![self.archive_url(tag)];
+        for mirror in &self.mirrors {
+            urls.push(mirror.url(self, tag));
+        }
+        urls
+    }
+
+    /// The urls of a release tag whose SHA256SUMS is signed, the source
+    /// tarball next to SHA256SUMS first, as it is the only archive the
+    /// signatures cover, followed by the candidate_urls
+    pub fn signed_candidate_urls(&self, tag: &str) -> Vec<String> {
+        let version = tag.trim_start_matches('v');
+        let tarball = format!(
+            "{}/bitcoin-{}{}",
+            self.binaries_url(tag),
+            version,
+            ShranDefault::FILE_EXTENSION
+        );
+        let mut urls = vec![tarball];
+        for url in self.candidate_urls(tag) {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+        urls
+    }
+
+    fn expand(&self, template: &str, tag: &str) -> String {
+        template
+            .replace("{owner}", &self.owner)
+            .replace("{repo}", &self.repo)
+            .replace("{tag}", tag)
+            .replace("{version}", tag.trim_start_matches('v'))
+    }
+}
+
+/// The contents of `~/.config/shran/config.yaml`, every key is optional,
+/// anything missing falls back to the defaults. Except for the upstreams,
+/// the environment and the command line take precedence, see Settings.
+///
+/// # Example
+///
+/// ```yaml
+/// jobs: 8
+/// cache_dir: /srv/shran/cache
+/// install_prefix: /opt/bitcoin
+/// profile: rpc
+/// colors: never
+/// upstream:
+///   bitcoin:
+///     owner: my-org
+///     repo: bitcoin
+///     api_base_url: https://github.example.com/api/v3/
+///     mirrors:
+///       - kind: http
+///         url: http://mirror.internal/{repo}/{tag}.tar.gz
+///       - kind: directory
+///         path: /srv/mirrors/bitcoin
+///       - kind: bitcoincore
+///         url: https://bitcoincore.org/bin
+/// ```
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShranConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colors: Option<ColorMode>,
+    pub upstream: HashMap<String, Upstream>,
+}
+
+impl ShranConfig {
+    /// Returns the configured upstream for a blockchain, or the default
+    /// upstream if none has been configured.
+    pub fn upstream(&self, blockchain: &str) -> Upstream {
+        self.upstream.get(blockchain).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mirror, ShranConfig, Upstream};
+
+    #[test]
+    fn test_default_upstream_archive_url() {
+        let upstream = Upstream::default();
+        assert_eq!(
+            upstream.archive_url("v23.0"),
+            "https://github.com/bitcoin/bitcoin/archive/refs/tags/v23.0.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_default_upstream_commit_archive_url() {
+        let upstream = Upstream::default();
+        assert_eq!(
+            upstream.commit_archive_url("1b2c3d4e"),
+            "https://github.com/bitcoin/bitcoin/archive/1b2c3d4e.tar.gz"
+        );
+        assert_eq!(
+            upstream.clone_url(),
+            "https://github.com/bitcoin/bitcoin.git"
+        );
+    }
+
+    #[test]
+    fn test_binaries_url() {
+        let mut upstream = Upstream::default();
+        assert_eq!(
+            upstream.binaries_url("v25.0"),
+            "https://bitcoincore.org/bin/bitcoin-core-25.0"
+        );
+        upstream.binaries_url = String::from("file:///srv/bin/");
+        assert_eq!(
+            upstream.binaries_url("v25.0"),
+            "file:///srv/bin/bitcoin-core-25.0"
+        );
+        upstream.mirrors = vec![Mirror::Bitcoincore {
+            url: String::from("file:///srv/bin"),
+        }];
+        assert_eq!(
+            upstream.signed_candidate_urls("v25.0"),
+            vec![
+                "file:///srv/bin/bitcoin-core-25.0/bitcoin-25.0.tar.gz".to_string(),
+                upstream.archive_url("v25.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partial_upstream_config() {
+        let yaml = "upstream:\n  bitcoin:\n    owner: fork\n    archive_url_template: http://mirror.local/{repo}-{version}.tar.gz\n";
+        let config: ShranConfig = serde_yaml::from_str(yaml).unwrap();
+        let upstream = config.upstream("bitcoin");
+        assert_eq!(upstream.owner, "fork");
+        assert_eq!(upstream.repo, "bitcoin");
+        assert_eq!(upstream.api_base_url, Upstream::default().api_base_url);
+        assert_eq!(
+            upstream.archive_url("v23.0"),
+            "http://mirror.local/bitcoin-23.0.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_missing_upstream_falls_back_to_default() {
+        let config = ShranConfig::default();
+        assert_eq!(config.upstream("bitcoin"), Upstream::default());
+    }
+
+    #[test]
+    fn test_mirror_candidate_urls() {
+        let yaml = "upstream:\n  bitcoin:\n    mirrors:\n      - kind: directory\n        path: file:///srv/mirror/\n      - kind: bitcoincore\n        url: https://bitcoincore.org/bin\n";
+        let config: ShranConfig = serde_yaml::from_str(yaml).unwrap();
+        let upstream = config.upstream("bitcoin");
+        assert_eq!(
+            upstream.mirrors[0],
+            Mirror::Directory {
+                path: "file:///srv/mirror/".to_string()
+            }
+        );
+        assert_eq!(
+            upstream.candidate_urls("v25.0"),
+            vec![
+                "https://github.com/bitcoin/bitcoin/archive/refs/tags/v25.0.tar.gz",
+                "file:///srv/mirror/v25.0.tar.gz",
+                "https://bitcoincore.org/bin/bitcoin-core-25.0/bitcoin-25.0.tar.gz",
+            ]
+        );
+    }
+}
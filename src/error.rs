@@ -37,4 +37,95 @@ pub enum ShranError<'error> {
         line: u32,
         column: u32,
     },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    SignatureThresholdError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    DownloadError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    GithubRateLimitError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    ReleaseCacheError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    GitError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    SourceSpecError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    VersionConstraintError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    ManifestSchemaError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    ManifestConflictError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    TreeIntegrityError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    CachePolicyError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    ConfigError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
+    #[error("Error: {msg:?}\nFile: {file:?} [{line:?}:{column:?}]")]
+    GithubTokenError {
+        msg: String,
+        file: &'error str,
+        line: u32,
+        column: u32,
+    },
 }
@@ -0,0 +1,185 @@
+use super::{FetchedRelease, GithubClient, ReleaseCache};
+use crate::config::ShranDefault;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// How fetching a single version of a batch went
+#[derive(Debug)]
+pub struct FetchOutcome {
+    pub tag: String,
+    /// The downloaded release, or why it could not be downloaded
+    pub result: Result<FetchedRelease, String>,
+}
+
+impl fmt::Display for FetchOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(fetched) => write!(f, "{:<12} ok      {}", self.tag, fetched.archive.location),
+            Err(e) => write!(f, "{:<12} failed  {}", self.tag, e),
+        }
+    }
+}
+
+/// Downloads and extracts several releases at once, at most `concurrency`
+/// of them at a time. One version failing does not stop the others, every
+/// version gets its own FetchOutcome.
+///
+/// # Example
+/// ```no_run
+/// let batch = BatchFetch::new(gclient, cache).with_concurrency(2);
+/// for outcome in batch.fetch(vec!["v24.1".into(), "v25.0".into()]).await {
+///     println!("{}", outcome);
+/// }
+/// ```
+pub struct BatchFetch {
+    client: Arc<GithubClient>,
+    cache: Arc<ReleaseCache>,
+    concurrency: usize,
+}
+
+impl BatchFetch {
+    /// # Params
+    ///
+    /// 1. client: Shared by every download of the batch
+    /// 2. cache: Releases found in it are not looked up on github again
+    pub fn new(client: GithubClient, cache: ReleaseCache) -> Self {
+        Self {
+            client: Arc::new(client),
+            cache: Arc::new(cache),
+            concurrency: ShranDefault::FETCH_CONCURRENCY,
+        }
+    }
+
+    /// Limit the number of versions downloaded at the same time, at least one
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Fetch every tag, the outcomes are returned in the order of `tags`
+    pub async fn fetch(&self, tags: Vec<String>) -> Vec<FetchOutcome> {
+        let progress = ProgressBar::new(tags.len() as u64);
+        progress.set_style(
+            ProgressStyle::default_bar().template("{bar:40} {pos}/{len} versions {wide_msg}"),
+        );
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+
+        let mut handles = Vec::new();
+        for tag in tags {
+            let client = self.client.clone();
+            let cache = self.cache.clone();
+            let semaphore = semaphore.clone();
+            let progress = progress.clone();
+            handles.push((
+                tag.clone(),
+                tokio::spawn(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    progress.set_message(format!("fetching {}", tag));
+                    let result = fetch_one(client, &cache, tag).await;
+                    progress.inc(1);
+                    result
+                }),
+            ));
+        }
+
+        let mut outcomes: Vec<FetchOutcome> = Vec::new();
+        for (tag, handle) in handles {
+            let result = match handle.await {
+                Ok(result) => result,
+                Err(e) => Err(e.to_string()),
+            };
+            outcomes.push(FetchOutcome { tag, result });
+        }
+        progress.finish_and_clear();
+        outcomes
+    }
+}
+
+/// Look the release up, then download it on the blocking thread pool,
+/// curl would otherwise stall every other fetch on the same worker.
+async fn fetch_one(
+    client: Arc<GithubClient>,
+    cache: &ReleaseCache,
+    tag: String,
+) -> Result<FetchedRelease, String> {
+    let release = match cache.release(&tag) {
+        Some(release) => release.clone(),
+        None => client
+            .tagged_release(&tag)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+    tokio::task::spawn_blocking(move || {
+        client
+            .download_cached_release(release)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchFetch;
+    use crate::config::Upstream;
+    use crate::github::{GitRelease, GithubClient, ReleaseCache};
+    use crate::utils::mock_archive::gzip_tarball;
+    use crate::utils::mock_http::{self, MockResponse};
+    use crate::utils::FileSystemManager;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+
+    fn release(tag: &str) -> GitRelease {
+        GitRelease {
+            name: format!("Bitcoin Core {}", tag),
+            author: "fanquake".to_string(),
+            tag_name: tag.to_string(),
+            release_branch: "master".to_string(),
+            published_at: "2023-05-26 10:00:00 UTC".to_string(),
+            prerelease: false,
+            body: String::new(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_batch_fetch_reports_every_version() {
+        let pid = std::process::id();
+        let tags: Vec<String> = (1..=3).map(|n| format!("v{}.{}", pid, n)).collect();
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        // the last tag is missing upstream
+        for tag in &tags[..2] {
+            routes.insert(
+                format!("/archive/{}.tar.gz", tag),
+                MockResponse::ok(&gzip_tarball(&[(&format!("batch-{}/VERSION", tag), b"1")])),
+            );
+        }
+        let base = mock_http::serve(routes);
+        let upstream = Upstream {
+            archive_url_template: format!("{}/archive/{{tag}}.tar.gz", base),
+            api_base_url: "http://127.0.0.1:9/".to_string(),
+            ..Upstream::default()
+        };
+        let cache = ReleaseCache::new(tags.clone(), tags.iter().map(|tag| release(tag)).collect());
+        let dir = env::temp_dir().join(format!("shran-batch-{}", pid));
+        let fs = FileSystemManager::in_dir(dir.to_str().unwrap()).unwrap();
+        let client = GithubClient::new(None, upstream, fs).unwrap();
+
+        let outcomes = BatchFetch::new(client, cache)
+            .with_concurrency(2)
+            .fetch(tags.clone())
+            .await;
+        let tags_in_order: Vec<&String> = outcomes.iter().map(|outcome| &outcome.tag).collect();
+        assert_eq!(tags_in_order, tags.iter().collect::<Vec<&String>>());
+        assert!(outcomes[0].result.is_ok());
+        assert!(outcomes[1].result.is_ok());
+        assert!(outcomes[2].result.is_err());
+
+        assert!(dir
+            .join(format!("cache/bitcoin/batch-{}", tags[0]))
+            .is_dir());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
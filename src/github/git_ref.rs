@@ -0,0 +1,104 @@
+use crate::utils::ManifestKey;
+use serde::Deserialize;
+use std::fmt;
+
+/// Something other than a release that `fetch` can download,
+/// it is always resolved to an immutable commit first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GitRef {
+    /// A commit sha (full or abbreviated) or a branch name
+    Ref(String),
+    /// The head of a pull request
+    PullRequest(u64),
+}
+
+impl fmt::Display for GitRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitRef::Ref(name) => write!(f, "{}", name),
+            GitRef::PullRequest(number) => write!(f, "pull/{}", number),
+        }
+    }
+}
+
+/// A git ref pinned to the commit it pointed at when it was fetched
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedRef {
+    pub git_ref: GitRef,
+    /// Full sha of the resolved commit
+    pub sha: String,
+    pub committed_at: String,
+    /// Title of the pull request, None for plain refs
+    pub title: Option<String>,
+}
+
+impl ResolvedRef {
+    /// The key the fetch is recorded under in the manifest,
+    /// [e.g.] bitcoin:pull/27260#1b2c3d4e5f60
+    pub fn manifest_key(&self, blockchain: &str) -> ManifestKey {
+        ManifestKey::new(blockchain, &self.git_ref.to_string())
+            .with_variant(&self.sha[..self.sha.len().min(12)])
+    }
+}
+
+impl fmt::Display for ResolvedRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Ref: {}\nCommit: {}\nCommitted: {}",
+            self.git_ref, self.sha, self.committed_at
+        )?;
+        if let Some(title) = &self.title {
+            write!(f, "\nTitle: {}", title)?;
+        }
+        Ok(())
+    }
+}
+
+/// The parts of `GET /repos/{owner}/{repo}/commits/{ref}` shran needs
+#[derive(Deserialize, Debug)]
+pub struct CommitResponse {
+    pub sha: String,
+    pub commit: CommitDetails,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CommitDetails {
+    pub committer: CommitSignature,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CommitSignature {
+    pub date: String,
+}
+
+/// The parts of `GET /repos/{owner}/{repo}/pulls/{number}` shran needs
+#[derive(Deserialize, Debug)]
+pub struct PullRequestResponse {
+    pub title: String,
+    pub head: PullRequestHead,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PullRequestHead {
+    pub sha: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GitRef, ResolvedRef};
+
+    #[test]
+    fn test_resolved_ref_manifest_key() {
+        let resolved = ResolvedRef {
+            git_ref: GitRef::PullRequest(27260),
+            sha: "1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e".to_string(),
+            committed_at: "2023-05-26T10:00:00Z".to_string(),
+            title: None,
+        };
+        assert_eq!(
+            resolved.manifest_key("bitcoin").to_string(),
+            "bitcoin:pull/27260#1b2c3d4e5f60"
+        );
+    }
+}
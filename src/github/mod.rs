@@ -1,3 +1,14 @@
+pub mod batch;
+pub mod git_ref;
+pub mod notes;
+pub mod rate_limit;
+pub mod release_cache;
 pub mod releases;
+pub mod version;
 
-pub use releases::{GitRelease, GithubClient};
+pub use batch::BatchFetch;
+pub use git_ref::{GitRef, ResolvedRef};
+pub use notes::{render_notes, NotesFormat, ReleaseRange};
+pub use release_cache::{CachePolicy, ReleaseCache};
+pub use releases::{FetchedRelease, GitRelease, GithubClient, InstalledArchive};
+pub use version::VersionConstraint;
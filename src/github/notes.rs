@@ -0,0 +1,230 @@
+use super::version::Version;
+use super::{GitRelease, ReleaseCache};
+use crate::error::ShranError;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// How release notes are printed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NotesFormat {
+    /// Markdown markup replaced with plain text and ansi styles
+    Terminal,
+    /// Like Terminal, without ansi styles for output that is not colored
+    Plain,
+    /// The notes as published, ready to be pasted into a document
+    Markdown,
+}
+
+/// A range of releases written like a git range, `v24.0..v26.0` holds every
+/// release after v24.0 up to and including v26.0, which are exactly the notes
+/// worth reading before upgrading from v24.0 to v26.0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReleaseRange {
+    pub from: Version,
+    pub to: Version,
+}
+
+impl ReleaseRange {
+    /// The published releases of the range, oldest first
+    pub fn select<'a>(&self, cache: &'a ReleaseCache) -> Vec<&'a GitRelease> {
+        let mut releases: Vec<(Version, &GitRelease)> = cache
+            .releases
+            .iter()
+            .filter(|release| !release.prerelease)
+            .filter_map(|release| {
+                let version = release.tag_name.parse::<Version>().ok()?;
+                (version > self.from && version <= self.to).then_some((version, release))
+            })
+            .collect();
+        releases.sort_by_key(|(version, _)| *version);
+        releases.into_iter().map(|(_, release)| release).collect()
+    }
+}
+
+impl FromStr for ReleaseRange {
+    type Err = ShranError<'static>;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        match range.split_once("..") {
+            Some((from, to)) => Ok(Self {
+                from: from.parse()?,
+                to: to.parse()?,
+            }),
+            None => Err(ShranError::VersionConstraintError {
+                msg: format!("{}: expected a range like v24.0..v26.0", range),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }),
+        }
+    }
+}
+
+/// Render the notes of one or more releases, each under a heading of its own.
+/// A summary only keeps the section headings of every release.
+pub fn render_notes(releases: &[&GitRelease], format: NotesFormat, summary: bool) -> String {
+    let mut out = String::new();
+    for release in releases {
+        match format {
+            NotesFormat::Markdown => {
+                let _ = writeln!(out, "# {}\n", release.name);
+                let _ = writeln!(
+                    out,
+                    "Tag `{}`, published {}\n",
+                    release.tag_name, release.published_at
+                );
+            }
+            NotesFormat::Terminal => {
+                let _ = writeln!(out, "\x1b[1m{}\x1b[0m ({})", release.name, release.tag_name);
+                let _ = writeln!(out, "Published {}\n", release.published_at);
+            }
+            NotesFormat::Plain => {
+                let _ = writeln!(out, "{} ({})", release.name, release.tag_name);
+                let _ = writeln!(out, "Published {}\n", release.published_at);
+            }
+        }
+        let body = release.body.trim();
+        if body.is_empty() {
+            out.push_str("No release notes published\n\n");
+            continue;
+        }
+        for line in body.lines() {
+            let heading = line.trim_start().starts_with('#');
+            if summary && !heading {
+                continue;
+            }
+            match format {
+                // every release already has a top level heading, so its own
+                // headings are nested one level deeper
+                NotesFormat::Markdown if heading => {
+                    let _ = writeln!(out, "#{}", line.trim_start());
+                }
+                NotesFormat::Markdown => {
+                    let _ = writeln!(out, "{}", line);
+                }
+                NotesFormat::Terminal | NotesFormat::Plain => {
+                    let styled = format == NotesFormat::Terminal;
+                    let _ = writeln!(out, "{}", terminal_line(line, styled));
+                }
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Replace the markdown of a single line with something readable in a
+/// terminal, headings are underlined if the line is `styled`
+fn terminal_line(line: &str, styled: bool) -> String {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') {
+        let title = strip_inline(trimmed.trim_start_matches('#').trim());
+        if !styled {
+            return title;
+        }
+        return format!("\x1b[1;4m{}\x1b[0m", title);
+    }
+    let indent = &line[..line.len() - trimmed.len()];
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = trimmed.strip_prefix(bullet) {
+            return format!("{}  \u{2022} {}", indent, strip_inline(item));
+        }
+    }
+    format!("{}{}", indent, strip_inline(trimmed))
+}
+
+/// Drop emphasis and code markers, and turn `[text](url)` into `text <url>`
+fn strip_inline(text: &str) -> String {
+    let text = text.replace("**", "").replace('`', "");
+    let mut out = String::new();
+    let mut rest = text.as_str();
+    while let Some(open) = rest.find('[') {
+        let link = rest[open..]
+            .find("](")
+            .and_then(|mid| rest[open + mid..].find(')').map(|close| (mid, mid + close)));
+        match link {
+            Some((mid, close)) => {
+                out.push_str(&rest[..open]);
+                let label = &rest[open + 1..open + mid];
+                let url = &rest[open + mid + 2..open + close];
+                let _ = write!(out, "{} <{}>", label, url);
+                rest = &rest[open + close + 1..];
+            }
+            None => break,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_notes, strip_inline, NotesFormat, ReleaseRange};
+    use crate::github::{GitRelease, ReleaseCache};
+
+    fn release(tag: &str, prerelease: bool, body: &str) -> GitRelease {
+        GitRelease {
+            name: format!("Bitcoin Core {}", tag.trim_start_matches('v')),
+            author: "fanquake".to_string(),
+            tag_name: tag.to_string(),
+            release_branch: "master".to_string(),
+            published_at: "2023-05-26 10:00:00 UTC".to_string(),
+            prerelease,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_release_range_selects_oldest_first() {
+        let cache = ReleaseCache::new(
+            Vec::new(),
+            vec![
+                release("v26.0", false, ""),
+                release("v26.0rc1", true, ""),
+                release("v25.1", false, ""),
+                release("v25.0", false, ""),
+                release("v24.0", false, ""),
+            ],
+        );
+        let range: ReleaseRange = "v24.0..v26.0".parse().unwrap();
+        let tags: Vec<&str> = range
+            .select(&cache)
+            .iter()
+            .map(|release| release.tag_name.as_str())
+            .collect();
+        assert_eq!(tags, vec!["v25.0", "v25.1", "v26.0"]);
+        assert!("v24.0".parse::<ReleaseRange>().is_err());
+    }
+
+    #[test]
+    fn test_render_notes() {
+        let notes = release(
+            "v25.0",
+            false,
+            "## Notable changes\n- **P2P** fixes, see [#123](https://github.com/bitcoin/bitcoin/pull/123)\n## Credits\nThanks",
+        );
+        let markdown = render_notes(&[&notes], NotesFormat::Markdown, false);
+        assert!(markdown.starts_with("# Bitcoin Core 25.0\n"));
+        assert!(markdown.contains("\n### Notable changes\n"));
+
+        let summary = render_notes(&[&notes], NotesFormat::Markdown, true);
+        assert!(summary.contains("### Credits"));
+        assert!(!summary.contains("Thanks"));
+
+        let terminal = render_notes(&[&notes], NotesFormat::Terminal, false);
+        assert!(terminal.contains(
+            "  \u{2022} P2P fixes, see #123 <https://github.com/bitcoin/bitcoin/pull/123>"
+        ));
+        assert!(terminal.contains("\x1b[1;4mNotable changes\x1b[0m"));
+
+        let plain = render_notes(&[&notes], NotesFormat::Plain, false);
+        assert!(plain.starts_with("Bitcoin Core 25.0 (v25.0)\n"));
+        assert!(plain.contains("\nNotable changes\n"));
+        assert!(!plain.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_strip_inline_keeps_unbalanced_brackets() {
+        assert_eq!(strip_inline("see [1] and `code`"), "see [1] and code");
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::{TimeZone, Utc};
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use std::fmt;
+
+/// The quota of a single github api resource, as returned by
+/// the `/rate_limit` endpoint
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct RateLimitStatus {
+    pub limit: u64,
+    pub remaining: u64,
+    /// Unix timestamp of when the quota is refilled
+    pub reset: i64,
+}
+
+impl RateLimitStatus {
+    /// Every github api response carries the current quota in its headers,
+    /// returns None if they are missing, e.g. on an Enterprise instance
+    /// with rate limiting disabled.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| -> Option<i64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+        Some(Self {
+            limit: header("x-ratelimit-limit")? as u64,
+            remaining: header("x-ratelimit-remaining")? as u64,
+            reset: header("x-ratelimit-reset")?,
+        })
+    }
+
+    /// Seconds until the quota is refilled
+    pub fn seconds_until_reset(&self) -> i64 {
+        (self.reset - Utc::now().timestamp()).max(0)
+    }
+
+    /// Human readable time of when the quota is refilled
+    pub fn reset_at(&self) -> String {
+        match Utc.timestamp_opt(self.reset, 0).single() {
+            Some(reset) => reset.to_string(),
+            None => self.reset.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RateLimitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "GitHub API quota: {} of {} requests remaining, resets at {}",
+            self.remaining,
+            self.limit,
+            self.reset_at()
+        )
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RateLimitResources {
+    pub core: RateLimitStatus,
+}
+
+/// Response body of `GET /rate_limit`
+#[derive(Deserialize, Debug)]
+pub struct RateLimit {
+    pub resources: RateLimitResources,
+}
+
+/// What `shran auth --status` reports
+#[derive(Debug)]
+pub struct AuthStatus {
+    pub authenticated: bool,
+    /// None if github did not report any scopes, e.g. for fine grained tokens
+    pub scopes: Option<Vec<String>>,
+    pub quota: RateLimitStatus,
+}
+
+impl fmt::Display for AuthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        if !self.authenticated {
+            writeln!(f, "Authenticated: no (anonymous)")?;
+        } else {
+            match &self.scopes {
+                Some(scopes) if scopes.is_empty() => {
+                    writeln!(f, "Authenticated: yes\nToken scopes: none")?
+                }
+                Some(scopes) => {
+                    writeln!(f, "Authenticated: yes\nToken scopes: {}", scopes.join(", "))?
+                }
+                None => writeln!(
+                    f,
+                    "Authenticated: yes\nToken scopes: unknown (fine grained token)"
+                )?,
+            }
+        }
+        write!(f, "{}", self.quota)
+    }
+}
+
+/// Extract the url of the next page from a github `Link` header, e.g.
+/// `<https://api.github.com/repositories/1/tags?page=2>; rel="next", <...>; rel="last"`
+pub fn next_page(link: &str) -> Option<String> {
+    for part in link.split(',') {
+        let mut pieces = part.split(';');
+        let url = pieces.next()?.trim();
+        if pieces.any(|p| p.trim() == "rel=\"next\"") {
+            return Some(
+                url.trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string(),
+            );
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_page, RateLimitStatus};
+    use reqwest::header::HeaderMap;
+
+    #[test]
+    fn test_next_page() {
+        let link = r#"<https://api.github.com/repositories/1/tags?page=2>; rel="next", <https://api.github.com/repositories/1/tags?page=9>; rel="last""#;
+        assert_eq!(
+            next_page(link),
+            Some("https://api.github.com/repositories/1/tags?page=2".to_string())
+        );
+        let last = r#"<https://api.github.com/repositories/1/tags?page=8>; rel="prev""#;
+        assert_eq!(next_page(last), None);
+    }
+
+    #[test]
+    fn test_rate_limit_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(RateLimitStatus::from_headers(&headers), None);
+        headers.insert("x-ratelimit-limit", "60".parse().unwrap());
+        headers.insert("x-ratelimit-remaining", "7".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1700000000".parse().unwrap());
+        let status = RateLimitStatus::from_headers(&headers).unwrap();
+        assert_eq!(status.remaining, 7);
+        assert_eq!(status.seconds_until_reset(), 0);
+    }
+}
@@ -0,0 +1,105 @@
+use super::GitRelease;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// How listing commands should treat the on disk release cache
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CachePolicy {
+    /// Use the cache while it is younger than its time to live
+    Default,
+    /// Never touch the network, answer purely from the cache
+    Offline,
+    /// Ignore the cache and update it from github
+    Refresh,
+}
+
+/// Release metadata of an upstream repository, persisted in
+/// `~/.cache/shran/releases` so that listing works on firewalled hosts.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReleaseCache {
+    /// Unix timestamp of the last update from github
+    pub fetched_at: i64,
+    pub tags: Vec<String>,
+    /// Every published release, newest first
+    pub releases: Vec<GitRelease>,
+}
+
+impl ReleaseCache {
+    pub fn new(tags: Vec<String>, releases: Vec<GitRelease>) -> Self {
+        Self {
+            fetched_at: Utc::now().timestamp(),
+            tags,
+            releases,
+        }
+    }
+
+    /// True if the cache has never been filled
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.fetched_at == 0
+    }
+
+    #[inline(always)]
+    pub fn is_fresh(&self, ttl_secs: i64) -> bool {
+        !self.is_empty() && Utc::now().timestamp() - self.fetched_at < ttl_secs
+    }
+
+    /// The newest release which is not a pre-release, the same
+    /// release github considers to be the latest one
+    pub fn latest(&self) -> Option<&GitRelease> {
+        self.releases.iter().find(|release| !release.prerelease)
+    }
+
+    /// Look up the metadata of a release by its tag
+    pub fn release(&self, tag: &str) -> Option<&GitRelease> {
+        self.releases.iter().find(|release| release.tag_name == tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReleaseCache;
+    use crate::github::GitRelease;
+
+    fn release(tag: &str, prerelease: bool) -> GitRelease {
+        GitRelease {
+            name: format!("Bitcoin Core {}", tag),
+            author: "fanquake".to_string(),
+            tag_name: tag.to_string(),
+            release_branch: "master".to_string(),
+            published_at: "2023-05-26 10:00:00 UTC".to_string(),
+            prerelease,
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_release_cache_latest_skips_prereleases() {
+        let cache = ReleaseCache::new(
+            Vec::new(),
+            vec![release("v26.0rc1", true), release("v25.0", false)],
+        );
+        assert_eq!(cache.latest().unwrap().tag_name, "v25.0");
+        assert!(cache.release("v26.0rc1").unwrap().prerelease);
+    }
+
+    #[test]
+    fn test_release_cache_freshness() {
+        let mut cache = ReleaseCache::default();
+        assert!(cache.is_empty());
+        assert!(!cache.is_fresh(3600));
+        cache = ReleaseCache::new(Vec::new(), Vec::new());
+        assert!(cache.is_fresh(3600));
+        cache.fetched_at -= 7200;
+        assert!(!cache.is_fresh(3600));
+    }
+
+    #[test]
+    fn test_release_cache_roundtrip() {
+        let cache = ReleaseCache::new(vec!["v25.0".to_string()], vec![release("v25.0", false)]);
+        let yaml = serde_yaml::to_string(&cache).unwrap();
+        let deserialized: ReleaseCache = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(cache, deserialized);
+    }
+}
@@ -1,22 +1,49 @@
-use crate::config::ShranDefault;
-use crate::utils::{BlockchainKind, FileSystemManager};
+use super::git_ref::{CommitResponse, PullRequestResponse};
+use super::rate_limit::{self, AuthStatus, RateLimit, RateLimitStatus};
+use super::{CachePolicy, GitRef, ReleaseCache, ResolvedRef};
+use crate::config::{ShranDefault, Upstream};
+use crate::error::ShranError;
+use crate::utils::{redact, BlockchainKind, Downloader, EtagEntry, FileSystemManager, ManifestKey};
+use crate::verify::ThresholdVerifier;
 use chrono::Utc;
-use curl::easy::Easy;
 use octocrab::models::repos::{Release, Tag};
-use octocrab::{Octocrab, Page};
+use octocrab::Octocrab;
+use reqwest::header::{ETAG, IF_NONE_MATCH, LINK};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
 /// Reprents all necessary information about a github repositories
 /// release information, most of this information is taken from
 /// the similar but much larger octocrab Release struct
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct GitRelease {
     pub name: String,
     pub author: String,
     pub tag_name: String,
     pub release_branch: String,
     pub published_at: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    /// The release notes, in markdown
+    #[serde(default)]
+    pub body: String,
+}
+
+impl From<Release> for GitRelease {
+    fn from(release: Release) -> Self {
+        Self {
+            name: release.name.unwrap_or("None".to_string()),
+            author: release.author.login,
+            tag_name: release.tag_name,
+            release_branch: release.target_commitish,
+            published_at: release.published_at.unwrap_or(Utc::now()).to_string(),
+            prerelease: release.prerelease,
+            body: release.body.unwrap_or_default(),
+        }
+    }
 }
 
 impl fmt::Display for GitRelease {
@@ -25,88 +52,373 @@ impl fmt::Display for GitRelease {
             f,
             "Name: {}\nAuthor: {}\nTag: {}\nRelease Branch: {}\nPublished: {}",
             self.name, self.author, self.tag_name, self.release_branch, self.published_at,
+        )?;
+        if self.prerelease {
+            write!(f, "\nPre-release: yes")?;
+        }
+        Ok(())
+    }
+}
+
+/// A source archive that has been downloaded, verified and extracted
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstalledArchive {
+    /// The directory the archive was extracted to
+    pub location: String,
+    /// The url of the candidate which served the archive
+    pub source: String,
+    /// Hex encoded sha256 digest of the archive
+    pub sha256: String,
+}
+
+impl fmt::Display for InstalledArchive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Location: {}\nSource: {}\nSHA256: {}",
+            self.location,
+            redact(&self.source),
+            self.sha256
         )
     }
 }
 
+/// A release together with the archive of its source code
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedRelease {
+    pub release: GitRelease,
+    pub archive: InstalledArchive,
+}
+
+impl fmt::Display for FetchedRelease {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n{}", self.release, self.archive)
+    }
+}
+
 /// A wrapper around around curl and Octocrab, GithubClient exposes
 /// only the necessary functionality to search, verify and download
 /// releases of specified Proof-of-Work Nodes, most notably bitcoin.
 pub struct GithubClient {
     octocrab: Octocrab,
-    easy: Easy,
+    authenticated: bool,
+    upstream: Upstream,
     fs: FileSystemManager,
+    verifier: Option<ThresholdVerifier>,
+    checksum: Option<String>,
 }
 
 impl GithubClient {
-    pub fn new(token: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let octocrab = Octocrab::builder().personal_token(token).build()?;
-        let easy: Easy = Easy::new();
-        let fs = FileSystemManager::new()?;
-
-        Ok(Self { octocrab, easy, fs })
-    }
-
-    fn download_release(mut self, url: &String, file_name: String) -> Result<(), Box<dyn Error>> {
-        let mut file_bytes: Vec<u8> = Vec::new();
-        self.easy.url(url)?;
-        self.easy.follow_location(true)?;
-
-        {
-            let mut transfer = self.easy.transfer();
-            transfer.write_function(|data| {
-                file_bytes.extend_from_slice(data);
-                Ok(data.len())
-            })?;
-            transfer.perform()?;
-        }
-        {
-            self.fs.write_and_extract_blockchain_archive(
-                &file_name,
-                file_bytes,
-                BlockchainKind::Bitcoin,
-            )?;
+    /// Build a client for the repository described by `upstream`,
+    /// which is the official bitcoin repository by default. Without a
+    /// token github is used anonymously, which works for public repositories
+    /// but is limited to 60 requests per hour. The api caches and downloads
+    /// are kept in the directories of `fs`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let fs = FileSystemManager::new()?;
+    /// let upstream = fs.read_config()?.upstream("bitcoin");
+    /// let gclient = GithubClient::new(token, upstream, fs)?;
+    /// ```
+    pub fn new(
+        token: Option<String>,
+        upstream: Upstream,
+        fs: FileSystemManager,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Url::join drops the last path segment of a base url without a
+        // trailing slash, which would break GitHub Enterprise's /api/v3
+        let mut api_base_url = upstream.api_base_url.clone();
+        if !api_base_url.ends_with('/') {
+            api_base_url.push('/');
+        }
+        let mut builder = Octocrab::builder().base_url(api_base_url.as_str())?;
+        let authenticated = token.is_some();
+        if let Some(token) = token {
+            builder = builder.personal_token(token);
         }
+        let octocrab = builder.build()?;
+
+        Ok(Self {
+            octocrab,
+            authenticated,
+            upstream,
+            fs,
+            verifier: None,
+            checksum: None,
+        })
+    }
+
+    /// Require every release to pass a multi signature threshold check
+    /// before its source code is downloaded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let verifier = ThresholdVerifier::new("builders.gpg", 3)?;
+    /// let gclient = GithubClient::new(Some(token), Upstream::default(), FileSystemManager::new()?)?.with_verifier(verifier);
+    /// ```
+    pub fn with_verifier(mut self, verifier: ThresholdVerifier) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Require the downloaded source archive to match a sha256 digest,
+    /// every mirror is checked against it until one matches.
+    pub fn with_checksum(mut self, sha256: String) -> Self {
+        self.checksum = Some(sha256);
+        self
+    }
+
+    /// Checks the remaining api quota before making a request, querying it
+    /// does not count against the quota itself. Warns when the quota is almost
+    /// used up, and fails early once it is exhausted.
+    async fn check_rate_limit(&self) -> Result<(), Box<dyn Error>> {
+        // GitHub Enterprise instances can have rate limiting disabled,
+        // in which case this endpoint returns a 404
+        let rate_limit: RateLimit = match self.octocrab.get("rate_limit", None::<&()>).await {
+            Ok(rate_limit) => rate_limit,
+            Err(_) => return Ok(()),
+        };
+        let core = rate_limit.resources.core;
+        if core.remaining == 0 {
+            return Err(self.rate_limit_error(&core));
+        }
+        self.warn_if_low(&core);
         Ok(())
     }
 
-    fn release_helper(self, release: Release) -> Result<GitRelease, Box<dyn Error>> {
-        let url = format!(
-            "{}/{}{}",
-            ShranDefault::BITCOIN_BASE_URL,
-            release.tag_name,
-            ShranDefault::FILE_EXTENSION
+    fn warn_if_low(&self, quota: &RateLimitStatus) {
+        if quota.remaining <= ShranDefault::RATE_LIMIT_WARNING {
+            eprintln!("Warning: {}{}", quota, self.auth_hint());
+        }
+    }
+
+    fn rate_limit_error(&self, quota: &RateLimitStatus) -> Box<dyn Error> {
+        Box::new(ShranError::GithubRateLimitError {
+            msg: format!("{}{}", quota, self.auth_hint()),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        })
+    }
+
+    /// Sleep until the quota is refilled if that happens soon enough,
+    /// otherwise give up with an error stating when it will be refilled.
+    async fn wait_for_reset(&self, quota: &RateLimitStatus) -> Result<(), Box<dyn Error>> {
+        let wait = quota.seconds_until_reset();
+        if wait > ShranDefault::RATE_LIMIT_MAX_WAIT_SECS {
+            return Err(self.rate_limit_error(quota));
+        }
+        eprintln!(
+            "GitHub API quota exhausted, pausing {}s until {}",
+            wait,
+            quota.reset_at()
         );
+        tokio::time::sleep(Duration::from_secs(wait as u64 + 1)).await;
+        Ok(())
+    }
 
-        let file_name = format!("{}{}", release.tag_name, ShranDefault::FILE_EXTENSION);
-        self.download_release(&url, file_name)?;
+    /// GET a github api url, revalidating a previously cached response with
+    /// its ETag so that unchanged resources do not count against the quota.
+    /// Returns the response body and the url of the next page, if any.
+    async fn conditional_get(&self, url: &str) -> Result<(String, Option<String>), Box<dyn Error>> {
+        loop {
+            let cached: Option<EtagEntry> = self.fs.read_etag_entry(url);
+            let mut request = self.octocrab.request_builder(url, Method::GET);
+            if let Some(entry) = &cached {
+                request = request.header(IF_NONE_MATCH, entry.etag.as_str());
+            }
+            let response = self.octocrab.execute(request).await?;
+            let quota = RateLimitStatus::from_headers(response.headers());
 
-        Ok(GitRelease {
-            name: release.name.unwrap_or("None".to_string()),
-            author: release.author.login,
-            tag_name: release.tag_name,
-            release_branch: release.target_commitish,
-            published_at: release.published_at.unwrap_or(Utc::now()).to_string(),
+            if response.status() == StatusCode::NOT_MODIFIED {
+                if let Some(entry) = cached {
+                    return Ok((entry.body, entry.next));
+                }
+            }
+            if let Some(quota) = &quota {
+                let limited = response.status() == StatusCode::FORBIDDEN
+                    || response.status() == StatusCode::TOO_MANY_REQUESTS;
+                if limited && quota.remaining == 0 {
+                    self.wait_for_reset(quota).await?;
+                    continue;
+                }
+                self.warn_if_low(quota);
+            }
+
+            let response = octocrab::map_github_error(response).await?;
+            let etag = header_string(response.headers().get(ETAG));
+            let next = header_string(response.headers().get(LINK))
+                .and_then(|link| rate_limit::next_page(&link));
+            let body = response.text().await?;
+            if let Some(etag) = etag {
+                let entry = EtagEntry {
+                    etag,
+                    body: body.clone(),
+                    next: next.clone(),
+                };
+                self.fs.write_etag_entry(url, &entry)?;
+            }
+            return Ok((body, next));
+        }
+    }
+
+    /// Show whether the client is authenticated, the scopes of its token,
+    /// and how much of the api quota is left.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let gclient = GithubClient::new(token, Upstream::default(), FileSystemManager::new()?)?;
+    /// println!("{}", gclient.auth_status().await?);
+    /// ```
+    pub async fn auth_status(&self) -> Result<AuthStatus, Box<dyn Error>> {
+        let url = self.octocrab.absolute_url("rate_limit")?;
+        let response = self.octocrab._get(url, None::<&()>).await?;
+        let response = octocrab::map_github_error(response).await?;
+        // classic personal access tokens report their scopes on every response,
+        // fine grained tokens do not have scopes at all
+        let scopes = header_string(response.headers().get("x-oauth-scopes")).map(|scopes| {
+            scopes
+                .split(',')
+                .map(|scope| scope.trim().to_string())
+                .filter(|scope| !scope.is_empty())
+                .collect()
+        });
+        let rate_limit: RateLimit = response.json().await?;
+
+        Ok(AuthStatus {
+            authenticated: self.authenticated,
+            scopes,
+            quota: rate_limit.resources.core,
+        })
+    }
+
+    /// Only anonymous users gain anything from authenticating
+    fn auth_hint(&self) -> &'static str {
+        if self.authenticated {
+            return "";
+        }
+        "\nAnonymous requests are limited to 60 per hour, run `shran auth --token <token>` to raise the limit"
+    }
+
+    fn download_release(
+        &self,
+        tag: &str,
+        expected_sha256: Option<String>,
+    ) -> Result<InstalledArchive, Box<dyn Error>> {
+        let file_name = format!("{}{}", tag, ShranDefault::FILE_EXTENSION);
+        let candidates = match &self.verifier {
+            Some(_) => self.upstream.signed_candidate_urls(tag),
+            None => self.upstream.candidate_urls(tag),
+        };
+        let key = ManifestKey::new(ShranDefault::SUPPORTED_BLOCKCHAINS[0], tag);
+        self.download_archive(candidates, &file_name, expected_sha256, &key)
+    }
+
+    fn download_archive(
+        &self,
+        candidates: Vec<String>,
+        file_name: &str,
+        expected_sha256: Option<String>,
+        key: &ManifestKey,
+    ) -> Result<InstalledArchive, Box<dyn Error>> {
+        let mut downloader = Downloader::new(candidates, expected_sha256);
+        let download = downloader.fetch()?;
+        println!("{}", download);
+
+        let location = self.fs.write_and_extract_blockchain_archive(
+            file_name,
+            download.bytes,
+            BlockchainKind::Bitcoin,
+            key,
+        )?;
+        Ok(InstalledArchive {
+            location,
+            source: download.source,
+            sha256: download.sha256,
         })
     }
 
+    /// Verify and download a release whose metadata is already known,
+    /// e.g. from the release cache, without asking the github api for it again.
+    pub fn download_cached_release(
+        &self,
+        release: GitRelease,
+    ) -> Result<FetchedRelease, Box<dyn Error>> {
+        let mut expected_sha256 = self.checksum.clone();
+        if let Some(verifier) = &self.verifier {
+            let report = verifier.verify_release(&self.upstream, &release.tag_name)?;
+            println!("{}", report);
+            // only the official tarball is covered by SHA256SUMS, so a github
+            // archive is rejected in favour of the tarball next to it
+            expected_sha256 = Some(report.bind_source_sha256(expected_sha256.as_deref())?);
+        }
+
+        let archive = self.download_release(&release.tag_name, expected_sha256)?;
+        Ok(FetchedRelease { release, archive })
+    }
+
     /// Download the latest release from github
     ///
     /// # Example
     ///
     /// ```no_run
-    /// let gclient = GithubClient::new(token)?;
-    /// let release: GitRelease = gclient.get_latest_release().await?;
+    /// let gclient = GithubClient::new(Some(token), Upstream::default(), FileSystemManager::new()?)?;
+    /// let fetched: FetchedRelease = gclient.get_latest_release().await?;
     /// ```
-    pub async fn get_latest_release(self) -> Result<GitRelease, Box<dyn Error>> {
+    pub async fn get_latest_release(&self) -> Result<FetchedRelease, Box<dyn Error>> {
+        let release = self.fetch_latest_release().await?;
+        self.download_cached_release(release)
+    }
+
+    /// Look up the metadata of the latest release without downloading it.
+    /// A fresh release cache answers without contacting github, otherwise
+    /// only the latest release is asked for, not every tag and release.
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::ReleaseCacheError if the policy is CachePolicy::Offline
+    /// and no release has been cached yet.
+    pub async fn latest_release(&self, policy: CachePolicy) -> Result<GitRelease, Box<dyn Error>> {
+        let cache = match policy {
+            CachePolicy::Offline => self.release_cache(policy).await?,
+            CachePolicy::Default => self.fs.read_release_cache(&self.upstream)?,
+            CachePolicy::Refresh => return self.fetch_latest_release().await,
+        };
+        match cache.latest() {
+            Some(release)
+                if policy == CachePolicy::Offline
+                    || cache.is_fresh(ShranDefault::RELEASE_CACHE_TTL_SECS) =>
+            {
+                Ok(release.clone())
+            }
+            None if policy == CachePolicy::Offline => {
+                Err(Box::new(ShranError::ReleaseCacheError {
+                    msg: format!(
+                        "No release of {}/{} is cached, run `shran fetch --list-remote --refresh` while online",
+                        self.upstream.owner, self.upstream.repo
+                    ),
+                    file: file!(),
+                    line: line!(),
+                    column: column!(),
+                }))
+            }
+            _ => self.fetch_latest_release().await,
+        }
+    }
+
+    async fn fetch_latest_release(&self) -> Result<GitRelease, Box<dyn Error>> {
+        self.check_rate_limit().await?;
         let release: Release = self
             .octocrab
-            .repos("bitcoin", "bitcoin")
+            .repos(&self.upstream.owner, &self.upstream.repo)
             .releases()
             .get_latest()
             .await?;
-
-        self.release_helper(release)
+        Ok(GitRelease::from(release))
     }
 
     /// Download a release specified by a tag
@@ -114,22 +426,77 @@ impl GithubClient {
     /// # Example
     ///
     /// ```no_run
-    /// let gclient = GithubClient::new(token)?;
-    /// let tag = String::from("v23.0");
-    /// let release: GitRelease = gclient.get_tagged_release(&tag).await?;
+    /// let gclient = GithubClient::new(Some(token), Upstream::default(), FileSystemManager::new()?)?;
+    /// let fetched: FetchedRelease = gclient.get_tagged_release("v23.0").await?;
     /// ```
-    pub async fn get_tagged_release(self, tag: &String) -> Result<GitRelease, Box<dyn Error>> {
+    pub async fn get_tagged_release(&self, tag: &str) -> Result<FetchedRelease, Box<dyn Error>> {
+        let release = self.tagged_release(tag).await?;
+        self.download_cached_release(release)
+    }
+
+    /// Look up the metadata of a release by its tag, without downloading it
+    pub async fn tagged_release(&self, tag: &str) -> Result<GitRelease, Box<dyn Error>> {
+        self.check_rate_limit().await?;
         let release: Release = self
             .octocrab
-            .repos("bitcoin", "bitcoin")
+            .repos(&self.upstream.owner, &self.upstream.repo)
             .releases()
             .get_by_tag(tag)
             .await?;
 
-        self.release_helper(release)
+        Ok(GitRelease::from(release))
     }
 
-    /// Fetches all available tags (releases) from bitcoins repository.
+    /// Resolve a branch, commit or pull request to the full sha of the
+    /// commit it currently points at.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let gclient = GithubClient::new(token, Upstream::default(), FileSystemManager::new()?)?;
+    /// let resolved: ResolvedRef = gclient.resolve_ref(&GitRef::PullRequest(27260)).await?;
+    /// ```
+    pub async fn resolve_ref(&self, git_ref: &GitRef) -> Result<ResolvedRef, Box<dyn Error>> {
+        let (commitish, title) = match git_ref {
+            GitRef::Ref(name) => (name.to_owned(), None),
+            GitRef::PullRequest(number) => {
+                let url = self.octocrab.absolute_url(format!(
+                    "repos/{}/{}/pulls/{}",
+                    self.upstream.owner, self.upstream.repo, number
+                ))?;
+                let (body, _) = self.conditional_get(url.as_str()).await?;
+                let pull: PullRequestResponse = serde_json::from_str(&body)?;
+                (pull.head.sha, Some(pull.title))
+            }
+        };
+
+        let url = self.octocrab.absolute_url(format!(
+            "repos/{}/{}/commits/{}",
+            self.upstream.owner, self.upstream.repo, commitish
+        ))?;
+        let (body, _) = self.conditional_get(url.as_str()).await?;
+        let commit: CommitResponse = serde_json::from_str(&body)?;
+        Ok(ResolvedRef {
+            git_ref: git_ref.to_owned(),
+            sha: commit.sha,
+            committed_at: commit.commit.committer.date,
+            title,
+        })
+    }
+
+    /// Download the source archive of a resolved commit. Mirrors only serve
+    /// release tags, so the commit archive url is the only candidate.
+    pub fn download_ref(&self, resolved: &ResolvedRef) -> Result<InstalledArchive, Box<dyn Error>> {
+        let file_name = format!("{}{}", resolved.sha, ShranDefault::FILE_EXTENSION);
+        self.download_archive(
+            vec![self.upstream.commit_archive_url(&resolved.sha)],
+            &file_name,
+            self.checksum.clone(),
+            &resolved.manifest_key(ShranDefault::SUPPORTED_BLOCKCHAINS[0]),
+        )
+    }
+
+    /// Fetches all available tags (releases) from the upstream repository.
     ///
     /// # BUG
     /// FIXME: It turns out that not all tags are releases.. Not sure
@@ -139,33 +506,417 @@ impl GithubClient {
     ///
     /// # Example
     /// ```no_run
-    /// let gclient = GithubClient::new(token)?;
+    /// let gclient = GithubClient::new(Some(token), Upstream::default(), FileSystemManager::new()?)?;
     /// let tags: Vec<String> = gclient.get_all_tags().await?;
     /// for tag in tags {
     ///    println!("{}", tag);
     /// }
     /// ```
-    pub async fn get_all_tags(self) -> Result<Vec<String>, Box<dyn Error>> {
-        let mut current_page: Page<Tag> = self
-            .octocrab
-            .repos("bitcoin", "bitcoin")
-            .list_tags()
-            .send()
-            .await?;
+    pub async fn get_all_tags(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let first_page = self.octocrab.absolute_url(format!(
+            "repos/{}/{}/tags?per_page=100",
+            self.upstream.owner, self.upstream.repo
+        ))?;
 
-        // A Page<T> is basically a linked list, so we will iterate through it,
-        // with the facillities that the ocotocrab library gives us, to ensure
-        // we get the complete history of every release bitcoin has ever made.
-        let mut page_of_tags: Vec<Tag> = current_page.take_items();
+        // The pages form a linked list through their Link headers, every page
+        // is revalidated with its ETag, so listing an unchanged repository
+        // again does not use up any of the quota.
         let mut tags: Vec<String> = Vec::new();
-        while let Ok(Some(mut new_page)) = self.octocrab.get_page(&current_page.next).await {
-            page_of_tags.extend(new_page.take_items());
-
-            for tag in page_of_tags.drain(..) {
+        let mut next_page: Option<String> = Some(first_page.to_string());
+        while let Some(url) = next_page {
+            let (body, next) = self.conditional_get(&url).await?;
+            let page_of_tags: Vec<Tag> = serde_json::from_str(&body)?;
+            for tag in page_of_tags {
                 tags.push(tag.name);
             }
-            current_page = new_page;
+            next_page = next;
         }
         Ok(tags)
     }
+
+    /// Fetches the metadata of every published release, newest first.
+    /// Pages are revalidated with their ETags just like the tag listing.
+    pub async fn get_all_releases(&self) -> Result<Vec<GitRelease>, Box<dyn Error>> {
+        let first_page = self.octocrab.absolute_url(format!(
+            "repos/{}/{}/releases?per_page=100",
+            self.upstream.owner, self.upstream.repo
+        ))?;
+
+        let mut releases: Vec<GitRelease> = Vec::new();
+        let mut next_page: Option<String> = Some(first_page.to_string());
+        while let Some(url) = next_page {
+            let (body, next) = self.conditional_get(&url).await?;
+            let page_of_releases: Vec<Release> = serde_json::from_str(&body)?;
+            releases.extend(page_of_releases.into_iter().map(GitRelease::from));
+            next_page = next;
+        }
+        Ok(releases)
+    }
+
+    /// Update the on disk release cache of the upstream repository
+    ///
+    /// # Example
+    /// ```no_run
+    /// let gclient = GithubClient::new(token, Upstream::default(), FileSystemManager::new()?)?;
+    /// let cache: ReleaseCache = gclient.refresh_release_cache().await?;
+    /// ```
+    pub async fn refresh_release_cache(&self) -> Result<ReleaseCache, Box<dyn Error>> {
+        let cache = ReleaseCache::new(self.get_all_tags().await?, self.get_all_releases().await?);
+        self.fs.write_release_cache(&self.upstream, &cache)?;
+        Ok(cache)
+    }
+
+    /// Release metadata of the upstream repository, taken from the on disk
+    /// cache or from github depending on the cache policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::ReleaseCacheError if the policy is CachePolicy::Offline
+    /// and nothing has been cached yet.
+    pub async fn release_cache(&self, policy: CachePolicy) -> Result<ReleaseCache, Box<dyn Error>> {
+        let cache = self.fs.read_release_cache(&self.upstream)?;
+        match policy {
+            CachePolicy::Offline if cache.is_empty() => Err(Box::new(ShranError::ReleaseCacheError {
+                msg: format!(
+                    "No release metadata cached for {}/{}, run `shran fetch --list-remote --refresh` while online",
+                    self.upstream.owner, self.upstream.repo
+                ),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            })),
+            CachePolicy::Offline => Ok(cache),
+            CachePolicy::Default if cache.is_fresh(ShranDefault::RELEASE_CACHE_TTL_SECS) => Ok(cache),
+            _ => self.refresh_release_cache().await,
+        }
+    }
+}
+
+fn header_string(value: Option<&reqwest::header::HeaderValue>) -> Option<String> {
+    value
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GithubClient;
+    use crate::config::Upstream;
+    use crate::github::{CachePolicy, GitRef, GitRelease, ReleaseCache};
+    use crate::utils::mock_http::{self, MockResponse};
+    use crate::utils::{EtagEntry, FileSystemManager};
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Every test keeps its caches in a directory of its own
+    fn test_dir(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("shran-releases-{}-{}", name, std::process::id()))
+    }
+
+    fn test_fs(name: &str) -> FileSystemManager {
+        FileSystemManager::in_dir(test_dir(name).to_str().unwrap()).unwrap()
+    }
+
+    fn client(name: &str, token: Option<String>, upstream: Upstream) -> GithubClient {
+        GithubClient::new(token, upstream, test_fs(name)).unwrap()
+    }
+
+    fn tag_json(name: &str) -> String {
+        format!(
+            r#"{{"name": "{0}", "node_id": "x", "commit": {{"sha": "abc", "url": "http://localhost/c"}}, "zipball_url": "http://localhost/{0}.zip", "tarball_url": "http://localhost/{0}.tar.gz"}}"#,
+            name
+        )
+    }
+
+    #[tokio::test]
+    async fn test_get_all_tags_from_configured_upstream() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert(
+            "/repos/fork/bitcoin/tags".to_string(),
+            MockResponse::json(&format!("[{}, {}]", tag_json("v23.0"), tag_json("v22.0")))
+                .header("Link", r#"<{base}/repos/fork/bitcoin/tags2>; rel="next""#),
+        );
+        routes.insert(
+            "/repos/fork/bitcoin/tags2".to_string(),
+            MockResponse::json(&format!("[{}]", tag_json("v21.0"))),
+        );
+        let base = mock_http::serve(routes);
+
+        let upstream = Upstream {
+            owner: "fork".to_string(),
+            api_base_url: base,
+            ..Upstream::default()
+        };
+        let gclient = client(
+            "get_all_tags_from_configured_upstream",
+            Some(String::from("token")),
+            upstream,
+        );
+        let tags = gclient.get_all_tags().await.unwrap();
+        assert_eq!(tags, vec!["v23.0", "v22.0", "v21.0"]);
+        let _ = fs::remove_dir_all(test_dir("get_all_tags_from_configured_upstream"));
+    }
+
+    fn user_json(login: &str) -> String {
+        let url = "http://localhost/u";
+        format!(
+            r#"{{"login": "{0}", "id": 1, "node_id": "u", "avatar_url": "{1}", "gravatar_id": "", "url": "{1}", "html_url": "{1}", "followers_url": "{1}", "following_url": "{1}", "gists_url": "{1}", "starred_url": "{1}", "subscriptions_url": "{1}", "organizations_url": "{1}", "repos_url": "{1}", "events_url": "{1}", "received_events_url": "{1}", "type": "User", "site_admin": false}}"#,
+            login, url
+        )
+    }
+
+    fn release_json(tag: &str, prerelease: bool, body: &str) -> String {
+        format!(
+            r#"{{"url": "http://localhost/r", "html_url": "http://localhost/r", "assets_url": "http://localhost/r", "upload_url": "http://localhost/r", "tarball_url": null, "zipball_url": null, "id": 1, "node_id": "r", "tag_name": "{0}", "target_commitish": "master", "name": "Bitcoin Core {0}", "body": "{2}", "draft": false, "prerelease": {1}, "created_at": "2023-05-26T10:00:00Z", "published_at": "2023-05-26T10:00:00Z", "author": {3}, "assets": []}}"#,
+            tag,
+            prerelease,
+            body,
+            user_json("fanquake")
+        )
+    }
+
+    #[tokio::test]
+    async fn test_release_cache_policies() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert(
+            "/repos/cached/bitcoin/tags".to_string(),
+            MockResponse::json(&format!("[{}]", tag_json("v25.0"))),
+        );
+        routes.insert(
+            "/repos/cached/bitcoin/releases".to_string(),
+            MockResponse::json(&format!(
+                "[{}, {}]",
+                release_json("v26.0rc1", true, "rc"),
+                release_json("v25.0", false, "notes")
+            )),
+        );
+        let upstream = Upstream {
+            owner: "cached".to_string(),
+            api_base_url: mock_http::serve(routes),
+            ..Upstream::default()
+        };
+        let _ = fs::remove_dir_all(test_dir("release_cache_policies"));
+        let gclient = client("release_cache_policies", None, upstream.clone());
+        match gclient.release_cache(CachePolicy::Offline).await {
+            Ok(_) => panic!("offline listing without a cache succeeded"),
+            Err(e) => assert!(e.to_string().contains("--refresh")),
+        }
+
+        let cache = gclient.release_cache(CachePolicy::Refresh).await.unwrap();
+        assert_eq!(cache.tags, vec!["v25.0"]);
+        assert_eq!(cache.latest().unwrap().body, "notes");
+
+        // offline listing never touches the api
+        let offline = client(
+            "release_cache_policies",
+            None,
+            Upstream {
+                api_base_url: "http://127.0.0.1:9/".to_string(),
+                ..upstream
+            },
+        );
+        let cached = offline.release_cache(CachePolicy::Offline).await.unwrap();
+        assert_eq!(cached, cache);
+        assert_eq!(
+            offline.release_cache(CachePolicy::Default).await.unwrap(),
+            cache
+        );
+        let _ = fs::remove_dir_all(test_dir("release_cache_policies"));
+    }
+
+    #[tokio::test]
+    async fn test_latest_release_asks_only_for_the_latest() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert(
+            "/repos/newest/bitcoin/releases/latest".to_string(),
+            MockResponse::json(&release_json("v25.0", false, "notes")),
+        );
+        let upstream = Upstream {
+            owner: "newest".to_string(),
+            api_base_url: mock_http::serve(routes),
+            ..Upstream::default()
+        };
+        let _ = fs::remove_dir_all(test_dir("latest_release"));
+        let gclient = client("latest_release", None, upstream.clone());
+        assert!(gclient.latest_release(CachePolicy::Offline).await.is_err());
+
+        // neither the tags nor the releases are listed
+        let latest = gclient.latest_release(CachePolicy::Default).await.unwrap();
+        assert_eq!(latest.tag_name, "v25.0");
+
+        // a fresh cache is enough
+        let cache = ReleaseCache::new(
+            vec!["v24.0".to_string()],
+            vec![GitRelease {
+                tag_name: "v24.0".to_string(),
+                ..latest.clone()
+            }],
+        );
+        test_fs("latest_release")
+            .write_release_cache(&upstream, &cache)
+            .unwrap();
+        let cached = gclient.latest_release(CachePolicy::Default).await.unwrap();
+        assert_eq!(cached.tag_name, "v24.0");
+        let refreshed = gclient.latest_release(CachePolicy::Refresh).await.unwrap();
+        assert_eq!(refreshed.tag_name, "v25.0");
+        let _ = fs::remove_dir_all(test_dir("latest_release"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_pull_request_to_commit() {
+        let sha = "1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e";
+        let commit = format!(
+            r#"{{"sha": "{}", "commit": {{"committer": {{"date": "2023-05-26T10:00:00Z"}}}}}}"#,
+            sha
+        );
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert(
+            "/repos/bitcoin/bitcoin/pulls/27260".to_string(),
+            MockResponse::json(&format!(
+                r#"{{"title": "net: fix things", "head": {{"sha": "{}"}}}}"#,
+                sha
+            )),
+        );
+        routes.insert(
+            format!("/repos/bitcoin/bitcoin/commits/{}", sha),
+            MockResponse::json(&commit),
+        );
+        routes.insert(
+            "/repos/bitcoin/bitcoin/commits/master".to_string(),
+            MockResponse::json(&commit),
+        );
+        let upstream = Upstream {
+            api_base_url: mock_http::serve(routes),
+            ..Upstream::default()
+        };
+        let gclient = client("resolve_pull_request_to_commit", None, upstream);
+
+        let pull = gclient
+            .resolve_ref(&GitRef::PullRequest(27260))
+            .await
+            .unwrap();
+        assert_eq!(pull.sha, sha);
+        assert_eq!(pull.title, Some("net: fix things".to_string()));
+
+        let branch = gclient
+            .resolve_ref(&GitRef::Ref("master".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(branch.sha, sha);
+        assert_eq!(branch.committed_at, "2023-05-26T10:00:00Z");
+        assert_eq!(branch.title, None);
+        let _ = fs::remove_dir_all(test_dir("resolve_pull_request_to_commit"));
+    }
+
+    fn rate_limit_json(remaining: u64) -> String {
+        format!(
+            r#"{{"resources": {{"core": {{"limit": 60, "remaining": {}, "reset": 1700000000, "used": 0}}}}}}"#,
+            remaining
+        )
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_rate_limit_exhausted_suggests_auth() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        let mut exhausted = MockResponse::json(r#"{"message": "API rate limit exceeded"}"#)
+            .header("x-ratelimit-limit", "60")
+            .header("x-ratelimit-remaining", "0")
+            .header("x-ratelimit-reset", "4102444800");
+        exhausted.status = 403;
+        routes.insert("/repos/bitcoin/bitcoin/tags".to_string(), exhausted);
+        let upstream = Upstream {
+            api_base_url: mock_http::serve(routes),
+            ..Upstream::default()
+        };
+        let gclient = client(
+            "anonymous_rate_limit_exhausted_suggests_auth",
+            None,
+            upstream,
+        );
+        match gclient.get_all_tags().await {
+            Ok(_) => panic!("exhausted rate limit was ignored"),
+            Err(e) => assert!(e.to_string().contains("shran auth")),
+        }
+        let _ = fs::remove_dir_all(test_dir("anonymous_rate_limit_exhausted_suggests_auth"));
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_client_lists_tags() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert(
+            "/rate_limit".to_string(),
+            MockResponse::json(&rate_limit_json(59)),
+        );
+        routes.insert(
+            "/repos/bitcoin/bitcoin/tags".to_string(),
+            MockResponse::json(&format!("[{}]", tag_json("v23.0"))),
+        );
+        let upstream = Upstream {
+            api_base_url: mock_http::serve(routes),
+            ..Upstream::default()
+        };
+        let gclient = client("anonymous_client_lists_tags", None, upstream);
+        assert_eq!(gclient.get_all_tags().await.unwrap(), vec!["v23.0"]);
+        let _ = fs::remove_dir_all(test_dir("anonymous_client_lists_tags"));
+    }
+
+    #[tokio::test]
+    async fn test_get_all_tags_revalidates_with_etag() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert(
+            "/repos/etag/bitcoin/tags".to_string(),
+            MockResponse::json(&format!("[{}]", tag_json("v23.0"))).etag("\"abc\""),
+        );
+        let base = mock_http::serve(routes);
+        let upstream = Upstream {
+            owner: "etag".to_string(),
+            api_base_url: base.clone(),
+            ..Upstream::default()
+        };
+
+        let gclient = client("get_all_tags_revalidates_with_etag", None, upstream.clone());
+        assert_eq!(gclient.get_all_tags().await.unwrap(), vec!["v23.0"]);
+
+        // tamper with the cached body, a 304 must be answered from the cache
+        let url = format!("{}/repos/etag/bitcoin/tags?per_page=100", base);
+        let fs = test_fs("get_all_tags_revalidates_with_etag");
+        let entry = EtagEntry {
+            etag: "\"abc\"".to_string(),
+            body: format!("[{}]", tag_json("cached")),
+            next: None,
+        };
+        fs.write_etag_entry(&url, &entry).unwrap();
+
+        let gclient = client("get_all_tags_revalidates_with_etag", None, upstream);
+        assert_eq!(gclient.get_all_tags().await.unwrap(), vec!["cached"]);
+        let _ = fs::remove_dir_all(test_dir("get_all_tags_revalidates_with_etag"));
+    }
+
+    #[tokio::test]
+    async fn test_auth_status_reports_scopes_and_quota() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert(
+            "/rate_limit".to_string(),
+            MockResponse::json(&rate_limit_json(4999)).header("X-OAuth-Scopes", "repo, read:org"),
+        );
+        let upstream = Upstream {
+            api_base_url: mock_http::serve(routes),
+            ..Upstream::default()
+        };
+        let gclient = client(
+            "auth_status_reports_scopes_and_quota",
+            Some(String::from("token")),
+            upstream,
+        );
+        let status = gclient.auth_status().await.unwrap();
+        assert!(status.authenticated);
+        assert_eq!(
+            status.scopes,
+            Some(vec!["repo".to_string(), "read:org".to_string()])
+        );
+        assert_eq!(status.quota.remaining, 4999);
+        let _ = fs::remove_dir_all(test_dir("auth_status_reports_scopes_and_quota"));
+    }
 }
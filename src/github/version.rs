@@ -0,0 +1,143 @@
+use crate::error::ShranError;
+use std::fmt;
+use std::str::FromStr;
+
+/// A release version, [e.g.] `v25.0` or `0.21.2`. Release candidates
+/// like `v26.0rc1` do not parse, so constraints never select them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl FromStr for Version {
+    type Err = ShranError<'static>;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let invalid = || ShranError::VersionConstraintError {
+            msg: format!("{} is not a release version", version),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        };
+        let mut parts = [0u32; 3];
+        for (i, part) in version
+            .trim()
+            .trim_start_matches('v')
+            .split('.')
+            .enumerate()
+        {
+            if i == parts.len() {
+                return Err(invalid());
+            }
+            parts[i] = part.parse::<u32>().map_err(|_| invalid())?;
+        }
+        Ok(Self {
+            major: parts[0],
+            minor: parts[1],
+            patch: parts[2],
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Comparison {
+    Exact,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+/// A set of comparisons every selected version has to satisfy, separated
+/// by commas, [e.g.] `>=23.0, <26` selects every release from v23.0 up to
+/// but excluding v26.0. A version without an operator must match exactly,
+/// missing minor and patch numbers count as 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionConstraint {
+    comparisons: Vec<(Comparison, Version)>,
+}
+
+impl VersionConstraint {
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparisons
+            .iter()
+            .all(|(comparison, bound)| match comparison {
+                Comparison::Exact => version == bound,
+                Comparison::Greater => version > bound,
+                Comparison::GreaterOrEqual => version >= bound,
+                Comparison::Less => version < bound,
+                Comparison::LessOrEqual => version <= bound,
+            })
+    }
+
+    /// The tags satisfying the constraint, tags that are not plain
+    /// release versions are skipped
+    pub fn select(&self, tags: &[String]) -> Vec<String> {
+        tags.iter()
+            .filter(|tag| {
+                tag.parse::<Version>()
+                    .is_ok_and(|version| self.matches(&version))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+impl FromStr for VersionConstraint {
+    type Err = ShranError<'static>;
+
+    fn from_str(constraint: &str) -> Result<Self, Self::Err> {
+        let mut comparisons: Vec<(Comparison, Version)> = Vec::new();
+        for part in constraint.split(',') {
+            let part = part.trim();
+            let (comparison, version) = if let Some(version) = part.strip_prefix(">=") {
+                (Comparison::GreaterOrEqual, version)
+            } else if let Some(version) = part.strip_prefix("<=") {
+                (Comparison::LessOrEqual, version)
+            } else if let Some(version) = part.strip_prefix('>') {
+                (Comparison::Greater, version)
+            } else if let Some(version) = part.strip_prefix('<') {
+                (Comparison::Less, version)
+            } else {
+                (Comparison::Exact, part.trim_start_matches('='))
+            };
+            comparisons.push((comparison, version.parse()?));
+        }
+        Ok(Self { comparisons })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Version, VersionConstraint};
+
+    #[test]
+    fn test_parse_versions() {
+        let version: Version = "v0.21.2".parse().unwrap();
+        assert_eq!((version.major, version.minor, version.patch), (0, 21, 2));
+        assert!("v25.0".parse::<Version>().unwrap() > "v24.1".parse::<Version>().unwrap());
+        assert!("v26.0rc1".parse::<Version>().is_err());
+        assert!("1.2.3.4".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn test_select_tags_by_constraint() {
+        let tags: Vec<String> = ["v26.0rc1", "v25.1", "v25.0", "v24.1", "v23.0", "v0.21.2"]
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect();
+        let constraint: VersionConstraint = ">=23.0, <25.1".parse().unwrap();
+        assert_eq!(constraint.select(&tags), vec!["v25.0", "v24.1", "v23.0"]);
+        let exact: VersionConstraint = "=v24.1".parse().unwrap();
+        assert_eq!(exact.select(&tags), vec!["v24.1"]);
+        assert!(">=twenty".parse::<VersionConstraint>().is_err());
+    }
+}
@@ -1,125 +1,1326 @@
+mod bundle;
 mod cli;
 mod config;
 mod error;
 mod github;
+mod prebuilt;
+mod sources;
 mod strategies;
 mod utils;
+mod verify;
 
+pub use bundle::{install_bundle, OfflineBundle};
 pub use cli::commands::{ActiveCommand, ArgName, SubCommandName};
 pub use cli::Cli;
-pub use config::{ShranDefault, ShranFile};
+pub use config::{SettingSource, Settings, ShranDefault, ShranFile, Upstream};
 pub use error::ShranError;
-pub use github::{GitRelease, GithubClient};
+pub use github::{
+    render_notes, BatchFetch, CachePolicy, FetchedRelease, GitRef, GitRelease, GithubClient,
+    InstalledArchive, NotesFormat, ReleaseRange, ResolvedRef, VersionConstraint,
+};
+pub use prebuilt::{host_platform, PrebuiltRelease};
+pub use sources::{
+    DependsFetcher, DirectoryProvider, GitMirror, GitProvider, GithubProvider, ProvidedSource,
+    SourceProvider, SourceSpec, TarballProvider,
+};
 use std::process::ExitCode;
 pub use strategies::bitcoin::{BuildOptionName, BuildStrategy, OptionEnabled};
-pub use utils::{FileSystemManager, ManifestManager};
+pub use utils::manifest_manager::{parse_timestamp, EntryKind, ManifestEntry};
+pub use utils::token_store::read_passphrase;
+pub use utils::{
+    disk_usage, human_size, parse_age, parse_size, redact, select, CacheCollector,
+    FileSystemManager, GcPolicy, ManifestKey, ManifestManager, ManifestQuery, ManifestScanner,
+    SortKey, SourceType, Uninstaller,
+};
+pub use verify::{ThresholdVerifier, TreeVerifier};
 
 fn run_generate(node_type: &String) {
     println!("Generating build for: {}", node_type);
 }
 
-fn run_build(path: &String) {
+async fn run_build(path: &String, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
     println!("Build file path {}", path);
+    if let Some(spec) = cli.modifier(ArgName::SOURCE).and_then(|arg| arg.value) {
+        // recorded like a fetch, so the tree shows up in ls, verify and remove
+        let source = run_fetch_source(spec, cli).await?;
+        println!("Source directory {}", source.location);
+    }
+    Ok(())
 }
 
-fn run_auth(token: &String) -> Result<(), Box<dyn std::error::Error>> {
+fn run_auth(token: &String, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
     let fs = FileSystemManager::new()?;
-    fs.write_token(token.to_owned())?;
+    let passphrase = match cli.modifier(ArgName::ENCRYPT) {
+        Some(_) => Some(read_passphrase(true)?),
+        None => None,
+    };
+    fs.write_token(token.to_owned(), passphrase.as_deref())?;
+    for var in ShranDefault::GITHUB_TOKEN_ENVS {
+        if matches!(std::env::var(var), Ok(token) if !token.trim().is_empty()) {
+            println!("{} is set and takes precedence over the stored token", var);
+            break;
+        }
+    }
 
     Ok(())
 }
 
-async fn run_get_latest() -> Result<GitRelease, Box<dyn std::error::Error>> {
+async fn run_auth_status() -> Result<(), Box<dyn std::error::Error>> {
     let fs = FileSystemManager::new()?;
     let token = fs.read_token()?;
-    let gclient = GithubClient::new(token)?;
-    let release: GitRelease = gclient.get_latest_release().await?;
-    Ok(release)
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let gclient = GithubClient::new(token, upstream, fs)?;
+    println!("{}", gclient.auth_status().await?);
+    Ok(())
+}
+
+fn threshold_verifier(cli: &Cli) -> Result<Option<ThresholdVerifier>, Box<dyn std::error::Error>> {
+    match cli.modifier(ArgName::KEYRING).and_then(|arg| arg.value) {
+        Some(keyring) => {
+            let threshold = match cli.modifier(ArgName::THRESHOLD).and_then(|arg| arg.value) {
+                Some(threshold) => threshold.parse::<usize>()?,
+                None => ShranDefault::DEFAULT_SIGNATURE_THRESHOLD,
+            };
+            Ok(Some(ThresholdVerifier::new(&keyring, threshold)?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// True if a version fetched before may be replaced, see FileSystemManager::with_force
+fn forced(cli: &Cli) -> bool {
+    cli.modifier(ArgName::FORCE).is_some()
+}
+
+fn cache_policy(cli: &Cli) -> CachePolicy {
+    if cli.modifier(ArgName::OFFLINE).is_some() {
+        CachePolicy::Offline
+    } else if cli.modifier(ArgName::REFRESH).is_some() {
+        CachePolicy::Refresh
+    } else {
+        CachePolicy::Default
+    }
+}
+
+/// Record an entry in the manifest together with the content hash of its
+/// tree, replacing an older entry. A tree that is fetched again in place keeps
+/// the hash it was first recorded with, files added to it since stay visible
+/// to `shran verify`. A `dir:` working tree is edited by design and gets no
+/// hash.
+fn record_entry(key: ManifestKey, entry: ManifestEntry) -> Result<(), Box<dyn std::error::Error>> {
+    let mut manifest = ManifestManager::new()?;
+    if SourceType::of(&entry) == SourceType::Directory {
+        manifest.set_entry(key, &entry)?;
+        return Ok(());
+    }
+    let recorded = manifest.get_entry(key.clone()).ok().and_then(|recorded| {
+        let same_tree = recorded.installation_location == entry.installation_location
+            && recorded.sha256 == entry.sha256
+            && recorded.commit == entry.commit;
+        recorded.tree_hash.clone().filter(|_| same_tree)
+    });
+    let tree_hash = match recorded {
+        Some(tree_hash) => tree_hash,
+        None => TreeVerifier::new().record(&entry.installation_location)?,
+    };
+    manifest.set_entry(key, &entry.with_tree_hash(tree_hash))?;
+    Ok(())
 }
 
-async fn run_get_tagged_release(tag: String) -> Result<GitRelease, Box<dyn std::error::Error>> {
+/// Record a downloaded release in the manifest, replacing an older entry
+fn record_release(fetched: &FetchedRelease) -> Result<(), Box<dyn std::error::Error>> {
+    let release: &GitRelease = &fetched.release;
+    let entry = ManifestEntry::new(
+        release.tag_name.clone(),
+        parse_timestamp(&release.published_at)?,
+        &fetched.archive.location,
+    )
+    .with_source(
+        fetched.archive.source.clone(),
+        Some(fetched.archive.sha256.clone()),
+    );
+    let key = ManifestKey::for_entry(ShranDefault::SUPPORTED_BLOCKCHAINS[0], &entry);
+    record_entry(key, entry)?;
+    Ok(())
+}
+
+async fn run_get_latest(cli: &Cli) -> Result<FetchedRelease, Box<dyn std::error::Error>> {
     let fs = FileSystemManager::new()?;
     let token = fs.read_token()?;
-    let gclient = GithubClient::new(token)?;
-    let release: GitRelease = gclient.get_tagged_release(&tag).await?;
-    Ok(release)
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let mut gclient = GithubClient::new(token, upstream, fs.with_force(forced(cli)))?;
+    if let Some(verifier) = threshold_verifier(cli)? {
+        gclient = gclient.with_verifier(verifier);
+    }
+    if let Some(sha256) = cli.modifier(ArgName::SHA256).and_then(|arg| arg.value) {
+        gclient = gclient.with_checksum(sha256);
+    }
+    let release = gclient.latest_release(cache_policy(cli)).await?;
+    let fetched: FetchedRelease = gclient.download_cached_release(release)?;
+    record_release(&fetched)?;
+    Ok(fetched)
 }
 
-async fn run_get_remote() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+async fn run_get_tagged_release(
+    tag: String,
+    cli: &Cli,
+) -> Result<FetchedRelease, Box<dyn std::error::Error>> {
     let fs = FileSystemManager::new()?;
     let token = fs.read_token()?;
-    let gclient = GithubClient::new(token)?;
-    let tags: Vec<String> = gclient.get_all_tags().await?;
-    Ok(tags)
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let mut gclient = GithubClient::new(token, upstream, fs.with_force(forced(cli)))?;
+    if let Some(verifier) = threshold_verifier(cli)? {
+        gclient = gclient.with_verifier(verifier);
+    }
+    if let Some(sha256) = cli.modifier(ArgName::SHA256).and_then(|arg| arg.value) {
+        gclient = gclient.with_checksum(sha256);
+    }
+    let fetched: FetchedRelease = match cache_policy(cli) {
+        CachePolicy::Offline => {
+            let cache = gclient.release_cache(CachePolicy::Offline).await?;
+            match cache.release(&tag) {
+                Some(release) => gclient.download_cached_release(release.clone())?,
+                None => {
+                    return Err(Box::new(ShranError::ReleaseCacheError {
+                        msg: format!("{} is not in the release cache", tag),
+                        file: file!(),
+                        line: line!(),
+                        column: column!(),
+                    }))
+                }
+            }
+        }
+        _ => gclient.get_tagged_release(&tag).await?,
+    };
+    record_release(&fetched)?;
+    Ok(fetched)
 }
 
-#[tokio::main]
-async fn main() -> ExitCode {
-    let mut exit_code = ExitCode::SUCCESS;
-    match Cli::new() {
-        Ok(cli) => {
-            if cli.subcommand_auth() {
-                if let Err(e) = run_auth(&cli.args().value.unwrap()) {
-                    eprintln!("{}", e);
-                    exit_code = ExitCode::FAILURE;
+fn notes_format(cli: &Cli, settings: &Settings) -> NotesFormat {
+    match cli.modifier(ArgName::MARKDOWN) {
+        Some(_) => NotesFormat::Markdown,
+        None if settings.colors().enabled() => NotesFormat::Terminal,
+        None => NotesFormat::Plain,
+    }
+}
+
+async fn run_fetch_notes(
+    tag: String,
+    cli: &Cli,
+    settings: &Settings,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let fs = FileSystemManager::new()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let gclient = GithubClient::new(fs.read_token()?, upstream, fs)?;
+    let cache = gclient.release_cache(cache_policy(cli)).await?;
+    let release = match cache.release(&tag) {
+        Some(release) => release.clone(),
+        None if cache_policy(cli) == CachePolicy::Offline => {
+            return Err(Box::new(ShranError::ReleaseCacheError {
+                msg: format!("{} is not in the release cache", tag),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }))
+        }
+        None => gclient.tagged_release(&tag).await?,
+    };
+    Ok(render_notes(
+        &[&release],
+        notes_format(cli, settings),
+        false,
+    ))
+}
+
+async fn run_notes(
+    range: String,
+    cli: &Cli,
+    settings: &Settings,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let range: ReleaseRange = range.parse()?;
+    let fs = FileSystemManager::new()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let gclient = GithubClient::new(fs.read_token()?, upstream, fs)?;
+    let cache = gclient.release_cache(cache_policy(cli)).await?;
+    let releases = range.select(&cache);
+    if releases.is_empty() {
+        return Err(Box::new(ShranError::VersionConstraintError {
+            msg: format!("no release between {} and {}", range.from, range.to),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+    Ok(render_notes(
+        &releases,
+        notes_format(cli, settings),
+        cli.modifier(ArgName::SUMMARY).is_some(),
+    ))
+}
+
+/// Download several releases concurrently, either the given tags
+/// or every release matching the `--versions` constraint
+async fn run_fetch_many(
+    value: String,
+    cli: &Cli,
+    settings: &Settings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for name in [ArgName::GIT, ArgName::PREBUILT, ArgName::SHA256] {
+        if cli.modifier(name).is_some() {
+            return Err(Box::new(ShranError::VersionConstraintError {
+                msg: format!("--{} only works with a single --tag", name),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }));
+        }
+    }
+    let constraint: Option<VersionConstraint> = match cli.args().name.as_str() {
+        ArgName::VERSIONS => Some(value.parse()?),
+        _ => None,
+    };
+    let fs = FileSystemManager::new()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let mut gclient = GithubClient::new(fs.read_token()?, upstream, fs.with_force(forced(cli)))?;
+    if let Some(verifier) = threshold_verifier(cli)? {
+        gclient = gclient.with_verifier(verifier);
+    }
+    let cache = gclient.release_cache(cache_policy(cli)).await?;
+    let tags: Vec<String> = if let Some(constraint) = constraint {
+        let published: Vec<String> = cache
+            .releases
+            .iter()
+            .filter(|release| !release.prerelease)
+            .map(|release| release.tag_name.clone())
+            .collect();
+        constraint.select(&published)
+    } else {
+        value.split(',').map(String::from).collect()
+    };
+    if tags.is_empty() {
+        return Err(Box::new(ShranError::VersionConstraintError {
+            msg: format!("no release matches {}", value),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+
+    let batch = BatchFetch::new(gclient, cache).with_concurrency(settings.jobs());
+    let outcomes = batch.fetch(tags).await;
+    for outcome in &outcomes {
+        println!("{}", redact(&outcome.to_string()));
+        if let Ok(fetched) = &outcome.result {
+            record_release(fetched)?;
+        }
+    }
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_err())
+        .count();
+    if failed > 0 {
+        return Err(Box::new(ShranError::DownloadError {
+            msg: format!("{} of {} versions failed", failed, outcomes.len()),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+    Ok(())
+}
+
+/// Install the official binaries of a release, the latest one if no tag is given
+async fn run_get_prebuilt(
+    tag: Option<String>,
+    cli: &Cli,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fs = FileSystemManager::new()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let gclient = GithubClient::new(fs.read_token()?, upstream.clone(), fs)?;
+    let cache = gclient.release_cache(cache_policy(cli)).await?;
+    let tag = match tag.or_else(|| cache.latest().map(|release| release.tag_name.clone())) {
+        Some(tag) => tag,
+        None => {
+            return Err(Box::new(ShranError::ReleaseCacheError {
+                msg: String::from("no release found to install"),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }))
+        }
+    };
+    let platform = match cli.modifier(ArgName::PLATFORM).and_then(|arg| arg.value) {
+        Some(platform) => platform,
+        None => match host_platform() {
+            Some(platform) => platform.to_string(),
+            None => {
+                return Err(Box::new(ShranError::DownloadError {
+                    msg: format!(
+                        "no release binaries are published for {}-{}, pass --platform",
+                        std::env::consts::ARCH,
+                        std::env::consts::OS
+                    ),
+                    file: file!(),
+                    line: line!(),
+                    column: column!(),
+                }))
+            }
+        },
+    };
+
+    let mut release = PrebuiltRelease::new(tag.clone(), upstream, platform);
+    if let Some(verifier) = threshold_verifier(cli)? {
+        release = release.with_verifier(verifier);
+    }
+    let installed = release.install()?;
+    println!("{}", installed);
+
+    let published_date = match cache.release(&tag) {
+        Some(release) => parse_timestamp(&release.published_at)?,
+        None => chrono::Utc::now(),
+    };
+    let entry = ManifestEntry::new(tag.clone(), published_date, installed.prefix)
+        .with_kind(EntryKind::Prebuilt)
+        .with_source(installed.source, Some(installed.sha256));
+    let key = ManifestKey::for_entry(ShranDefault::SUPPORTED_BLOCKCHAINS[0], &entry);
+    record_entry(key, entry)?;
+    Ok(())
+}
+
+fn git_ref(cli: &Cli) -> Result<GitRef, Box<dyn std::error::Error>> {
+    match cli.args().name.as_str() {
+        ArgName::PR => Ok(GitRef::PullRequest(
+            cli.args().value.unwrap().parse::<u64>()?,
+        )),
+        _ => Ok(GitRef::Ref(cli.args().value.unwrap())),
+    }
+}
+
+fn record_resolved_ref(
+    resolved: &ResolvedRef,
+    installation_location: String,
+    source: String,
+    sha256: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let entry = ManifestEntry::new(
+        resolved.git_ref.to_string(),
+        parse_timestamp(&resolved.committed_at)?,
+        installation_location,
+    )
+    .with_commit(resolved.git_ref.to_string(), resolved.sha.clone())
+    .with_source(source, sha256);
+    record_entry(
+        resolved.manifest_key(ShranDefault::SUPPORTED_BLOCKCHAINS[0]),
+        entry,
+    )?;
+    Ok(())
+}
+
+fn run_git_checkout(cli: &Cli) -> Result<ResolvedRef, Box<dyn std::error::Error>> {
+    let git_ref = git_ref(cli)?;
+    let fs = FileSystemManager::new()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let clone_url = upstream.clone_url();
+    let mirror = GitMirror::new(
+        clone_url.clone(),
+        ShranDefault::SUPPORTED_BLOCKCHAINS[0],
+        &upstream.repo,
+    );
+    mirror.sync()?;
+    let resolved = mirror.resolve(&git_ref)?;
+    let path = mirror.checkout(&resolved, cli.modifier(ArgName::SHALLOW).is_some())?;
+    println!("Checked out {} to {}", resolved.git_ref, path);
+    record_resolved_ref(&resolved, path, clone_url, None)?;
+    Ok(resolved)
+}
+
+async fn run_get_ref(
+    cli: &Cli,
+) -> Result<(ResolvedRef, InstalledArchive), Box<dyn std::error::Error>> {
+    let git_ref = git_ref(cli)?;
+    let fs = FileSystemManager::new()?;
+    let token = fs.read_token()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let mut gclient = GithubClient::new(token, upstream, fs.with_force(forced(cli)))?;
+    if let Some(sha256) = cli.modifier(ArgName::SHA256).and_then(|arg| arg.value) {
+        gclient = gclient.with_checksum(sha256);
+    }
+    let resolved = gclient.resolve_ref(&git_ref).await?;
+    let archive = gclient.download_ref(&resolved)?;
+    record_resolved_ref(
+        &resolved,
+        archive.location.clone(),
+        archive.source.clone(),
+        Some(archive.sha256.clone()),
+    )?;
+    Ok((resolved, archive))
+}
+
+/// Pick the SourceProvider for a source spec
+fn source_provider(
+    spec: SourceSpec,
+    cli: &Cli,
+) -> Result<Box<dyn SourceProvider>, Box<dyn std::error::Error>> {
+    let checksum = cli.modifier(ArgName::SHA256).and_then(|arg| arg.value);
+    match spec {
+        SourceSpec::Github { owner, repo, tag } => {
+            let fs = FileSystemManager::new()?;
+            let token = fs.read_token()?;
+            let upstream = fs
+                .read_config()?
+                .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+            let mut provider = GithubProvider::new(
+                token,
+                Upstream {
+                    owner,
+                    repo,
+                    ..upstream
+                },
+                tag,
+            );
+            if let Some(verifier) = threshold_verifier(cli)? {
+                provider = provider.with_verifier(verifier);
+            }
+            if let Some(sha256) = checksum {
+                provider = provider.with_checksum(sha256);
+            }
+            Ok(Box::new(provider.with_force(forced(cli))))
+        }
+        SourceSpec::Tarball { url } => Ok(Box::new(
+            TarballProvider::new(url, checksum).with_force(forced(cli)),
+        )),
+        SourceSpec::Directory { path } => Ok(Box::new(DirectoryProvider::new(path))),
+        SourceSpec::Git { url, git_ref } => Ok(Box::new(GitProvider::new(
+            url,
+            git_ref,
+            cli.modifier(ArgName::SHALLOW).is_some(),
+        ))),
+    }
+}
+
+async fn run_fetch_source(
+    spec: String,
+    cli: &Cli,
+) -> Result<ProvidedSource, Box<dyn std::error::Error>> {
+    let parsed: SourceSpec = spec.parse()?;
+    let git_ref = match &parsed {
+        SourceSpec::Git { git_ref, .. } => Some(git_ref.clone()),
+        _ => None,
+    };
+    let source = source_provider(parsed, cli)?.provide().await?;
+    record_source(&source, spec, git_ref.as_ref())?;
+    Ok(source)
+}
+
+/// Record a provided source in the manifest, `git_ref` is the ref a git
+/// source was checked out from
+fn record_source(
+    source: &ProvidedSource,
+    spec: String,
+    git_ref: Option<&GitRef>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entry = ManifestEntry::new(
+        source.version.clone(),
+        parse_timestamp(&source.published_date)?,
+        &source.location,
+    );
+    if let (Some(git_ref), Some(commit)) = (git_ref, &source.commit) {
+        entry = entry.with_commit(git_ref.to_string(), commit.to_owned());
+    }
+    entry = entry.with_source(spec, source.sha256.clone());
+    let key = ManifestKey::for_entry(ShranDefault::SUPPORTED_BLOCKCHAINS[0], &entry);
+    record_entry(key, entry)?;
+    Ok(())
+}
+
+/// The checksum an imported tarball has to match, either given directly,
+/// or taken from a SHA256SUMS file signed by enough trusted builders.
+fn import_checksum(
+    cli: &Cli,
+    upstream: &Upstream,
+    tag: &str,
+    file_name: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if let Some(sha256) = cli.modifier(ArgName::SHA256).and_then(|arg| arg.value) {
+        return Ok(Some(sha256));
+    }
+    let verifier = match threshold_verifier(cli)? {
+        Some(verifier) => verifier,
+        None => return Ok(None),
+    };
+    let sums = cli.modifier(ArgName::SUMS).and_then(|arg| arg.value);
+    let signature = cli.modifier(ArgName::SIGNATURE).and_then(|arg| arg.value);
+    let report = match (sums, signature) {
+        (Some(sums), Some(signature)) => verifier.verify_files(tag, &sums, &signature)?,
+        _ => verifier.verify_release(upstream, tag)?,
+    };
+    println!("{}", report);
+    match report
+        .sums
+        .get(file_name)
+        .cloned()
+        .or_else(|| report.source_sha256())
+    {
+        Some(sha256) => Ok(Some(sha256)),
+        None => Err(Box::new(ShranError::SignatureThresholdError {
+            msg: format!("{} is not listed in the signed SHA256SUMS", file_name),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        })),
+    }
+}
+
+async fn run_import(path: String, cli: &Cli) -> Result<ProvidedSource, Box<dyn std::error::Error>> {
+    let tag = cli.modifier(ArgName::AS).and_then(|arg| arg.value).unwrap();
+    let abs_path = std::fs::canonicalize(&path)?;
+    let file_name = abs_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let fs = FileSystemManager::new()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let expected_sha256 = import_checksum(cli, &upstream, &tag, &file_name)?;
+
+    let provider = TarballProvider::new(format!("file://{}", abs_path.display()), expected_sha256)
+        .with_version(tag.clone())
+        .with_force(forced(cli));
+    let mut source = provider.provide().await?;
+    // use the release date github published, if it has been cached while online
+    if let Some(release) = fs.read_release_cache(&upstream)?.release(&tag) {
+        source.published_date = release.published_at.clone();
+    }
+    record_source(&source, path, None)?;
+    Ok(source)
+}
+
+/// The extracted tree `fetch --depends` works on, either a path
+/// or the version of a source tree recorded in the manifest
+fn depends_tree(value: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if std::path::Path::new(value).is_dir() {
+        return Ok(value.to_string());
+    }
+    let manifest = ManifestManager::new()?;
+    let key = value
+        .parse::<ManifestKey>()
+        .unwrap_or_else(|_| ManifestKey::new(ShranDefault::SUPPORTED_BLOCKCHAINS[0], value));
+    match manifest.get_entry(key) {
+        Ok(entry) => Ok(entry.installation_location.to_string_lossy().to_string()),
+        Err(_) => Err(Box::new(ShranError::FileSystemError {
+            msg: format!("{} is neither a directory nor a fetched version", value),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        })),
+    }
+}
+
+fn run_fetch_depends(value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let tree = depends_tree(value)?;
+    let fetcher = DependsFetcher::new(tree.clone(), ShranDefault::depends_sources_dir());
+    let report = fetcher.prefetch()?;
+    println!("{}", report);
+    // the tree is left untouched, its recorded content hash stays valid
+    println!(
+        "Build with make -C {}/depends SOURCES_PATH={}",
+        tree, report.sources_path
+    );
+    if !report.failed.is_empty() {
+        return Err(Box::new(ShranError::DownloadError {
+            msg: format!(
+                "{} depends sources could not be fetched",
+                report.failed.len()
+            ),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+    Ok(())
+}
+
+fn run_bundle_create(cli: &Cli, settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    let value = |name: &str| cli.modifier(name).and_then(|arg| arg.value);
+    let tag = value(ArgName::TAG).unwrap();
+    let fs = FileSystemManager::new()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let published_date = fs
+        .read_release_cache(&upstream)?
+        .release(&tag)
+        .map(|release| release.published_at.clone());
+
+    let mut bundle = OfflineBundle::new(tag, upstream, settings.profile());
+    if let Some(sha256) = value(ArgName::SHA256) {
+        bundle = bundle.with_checksum(sha256);
+    }
+    if let Some(published_date) = published_date {
+        bundle = bundle.with_published_date(published_date);
+    }
+    let output = value(ArgName::OUTPUT).unwrap();
+    let manifest = bundle.create(&output)?;
+    println!(
+        "Bundled {} ({}) with {} signature files and {} depends sources to {}",
+        manifest.tag,
+        manifest.profile,
+        manifest.signatures.len(),
+        manifest.depends.len(),
+        output
+    );
+    Ok(())
+}
+
+fn run_bundle_install(path: &str, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let verifier = threshold_verifier(cli)?;
+    let installed = install_bundle(path, verifier.as_ref(), forced(cli))?;
+    let entry = ManifestEntry::new(
+        installed.manifest.tag.clone(),
+        parse_timestamp(&installed.manifest.published_date)?,
+        &installed.location,
+    )
+    .with_source(path.to_string(), Some(installed.manifest.sha256.clone()));
+    let key = ManifestKey::for_entry(ShranDefault::SUPPORTED_BLOCKCHAINS[0], &entry);
+    record_entry(key, entry)?;
+    println!(
+        "Installed {} to {}",
+        installed.manifest.tag, installed.location
+    );
+    Ok(())
+}
+
+/// A table of the installed versions matching the query, their size on disk
+/// and whether they have been built
+fn run_list_local(query: &ManifestQuery) -> Result<String, Box<dyn std::error::Error>> {
+    let manifest = ManifestManager::new()?;
+    let mut rows: Vec<[String; 7]> = vec![[
+        "BLOCKCHAIN".to_string(),
+        "VERSION".to_string(),
+        "SOURCE".to_string(),
+        "PUBLISHED".to_string(),
+        "SIZE".to_string(),
+        "BUILT".to_string(),
+        "LOCATION".to_string(),
+    ]];
+    for (key, entry) in manifest.query(query) {
+        let version = match &entry.commit {
+            Some(commit) => format!("{}@{}", entry.version, &commit[..commit.len().min(12)]),
+            None => entry.version.clone(),
+        };
+        let location = if entry.installation_location.exists() {
+            entry.installation_location.display().to_string()
+        } else {
+            format!("{} (missing)", entry.installation_location.display())
+        };
+        rows.push([
+            key.blockchain.clone(),
+            version,
+            SourceType::of(entry).to_string(),
+            entry.published_date.format("%Y-%m-%d %H:%M").to_string(),
+            human_size(disk_usage(&entry.installation_location)),
+            if entry.is_built() { "yes" } else { "no" }.to_string(),
+            location,
+        ]);
+    }
+    if rows.len() == 1 {
+        return Ok(String::from("No versions installed yet\n"));
+    }
+
+    let mut widths = [0usize; 7];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    Ok(table)
+}
+
+/// Build the manifest query of `shran ls` from its filters
+fn ls_query(cli: &Cli) -> Result<ManifestQuery, Box<dyn std::error::Error>> {
+    let value = |name: &str| cli.modifier(name).and_then(|arg| arg.value);
+    let mut query = ManifestQuery::new();
+    if let Some(blockchain) = value(ArgName::BLOCKCHAIN) {
+        query = query.with_blockchain(&blockchain);
+    }
+    if let Some(versions) = value(ArgName::VERSIONS) {
+        query = query.with_versions(versions.parse()?);
+    }
+    if let Some(source_type) = value(ArgName::SOURCE_TYPE) {
+        query = query.with_source_type(source_type.parse()?);
+    }
+    if cli.modifier(ArgName::BUILT).is_some() {
+        query = query.with_built(true);
+    } else if cli.modifier(ArgName::NOT_BUILT).is_some() {
+        query = query.with_built(false);
+    }
+    if let Some(sort) = value(ArgName::SORT) {
+        query = query.sorted_by(sort.parse::<SortKey>()?);
+    }
+    if cli.modifier(ArgName::REVERSE).is_some() {
+        query = query.descending();
+    }
+    Ok(query)
+}
+
+/// Ask a yes or no question on the terminal, anything but yes is a no
+fn confirm(question: &str) -> std::io::Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Delete every installed version matched by the selector, after the user
+/// confirmed the plan, and drop them from the manifest
+fn run_remove(selector: &str, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let mut manifest = ManifestManager::new()?;
+    let uninstaller = Uninstaller::new(std::env::current_dir()?);
+    let removals = uninstaller.plan(&manifest.entries(), selector)?;
+    for removal in &removals {
+        println!("{}", removal);
+    }
+    if cli.modifier(ArgName::DRY_RUN).is_some() {
+        println!("Dry run, nothing was removed");
+        return Ok(());
+    }
+    if cli.modifier(ArgName::YES).is_none()
+        && !confirm(&format!("Remove {} version(s)?", removals.len()))?
+    {
+        println!("Nothing was removed");
+        return Ok(());
+    }
+    for removal in removals {
+        uninstaller.remove(&removal)?;
+        manifest.remove_entry(removal.key.clone())?;
+        println!("Removed {}", removal.key);
+    }
+    Ok(())
+}
+
+/// Compare the cache with the manifest, recording the trees it lost track of
+fn run_manifest_rescan(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let mut manifest = ManifestManager::new()?;
+    let report = ManifestScanner::new().scan(&manifest.entries());
+    print!("{}", report);
+    if cli.modifier(ArgName::DRY_RUN).is_some() {
+        return Ok(());
+    }
+    // a recovered tree may have been modified since it was extracted, it
+    // gets no content hash so verify does not vouch for it
+    for (key, entry) in &report.recovered {
+        manifest.set_entry(key.clone(), entry)?;
+    }
+    if cli.modifier(ArgName::PRUNE).is_some() {
+        for key in report.orphaned {
+            manifest.remove_entry(key)?;
+        }
+    } else if !report.orphaned.is_empty() {
+        println!("Run with --prune to drop the orphaned entries");
+    }
+    Ok(())
+}
+
+/// Hash the selected trees again, every installed version except the `dir:`
+/// working trees if there is no selector, and report the files that changed
+/// since they were extracted
+///
+/// # Errors
+///
+/// Returns ShranError::TreeIntegrityError unless every selected tree is
+/// pristine
+fn run_verify(selector: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = ManifestManager::new()?;
+    let entries = manifest.entries();
+    let selected = match &selector {
+        Some(selector) => select(&entries, selector)?,
+        None => entries
+            .iter()
+            .filter(|(_, entry)| SourceType::of(entry) != SourceType::Directory)
+            .cloned()
+            .collect(),
+    };
+    let verifier = TreeVerifier::new();
+    let mut untrusted = 0;
+    for (key, entry) in &selected {
+        let tree_hash = match &entry.tree_hash {
+            Some(tree_hash) => tree_hash,
+            None => {
+                println!(
+                    "{}  no content hash recorded, fetch it again to record one",
+                    key
+                );
+                // nothing to check against is only an error when asked for
+                if selector.is_some() {
+                    untrusted += 1;
                 }
+                continue;
             }
+        };
+        match verifier.verify(&entry.installation_location, tree_hash) {
+            Ok(diff) if diff.is_pristine() => println!("{}  ok", key),
+            Ok(diff) => {
+                println!(
+                    "{}  {} modified, {} added, {} removed",
+                    key,
+                    diff.modified.len(),
+                    diff.added.len(),
+                    diff.removed.len()
+                );
+                print!("{}", diff);
+                untrusted += 1;
+            }
+            Err(e) => {
+                println!("{}  can not be verified", key);
+                eprintln!("{}", redact(&e.to_string()));
+                untrusted += 1;
+            }
+        }
+    }
+    if untrusted > 0 {
+        return Err(Box::new(ShranError::TreeIntegrityError {
+            msg: format!(
+                "{} of {} trees can not be trusted",
+                untrusted,
+                selected.len()
+            ),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+    Ok(())
+}
+
+/// Build the garbage collection policy of `shran cache gc` from its limits
+fn gc_policy(cli: &Cli) -> Result<GcPolicy, Box<dyn std::error::Error>> {
+    let value = |name: &str| cli.modifier(name).and_then(|arg| arg.value);
+    let mut policy = GcPolicy::new();
+    if let Some(count) = value(ArgName::KEEP_LAST) {
+        policy = policy.with_keep_last(count.parse()?);
+    }
+    if let Some(size) = value(ArgName::MAX_SIZE) {
+        policy = policy.with_max_size(parse_size(&size)?);
+    }
+    if let Some(age) = value(ArgName::MAX_AGE) {
+        policy = policy.with_max_age(parse_age(&age)?);
+    }
+    if policy.is_empty() {
+        return Err(Box::new(ShranError::CachePolicyError {
+            msg: "give at least one of --keep-last, --max-size or --max-age".to_string(),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+    Ok(policy)
+}
 
-            if cli.subcommand_build() {
-                run_build(&cli.args().value.unwrap());
+/// Delete the versions the policy collects after the user confirmed the
+/// plan, dropping them from the manifest as they go
+fn run_cache_gc(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let policy = gc_policy(cli)?;
+    let mut manifest = ManifestManager::new()?;
+    let collector = CacheCollector::new(std::env::current_dir()?);
+    let plan = collector.plan(&manifest.entries(), &policy, chrono::Utc::now());
+    if plan.candidates.is_empty() && plan.stale.is_empty() {
+        println!("The cache is within its limits, nothing to collect");
+        return Ok(());
+    }
+    println!("{}", plan);
+    if cli.modifier(ArgName::DRY_RUN).is_some() {
+        println!("Dry run, nothing was deleted");
+        return Ok(());
+    }
+    if cli.modifier(ArgName::YES).is_none()
+        && !confirm(&format!(
+            "Delete {} version(s) and {} stale file(s)?",
+            plan.candidates.len(),
+            plan.stale.len()
+        ))?
+    {
+        println!("Nothing was deleted");
+        return Ok(());
+    }
+    for candidate in &plan.candidates {
+        collector.uninstaller().remove(&candidate.removal)?;
+        manifest.remove_entry(candidate.removal.key.clone())?;
+        println!("Removed {}", candidate.removal.key);
+    }
+    collector.remove_stale(&plan)?;
+    Ok(())
+}
+
+/// Mark the selected versions as pinned, or not, in the manifest
+fn run_cache_pin(selector: &str, pinned: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut manifest = ManifestManager::new()?;
+    let entries = manifest.entries();
+    let selected: Vec<(ManifestKey, ManifestEntry)> = select(&entries, selector)?
+        .into_iter()
+        .map(|(key, entry)| (key.to_owned(), entry.to_owned()))
+        .collect();
+    for (key, mut entry) in selected {
+        entry.pinned = pinned;
+        manifest.set_entry(key.clone(), &entry)?;
+        println!("{} {}", if pinned { "Pinned" } else { "Unpinned" }, key);
+    }
+    Ok(())
+}
+
+fn run_cache(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    match cli.args().name.as_str() {
+        ArgName::GC => run_cache_gc(cli),
+        ArgName::DU => {
+            let manifest = ManifestManager::new()?;
+            let usage = CacheCollector::new(std::env::current_dir()?).usage(&manifest.entries());
+            print!("{}", usage);
+            Ok(())
+        }
+        name => run_cache_pin(&cli.args().value.unwrap(), name == ArgName::PIN),
+    }
+}
+
+/// Resolve the settings from the config file, the environment and the flags
+/// of the command line, and point the cache at the configured location. Has
+/// to run before the async runtime is started, see Settings::apply
+fn load_settings(cli: &Cli) -> Result<Settings, Box<dyn std::error::Error>> {
+    let flags: Vec<(&str, String)> = [
+        (Settings::JOBS, ArgName::JOBS),
+        (Settings::CACHE_DIR, ArgName::CACHE_DIR),
+        (Settings::INSTALL_PREFIX, ArgName::INSTALL_PREFIX),
+        (Settings::PROFILE, ArgName::PROFILE),
+        (Settings::COLORS, ArgName::COLOR),
+    ]
+    .iter()
+    .filter_map(|(key, name)| Some((*key, cli.modifier(name)?.value?)))
+    .collect();
+    let config_file = ShranDefault::forfile(ShranFile::ConfigFile);
+    let settings = Settings::load(&config_file, |var| std::env::var(var).ok(), &flags)?;
+    settings.apply();
+    Ok(settings)
+}
+
+fn run_config(cli: &Cli, settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    let key = cli.args().value.unwrap_or_default();
+    let setting = |key: &str| {
+        settings.get(key).ok_or_else(|| ShranError::ConfigError {
+            msg: format!("{} is not a setting", key),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        })
+    };
+    match cli.args().name.as_str() {
+        ArgName::LIST => {
+            for setting in settings.list() {
+                println!("{}", setting);
             }
+        }
+        ArgName::GET => println!("{}", setting(&key)?),
+        _ => {
+            let value = cli
+                .modifier(ArgName::VALUE)
+                .and_then(|arg| arg.value)
+                .unwrap();
+            let fs = FileSystemManager::new()?;
+            fs.write_config_setting(&key, &value)?;
+            println!(
+                "Set {} = {} in {}",
+                key,
+                value,
+                ShranDefault::forfile(ShranFile::ConfigFile)
+            );
+            let current = setting(&key)?;
+            if current.source != SettingSource::Default
+                && current.source != SettingSource::ConfigFile
+            {
+                println!("{} is still overridden by the {}", key, current.source);
+            }
+        }
+    }
+    Ok(())
+}
 
-            if cli.subcommand_fetch() {
-                match cli.args().value {
-                    Some(tag) => match run_get_tagged_release(tag).await {
-                        Ok(release) => {
-                            println!("{}", release);
+async fn run_get_remote(cli: &Cli) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let fs = FileSystemManager::new()?;
+    let token = fs.read_token()?;
+    let upstream = fs
+        .read_config()?
+        .upstream(ShranDefault::SUPPORTED_BLOCKCHAINS[0]);
+    let gclient = GithubClient::new(token, upstream, fs)?;
+    let cache = gclient.release_cache(cache_policy(cli)).await?;
+    Ok(cache.tags)
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::new() {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}", redact(&e.to_string()));
+            return ExitCode::FAILURE;
+        }
+    };
+    // the settings change the environment, which is only safe before the
+    // runtime starts its worker threads
+    let settings = match load_settings(&cli) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", redact(&e.to_string()));
+            return ExitCode::FAILURE;
+        }
+    };
+    match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime.block_on(run(cli, settings)),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli, settings: Settings) -> ExitCode {
+    let mut exit_code = ExitCode::SUCCESS;
+    if cli.subcommand_auth() {
+        let result = if cli.args().name == ArgName::STATUS {
+            run_auth_status().await
+        } else {
+            run_auth(&cli.args().value.unwrap(), &cli)
+        };
+        if let Err(e) = result {
+            eprintln!("{}", redact(&e.to_string()));
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    if cli.subcommand_build() {
+        if let Err(e) = run_build(&cli.args().value.unwrap(), &cli).await {
+            eprintln!("{}", redact(&e.to_string()));
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    if cli.subcommand_fetch() {
+        match cli.args().value {
+            Some(tag) if cli.args().name == ArgName::NOTES => {
+                match run_fetch_notes(tag, &cli, &settings).await {
+                    Ok(notes) => print!("{}", notes),
+                    Err(e) => {
+                        eprintln!("{}", redact(&e.to_string()));
+                        exit_code = ExitCode::FAILURE;
+                    }
+                }
+            }
+            Some(value)
+                if cli.args().name == ArgName::VERSIONS
+                    || (cli.args().name == ArgName::TAG && value.contains(',')) =>
+            {
+                if let Err(e) = run_fetch_many(value, &cli, &settings).await {
+                    eprintln!("{}", redact(&e.to_string()));
+                    exit_code = ExitCode::FAILURE;
+                }
+            }
+            Some(_) if cli.modifier(ArgName::GIT).is_some() => match run_git_checkout(&cli) {
+                Ok(resolved) => {
+                    println!("{}", resolved);
+                }
+                Err(e) => {
+                    eprintln!("{}", redact(&e.to_string()));
+                    exit_code = ExitCode::FAILURE;
+                }
+            },
+            Some(value) if cli.args().name == ArgName::DEPENDS => {
+                if let Err(e) = run_fetch_depends(&value) {
+                    eprintln!("{}", redact(&e.to_string()));
+                    exit_code = ExitCode::FAILURE;
+                }
+            }
+            Some(tag) if cli.modifier(ArgName::PREBUILT).is_some() => {
+                if let Err(e) = run_get_prebuilt(Some(tag), &cli).await {
+                    eprintln!("{}", redact(&e.to_string()));
+                    exit_code = ExitCode::FAILURE;
+                }
+            }
+            Some(path) if cli.args().name == ArgName::FROM_FILE => {
+                match run_import(path, &cli).await {
+                    Ok(source) => {
+                        println!("{}", source);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", redact(&e.to_string()));
+                        exit_code = ExitCode::FAILURE;
+                    }
+                }
+            }
+            Some(spec) if cli.args().name == ArgName::SOURCE => {
+                match run_fetch_source(spec, &cli).await {
+                    Ok(source) => {
+                        println!("{}", source);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", redact(&e.to_string()));
+                        exit_code = ExitCode::FAILURE;
+                    }
+                }
+            }
+            Some(_) if cli.args().name != ArgName::TAG => match run_get_ref(&cli).await {
+                Ok((resolved, archive)) => {
+                    println!("{}\n{}", resolved, archive);
+                }
+                Err(e) => {
+                    eprintln!("{}", redact(&e.to_string()));
+                    exit_code = ExitCode::FAILURE;
+                }
+            },
+            Some(tag) => match run_get_tagged_release(tag, &cli).await {
+                Ok(release) => {
+                    println!("{}", release);
+                }
+                Err(e) => {
+                    eprintln!("{}", redact(&e.to_string()));
+                    exit_code = ExitCode::FAILURE;
+                }
+            },
+            None => {
+                if cli.args().name == ArgName::LIST_REMOTE {
+                    match run_get_remote(&cli).await {
+                        Ok(tags) => {
+                            for tag in tags {
+                                println!("{}", tag);
+                            }
                         }
                         Err(e) => {
-                            eprintln!("{}", e);
+                            eprintln!("{}", redact(&e.to_string()));
                             exit_code = ExitCode::FAILURE;
                         }
-                    },
-                    None => {
-                        if cli.args().name == ArgName::LIST_REMOTE {
-                            match run_get_remote().await {
-                                Ok(tags) => {
-                                    for tag in tags {
-                                        println!("{}", tag);
-                                    }
-                                }
-                                Err(e) => {
-                                    eprintln!("{}", e);
-                                    exit_code = ExitCode::FAILURE;
-                                }
-                            }
+                    }
+                }
+                if cli.args().name == ArgName::LATEST && cli.modifier(ArgName::PREBUILT).is_some() {
+                    if let Err(e) = run_get_prebuilt(None, &cli).await {
+                        eprintln!("{}", redact(&e.to_string()));
+                        exit_code = ExitCode::FAILURE;
+                    }
+                } else if cli.args().name == ArgName::LATEST {
+                    match run_get_latest(&cli).await {
+                        Ok(release) => {
+                            println!("{}", release);
                         }
-                        if cli.args().name == ArgName::LATEST {
-                            match run_get_latest().await {
-                                Ok(release) => {
-                                    println!("{}", release);
-                                }
-                                Err(e) => {
-                                    eprintln!("{}", e);
-                                    exit_code = ExitCode::FAILURE;
-                                }
-                            }
+                        Err(e) => {
+                            eprintln!("{}", redact(&e.to_string()));
+                            exit_code = ExitCode::FAILURE;
                         }
-                        if cli.args().name == ArgName::LIST_LOCAL {
-                            println!("{} not implemented yet", cli.args().name);
+                    }
+                }
+                if cli.args().name == ArgName::LIST_LOCAL {
+                    match run_list_local(&ManifestQuery::new()) {
+                        Ok(table) => print!("{}", table),
+                        Err(e) => {
+                            eprintln!("{}", redact(&e.to_string()));
+                            exit_code = ExitCode::FAILURE;
                         }
                     }
                 }
             }
+        }
+    }
 
-            if cli.subcommand_generate() {
-                run_generate(&cli.args().name)
+    if cli.subcommand_notes() {
+        match run_notes(cli.args().value.unwrap(), &cli, &settings).await {
+            Ok(notes) => print!("{}", notes),
+            Err(e) => {
+                eprintln!("{}", redact(&e.to_string()));
+                exit_code = ExitCode::FAILURE;
             }
         }
-        Err(e) => {
-            eprintln!("{}", e);
+    }
+
+    if cli.subcommand_bundle() {
+        let result = match cli.args().value {
+            Some(path) => run_bundle_install(&path, &cli),
+            None => run_bundle_create(&cli, &settings),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", redact(&e.to_string()));
             exit_code = ExitCode::FAILURE;
         }
     }
+
+    if cli.subcommand_ls() {
+        match ls_query(&cli).and_then(|query| run_list_local(&query)) {
+            Ok(table) => print!("{}", table),
+            Err(e) => {
+                eprintln!("{}", redact(&e.to_string()));
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if cli.subcommand_remove() {
+        if let Err(e) = run_remove(&cli.args().value.unwrap(), &cli) {
+            eprintln!("{}", redact(&e.to_string()));
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    if cli.subcommand_manifest() {
+        if let Err(e) = run_manifest_rescan(&cli) {
+            eprintln!("{}", redact(&e.to_string()));
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    if cli.subcommand_verify() {
+        if let Err(e) = run_verify(cli.args().value) {
+            eprintln!("{}", redact(&e.to_string()));
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    if cli.subcommand_cache() {
+        if let Err(e) = run_cache(&cli) {
+            eprintln!("{}", redact(&e.to_string()));
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    if cli.subcommand_config() {
+        if let Err(e) = run_config(&cli, &settings) {
+            eprintln!("{}", redact(&e.to_string()));
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    if cli.subcommand_generate() {
+        run_generate(&cli.args().name)
+    }
     exit_code
 }
@@ -0,0 +1,298 @@
+use crate::config::{ShranDefault, Upstream};
+use crate::error::ShranError;
+use crate::utils::{Archiver, Downloader, FileSystemManager, TapeArchive};
+use crate::verify::signatures::parse_sha256sums;
+use crate::verify::ThresholdVerifier;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// The platform suffix of the official release binaries for the machine
+/// shran runs on, [e.g.] `x86_64-linux-gnu`. Returns None for hosts no
+/// `.tar.gz` release is published for.
+pub fn host_platform() -> Option<&'static str> {
+    match (std::env::consts::ARCH, std::env::consts::OS) {
+        ("x86_64", "linux") => Some("x86_64-linux-gnu"),
+        ("aarch64", "linux") => Some("aarch64-linux-gnu"),
+        ("arm", "linux") => Some("arm-linux-gnueabihf"),
+        ("powerpc64", "linux") => Some("powerpc64-linux-gnu"),
+        ("riscv64", "linux") => Some("riscv64-linux-gnu"),
+        ("x86_64", "macos") => Some("x86_64-apple-darwin"),
+        ("aarch64", "macos") => Some("arm64-apple-darwin"),
+        _ => None,
+    }
+}
+
+/// A release binary tarball that has been verified and installed
+#[derive(Debug)]
+pub struct InstalledBinaries {
+    pub tag: String,
+    pub platform: String,
+    /// The url the tarball was downloaded from
+    pub source: String,
+    pub sha256: String,
+    /// The versioned prefix holding `bin/`, `lib/`, `include/` and `share/`
+    pub prefix: String,
+}
+
+impl fmt::Display for InstalledBinaries {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Installed prebuilt {} ({}) to {}\nSHA256: {}",
+            self.tag, self.platform, self.prefix, self.sha256
+        )
+    }
+}
+
+/// Downloads the official prebuilt binaries of a release instead of
+/// compiling them. The tarball is only installed if its digest matches
+/// the release's SHA256SUMS, which in turn can be required to be signed
+/// by enough trusted builders.
+pub struct PrebuiltRelease {
+    tag: String,
+    upstream: Upstream,
+    platform: String,
+    verifier: Option<ThresholdVerifier>,
+    root: String,
+}
+
+impl PrebuiltRelease {
+    /// Build a new PrebuiltRelease
+    ///
+    /// # Params
+    ///
+    /// 1. tag: The release to install [e.g.] v25.0
+    /// 2. upstream: Its `binaries_url` is where the release directories live
+    /// 3. platform: The platform suffix of the tarball, see host_platform
+    pub fn new(tag: String, upstream: Upstream, platform: String) -> Self {
+        Self {
+            tag,
+            upstream,
+            platform,
+            verifier: None,
+            root: ShranDefault::prebuilt_dir(),
+        }
+    }
+
+    /// Require SHA256SUMS to carry enough trusted builder signatures
+    pub fn with_verifier(mut self, verifier: ThresholdVerifier) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Install beneath `root` instead of the prebuilt cache directory
+    pub fn with_root(mut self, root: String) -> Self {
+        self.root = root;
+        self
+    }
+
+    /// File name of the binary tarball, [e.g.] `bitcoin-25.0-x86_64-linux-gnu.tar.gz`
+    pub fn file_name(&self) -> String {
+        format!(
+            "bitcoin-{}-{}{}",
+            self.tag.trim_start_matches('v'),
+            self.platform,
+            ShranDefault::FILE_EXTENSION
+        )
+    }
+
+    /// The versioned prefix the binaries are installed to
+    pub fn prefix(&self) -> String {
+        format!("{}/{}-{}", self.root, self.tag, self.platform)
+    }
+
+    /// Download, verify and install the binaries
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::FileSystemError if the prefix already exists,
+    /// ShranError::SignatureThresholdError if the tarball is not listed in
+    /// SHA256SUMS or the signatures fall short of the threshold, and
+    /// ShranError::DownloadError if the tarball does not match its checksum.
+    pub fn install(&self) -> Result<InstalledBinaries, Box<dyn Error>> {
+        let prefix = self.prefix();
+        if Path::new(&prefix).exists() {
+            return Err(Box::new(ShranError::FileSystemError {
+                msg: format!("{} already exists", prefix),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }));
+        }
+
+        let base = self.upstream.binaries_url(&self.tag);
+        let sums = Downloader::new(
+            vec![format!("{}/{}", base, ShranDefault::SHA256SUMS_FILENAME)],
+            None,
+        )
+        .fetch()?;
+        let sums = match &self.verifier {
+            Some(verifier) => {
+                let signature = Downloader::new(
+                    vec![format!(
+                        "{}/{}",
+                        base,
+                        ShranDefault::SHA256SUMS_SIGNATURE_FILENAME
+                    )],
+                    None,
+                )
+                .fetch()?;
+                let fs = FileSystemManager::new()?;
+                let sums_path = fs.write_signature_file(
+                    &self.tag,
+                    ShranDefault::SHA256SUMS_FILENAME,
+                    sums.bytes,
+                )?;
+                let signature_path = fs.write_signature_file(
+                    &self.tag,
+                    ShranDefault::SHA256SUMS_SIGNATURE_FILENAME,
+                    signature.bytes,
+                )?;
+                let report = verifier.verify_files(&self.tag, &sums_path, &signature_path)?;
+                println!("{}", report);
+                report.sums
+            }
+            None => parse_sha256sums(&String::from_utf8_lossy(&sums.bytes)),
+        };
+
+        let file_name = self.file_name();
+        let expected = match sums.get(&file_name) {
+            Some(sha256) => sha256.clone(),
+            None => {
+                return Err(Box::new(ShranError::SignatureThresholdError {
+                    msg: format!("{} is not listed in SHA256SUMS", file_name),
+                    file: file!(),
+                    line: line!(),
+                    column: column!(),
+                }))
+            }
+        };
+        let download =
+            Downloader::new(vec![format!("{}/{}", base, file_name)], Some(expected)).fetch()?;
+        println!("{}", download);
+
+        // unpack next to the prefix, the tarball's own top level directory
+        // (bitcoin-25.0) does not say which platform it was built for. What
+        // an interrupted install left behind there is stale.
+        let staging = format!("{}/.{}", self.root, file_name);
+        if Path::new(&staging).exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        let unpacked = unpack_staged(&staging, &file_name, &download.bytes, &prefix);
+        if unpacked.is_err() {
+            let _ = fs::remove_dir_all(&staging);
+        }
+        unpacked?;
+
+        Ok(InstalledBinaries {
+            tag: self.tag.clone(),
+            platform: self.platform.clone(),
+            source: download.source,
+            sha256: download.sha256,
+            prefix,
+        })
+    }
+}
+
+/// Unpack the tarball `bytes` in `staging`, and move its contents to `prefix`
+fn unpack_staged(
+    staging: &str,
+    file_name: &str,
+    bytes: &[u8],
+    prefix: &str,
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(staging)?;
+    let archive_path = format!("{}/{}", staging, file_name);
+    fs::write(&archive_path, bytes)?;
+    let archive = TapeArchive::new(&archive_path, staging);
+    let root_dir = archive.root_dir()?;
+    archive.unpack()?;
+    fs::remove_file(&archive_path)?;
+    match root_dir {
+        Some(dir) => {
+            fs::rename(format!("{}/{}", staging, dir), prefix)?;
+            fs::remove_dir_all(staging)?;
+        }
+        None => fs::rename(staging, prefix)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PrebuiltRelease;
+    use crate::config::Upstream;
+    use crate::utils::downloader::sha256_hex;
+    use crate::utils::mock_archive::gzip_tarball;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_install_prebuilt_binaries() {
+        let dir = env::temp_dir().join(format!("shran-prebuilt-{}", std::process::id()));
+        let release_dir = dir.join("bin/bitcoin-core-25.0");
+        fs::create_dir_all(&release_dir).unwrap();
+        let bytes = gzip_tarball(&[("bitcoin-25.0/bin/bitcoind", b"#!/bin/sh\necho bitcoind\n")]);
+        fs::write(
+            release_dir.join("bitcoin-25.0-x86_64-linux-gnu.tar.gz"),
+            &bytes,
+        )
+        .unwrap();
+        fs::write(
+            release_dir.join("SHA256SUMS"),
+            format!(
+                "{}  bitcoin-25.0-x86_64-linux-gnu.tar.gz\n{}  bitcoin-25.0-arm-linux-gnueabihf.tar.gz\n",
+                sha256_hex(&bytes),
+                sha256_hex(b"other")
+            ),
+        )
+        .unwrap();
+        let upstream = Upstream {
+            binaries_url: format!("file://{}/bin", dir.display()),
+            ..Default::default()
+        };
+        let root = dir.join("prefix").to_string_lossy().to_string();
+
+        // listed in SHA256SUMS, but the tarball does not match
+        fs::write(
+            release_dir.join("bitcoin-25.0-arm-linux-gnueabihf.tar.gz"),
+            &bytes,
+        )
+        .unwrap();
+        let tampered = PrebuiltRelease::new(
+            "v25.0".to_string(),
+            upstream.clone(),
+            "arm-linux-gnueabihf".to_string(),
+        )
+        .with_root(root.clone());
+        assert!(tampered.install().is_err());
+
+        // left behind by an interrupted install
+        let staging = format!("{}/.bitcoin-25.0-x86_64-linux-gnu.tar.gz", root);
+        fs::create_dir_all(format!("{}/bitcoin-25.0", staging)).unwrap();
+        fs::write(format!("{}/bitcoin-25.0/stale", staging), "stale").unwrap();
+
+        let release = PrebuiltRelease::new(
+            "v25.0".to_string(),
+            upstream,
+            "x86_64-linux-gnu".to_string(),
+        )
+        .with_root(root.clone());
+        let installed = release.install().unwrap();
+        assert_eq!(installed.prefix, format!("{}/v25.0-x86_64-linux-gnu", root));
+        assert_eq!(installed.sha256, sha256_hex(&bytes));
+        assert!(
+            fs::read_to_string(format!("{}/bin/bitcoind", installed.prefix))
+                .unwrap()
+                .contains("bitcoind")
+        );
+        assert!(!Path::new(&format!("{}/stale", installed.prefix)).exists());
+        assert!(!Path::new(&staging).exists());
+        // a second install would overwrite the first
+        assert!(release.install().is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod binaries;
+
+pub use binaries::{host_platform, PrebuiltRelease};
@@ -0,0 +1,495 @@
+use crate::config::ShranDefault;
+use crate::error::ShranError;
+use crate::utils::downloader::sha256_hex;
+use crate::utils::Downloader;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+/// A single source archive one of the `depends/packages/*.mk` files asks for
+#[derive(Debug, Clone, PartialEq)]
+pub struct DependsSource {
+    pub package: String,
+    /// The name the archive is stored under in `SOURCES_PATH`
+    pub file_name: String,
+    pub sha256: String,
+    /// Where the depends system would download the archive from
+    pub url: String,
+}
+
+/// What a prefetch did, per archive
+#[derive(Debug, Default)]
+pub struct DependsReport {
+    pub sources_path: String,
+    pub downloaded: Vec<String>,
+    pub cached: Vec<String>,
+    /// Archives that could not be fetched, with the reason
+    pub failed: Vec<String>,
+}
+
+impl fmt::Display for DependsReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Depends sources: {} downloaded, {} already cached, {} failed\nSOURCES_PATH={}",
+            self.downloaded.len(),
+            self.cached.len(),
+            self.failed.len(),
+            self.sources_path
+        )?;
+        for failure in &self.failed {
+            write!(f, "\n  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Downloads every source archive the depends system of a bitcoin source
+/// tree needs into a shared cache, so that `make -C depends` never has to
+/// touch the network. The tree itself is only read, the cache is passed to
+/// make as `SOURCES_PATH`.
+///
+/// # Example
+/// ```no_run
+/// let fetcher = DependsFetcher::new(location, ShranDefault::depends_sources_dir());
+/// let report = fetcher.prefetch()?;
+/// ```
+pub struct DependsFetcher {
+    tree: String,
+    sources_path: String,
+}
+
+impl DependsFetcher {
+    /// # Params
+    ///
+    /// 1. tree: The root of an extracted bitcoin source tree
+    /// 2. sources_path: The shared directory archives are downloaded to
+    pub fn new(tree: String, sources_path: String) -> Self {
+        Self { tree, sources_path }
+    }
+
+    /// Download all depends sources which are not cached yet, or whose
+    /// cached copy does not match its hash. Every archive is checked
+    /// against the `sha256_hash` of its package definition.
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::FileSystemError if the tree has no depends packages,
+    /// a single archive failing to download is reported in DependsReport::failed
+    /// instead, so one dead upstream does not stop the others.
+    pub fn prefetch(&self) -> Result<DependsReport, Box<dyn Error>> {
+        let sources = parse_packages(&format!("{}/depends/packages", self.tree))?;
+        fs::create_dir_all(&self.sources_path)?;
+        let mut report = DependsReport {
+            sources_path: self.sources_path.clone(),
+            ..Default::default()
+        };
+        for source in sources {
+            let path = format!("{}/{}", self.sources_path, source.file_name);
+            if let Ok(bytes) = fs::read(&path) {
+                if sha256_hex(&bytes) == source.sha256 {
+                    report.cached.push(source.file_name);
+                    continue;
+                }
+            }
+            let candidates = vec![
+                source.url.clone(),
+                format!(
+                    "{}/{}",
+                    ShranDefault::DEPENDS_FALLBACK_URL,
+                    source.file_name
+                ),
+            ];
+            match Downloader::new(candidates, Some(source.sha256.clone())).fetch() {
+                Ok(download) => {
+                    fs::write(&path, download.bytes)?;
+                    report.downloaded.push(source.file_name);
+                }
+                Err(e) => report
+                    .failed
+                    .push(format!("{} ({}): {}", source.file_name, source.package, e)),
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Read every package definition in a `depends/packages` directory
+///
+/// # Errors
+///
+/// Returns ShranError::FileSystemError if the directory can not be read
+pub fn parse_packages(dir: &str) -> Result<Vec<DependsSource>, ShranError<'static>> {
+    let not_found = |e: std::io::Error| ShranError::FileSystemError {
+        msg: format!("{}: {}", dir, e),
+        file: file!(),
+        line: line!(),
+        column: column!(),
+    };
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .map_err(not_found)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "mk"))
+        .collect();
+    files.sort();
+
+    let mut vars = MakeVars::default();
+    let mut packages: Vec<String> = Vec::new();
+    for file in files {
+        // packages.mk only lists which packages make up each set
+        if file.file_name().is_some_and(|name| name == "packages.mk") {
+            continue;
+        }
+        let contents = fs::read_to_string(&file).map_err(not_found)?;
+        if let Some(package) = vars.read(&contents) {
+            packages.push(package);
+        }
+    }
+
+    let packages: HashSet<String> = packages.into_iter().collect();
+    let mut names: Vec<&String> = packages.iter().collect();
+    names.sort();
+    let mut sources: Vec<DependsSource> = Vec::new();
+    for package in names {
+        sources.extend(vars.sources(package, &packages));
+    }
+    Ok(sources)
+}
+
+/// Just enough of make's variable handling to evaluate the download
+/// related assignments of a depends package, `$(package)` references,
+/// nested `$($(package)_version)` references and `$(subst ...)`.
+#[derive(Default)]
+struct MakeVars {
+    vars: HashMap<String, String>,
+}
+
+impl MakeVars {
+    /// Record the assignments of one package file, returns the package name
+    fn read(&mut self, contents: &str) -> Option<String> {
+        let mut package: Option<String> = None;
+        let mut in_define = false;
+        let mut logical = String::new();
+        for line in contents.lines() {
+            // recipes, comments and multi line defines never carry download info
+            if line.starts_with('\t') || line.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Some(continued) = line.strip_suffix('\\') {
+                logical.push_str(continued);
+                logical.push(' ');
+                continue;
+            }
+            logical.push_str(line);
+            let statement = std::mem::take(&mut logical);
+            let statement = statement.trim();
+            if statement.starts_with("define ") {
+                in_define = true;
+                continue;
+            }
+            if in_define {
+                in_define = statement != "endef";
+                continue;
+            }
+            let (lhs, op, rhs) = match split_assignment(statement) {
+                Some(assignment) => assignment,
+                None => continue,
+            };
+            let current = package.clone().unwrap_or_default();
+            let name = lhs.replace("$(package)", &current);
+            let value = rhs.replace("$(package)", &current);
+            if name == "package" {
+                package = Some(value);
+                continue;
+            }
+            match op {
+                "+=" => {
+                    let entry = self.vars.entry(name).or_default();
+                    if !entry.is_empty() {
+                        entry.push(' ');
+                    }
+                    entry.push_str(&value);
+                }
+                "?=" => {
+                    self.vars.entry(name).or_insert(value);
+                }
+                _ => {
+                    self.vars.insert(name, value);
+                }
+            }
+        }
+        package
+    }
+
+    /// Every archive of a package, the main one and any extra ones
+    /// declared as `$(package)_<name>_file_name`
+    fn sources(&self, package: &str, packages: &HashSet<String>) -> Vec<DependsSource> {
+        let prefix = format!("{}_", package);
+        let mut names: Vec<&String> = self
+            .vars
+            .keys()
+            .filter(|key| key.starts_with(&prefix) && key.ends_with("file_name"))
+            .collect();
+        names.sort();
+
+        let mut sources: Vec<DependsSource> = Vec::new();
+        for name in names {
+            let stem = name.trim_end_matches("file_name");
+            // `libxcb_util_file_name` belongs to libxcb_util, not to libxcb
+            let owner = stem.trim_end_matches('_');
+            if !stem.ends_with('_') || (owner != package && packages.contains(owner)) {
+                continue;
+            }
+            let sha256 = match self.vars.get(&format!("{}sha256_hash", stem)) {
+                Some(hash) => self.expand(hash, 0),
+                None => continue,
+            };
+            let file_name = self.expand(&self.vars[name], 0);
+            let download_path = self
+                .vars
+                .get(&format!("{}download_path", stem))
+                .or_else(|| self.vars.get(&format!("{}download_path", prefix)))
+                .map(|path| self.expand(path, 0))
+                .unwrap_or_default();
+            let download_file = self
+                .vars
+                .get(&format!("{}download_file", stem))
+                .map(|file| self.expand(file, 0))
+                .unwrap_or_else(|| file_name.clone());
+            sources.push(DependsSource {
+                package: package.to_string(),
+                url: format!("{}/{}", download_path.trim_end_matches('/'), download_file),
+                file_name,
+                sha256: sha256.to_lowercase(),
+            });
+        }
+        sources
+    }
+
+    /// Expand every `$(...)` reference in `text`
+    fn expand(&self, text: &str, depth: usize) -> String {
+        // guards against variables that reference themselves
+        if depth > 16 {
+            return String::new();
+        }
+        let mut expanded = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("$(") {
+            expanded.push_str(&rest[..start]);
+            let inner = &rest[start + 2..];
+            let end = match closing_paren(inner) {
+                Some(end) => end,
+                None => {
+                    expanded.push_str(&rest[start..]);
+                    return expanded;
+                }
+            };
+            expanded.push_str(&self.evaluate(&inner[..end], depth));
+            rest = &inner[end + 1..];
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+
+    /// Evaluate the contents of a single `$(...)`
+    fn evaluate(&self, reference: &str, depth: usize) -> String {
+        if let Some(args) = reference.strip_prefix("subst ") {
+            let mut parts = args.splitn(3, ',');
+            if let (Some(from), Some(to), Some(text)) = (parts.next(), parts.next(), parts.next()) {
+                let from = self.expand(from, depth + 1);
+                let text = self.expand(text, depth + 1);
+                if from.is_empty() {
+                    return text;
+                }
+                return text.replace(&from, &self.expand(to, depth + 1));
+            }
+            return String::new();
+        }
+        let name = self.expand(reference, depth + 1);
+        match self.vars.get(name.trim()) {
+            Some(value) => self.expand(value, depth + 1),
+            None => String::new(),
+        }
+    }
+}
+
+/// Split `name op value` where op is one of `=`, `:=`, `+=` or `?=`
+fn split_assignment(statement: &str) -> Option<(&str, &str, &str)> {
+    let eq = statement.find('=')?;
+    let (lhs, op) = match statement[..eq].chars().last() {
+        Some(':') | Some('+') | Some('?') => (&statement[..eq - 1], &statement[eq - 1..=eq]),
+        _ => (&statement[..eq], "="),
+    };
+    let lhs = lhs.trim();
+    // conditionals and rules are not assignments
+    if lhs.is_empty() || lhs.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((lhs, op, statement[eq + 1..].trim()))
+}
+
+/// Index of the parenthesis closing the one just before `text`
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut open = 1;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => open += 1,
+            ')' => {
+                open -= 1;
+                if open == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_packages, DependsFetcher};
+    use crate::utils::downloader::sha256_hex;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    const BOOST_MK: &str = "package=boost
+$(package)_version=1.81.0
+$(package)_download_path=https://boostorg.jfrog.io/artifactory/main/release/$($(package)_version)/source/
+$(package)_file_name=boost_$(subst .,_,$($(package)_version)).tar.bz2
+$(package)_sha256_hash=71FEEED900FBCCCA04A3B4F2F84A7C217186F28A940ED8B7ED4725986BAF99FA
+
+define $(package)_stage_cmds
+  mkdir -p $($(package)_staging_prefix_dir)/include && \\
+  cp -r boost $($(package)_staging_prefix_dir)/include
+endef
+";
+
+    fn qt_mk(download_path: &str, hash: &str, translations_hash: &str) -> String {
+        format!(
+            "package=qt
+$(package)_version=5.15.5
+$(package)_download_path={}
+$(package)_suffix=everywhere-opensource-src-$($(package)_version).tar.xz
+$(package)_file_name=qtbase-$($(package)_suffix)
+$(package)_sha256_hash={}
+ifeq ($(host_os),darwin)
+$(package)_dependencies=native_$(package)
+endif
+$(package)_qttranslations_file_name=qttranslations-$($(package)_suffix)
+$(package)_qttranslations_sha256_hash={}
+$(package)_extra_sources = $($(package)_qttranslations_file_name)
+
+$(package)_config_opts = -release \\
+  -silent
+",
+            download_path, hash, translations_hash
+        )
+    }
+
+    fn depends_tree(name: &str) -> String {
+        let tree = env::temp_dir().join(format!("shran-depends-{}-{}", name, std::process::id()));
+        fs::create_dir_all(tree.join("depends/packages")).unwrap();
+        fs::write(tree.join("depends/packages/boost.mk"), BOOST_MK).unwrap();
+        fs::write(
+            tree.join("depends/packages/packages.mk"),
+            "packages:=boost\nqt_packages = qt\n",
+        )
+        .unwrap();
+        tree.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_parse_depends_packages() {
+        let tree = depends_tree("parse");
+        fs::write(
+            format!("{}/depends/packages/qt.mk", tree),
+            qt_mk(
+                "https://download.qt.io/archive/qt/5.15/5.15.5/submodules",
+                "aa",
+                "bb",
+            ),
+        )
+        .unwrap();
+
+        let sources = parse_packages(&format!("{}/depends/packages", tree)).unwrap();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].package, "boost");
+        assert_eq!(sources[0].file_name, "boost_1_81_0.tar.bz2");
+        assert_eq!(
+            sources[0].url,
+            "https://boostorg.jfrog.io/artifactory/main/release/1.81.0/source/boost_1_81_0.tar.bz2"
+        );
+        assert_eq!(
+            sources[0].sha256,
+            "71feeed900fbccca04a3b4f2f84a7c217186f28a940ed8b7ed4725986baf99fa"
+        );
+        assert_eq!(
+            sources[1].file_name,
+            "qtbase-everywhere-opensource-src-5.15.5.tar.xz"
+        );
+        assert_eq!(
+            sources[2].file_name,
+            "qttranslations-everywhere-opensource-src-5.15.5.tar.xz"
+        );
+        assert_eq!(sources[2].sha256, "bb");
+        let _ = fs::remove_dir_all(&tree);
+    }
+
+    #[test]
+    fn test_prefetch_depends_sources() {
+        let tree = depends_tree("prefetch");
+        // only qt is prefetched, boost would need the network
+        fs::remove_file(format!("{}/depends/packages/boost.mk", tree)).unwrap();
+        let mirror = format!("{}/mirror", tree);
+        fs::create_dir_all(&mirror).unwrap();
+        let qtbase = b"qtbase archive";
+        let translations = b"qttranslations archive";
+        fs::write(
+            format!("{}/qtbase-everywhere-opensource-src-5.15.5.tar.xz", mirror),
+            qtbase,
+        )
+        .unwrap();
+        fs::write(
+            format!(
+                "{}/qttranslations-everywhere-opensource-src-5.15.5.tar.xz",
+                mirror
+            ),
+            translations,
+        )
+        .unwrap();
+        fs::write(
+            format!("{}/depends/packages/qt.mk", tree),
+            qt_mk(
+                &format!("file://{}", mirror),
+                &sha256_hex(qtbase),
+                &sha256_hex(translations),
+            ),
+        )
+        .unwrap();
+
+        let sources_path = format!("{}/cache", tree);
+        let fetcher = DependsFetcher::new(tree.clone(), sources_path.clone());
+        let report = fetcher.prefetch().unwrap();
+        assert_eq!(report.downloaded.len(), 2);
+        assert!(report.failed.is_empty());
+        assert_eq!(
+            fs::read(format!(
+                "{}/qtbase-everywhere-opensource-src-5.15.5.tar.xz",
+                sources_path
+            ))
+            .unwrap(),
+            qtbase
+        );
+
+        let report = fetcher.prefetch().unwrap();
+        assert_eq!(report.cached.len(), 2);
+        assert!(report.downloaded.is_empty());
+
+        // the tree is only read, its content hash must not change
+        assert!(!Path::new(&format!("{}/depends/sources", tree)).exists());
+        let _ = fs::remove_dir_all(&tree);
+    }
+}
@@ -0,0 +1,75 @@
+use super::git;
+use super::{ProvidedSource, SourceProvider};
+use crate::error::ShranError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Uses a local directory, [e.g.] a developer's working tree, in place.
+/// Nothing is copied, so local modifications are picked up by every build.
+pub struct DirectoryProvider {
+    path: String,
+}
+
+impl DirectoryProvider {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait(?Send)]
+impl SourceProvider for DirectoryProvider {
+    async fn provide(&self) -> Result<ProvidedSource, Box<dyn Error>> {
+        if !Path::new(&self.path).is_dir() {
+            return Err(Box::new(ShranError::FileSystemError {
+                msg: format!("{} is not a directory", self.path),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }));
+        }
+        let location = fs::canonicalize(&self.path)?;
+        let location = location.to_string_lossy().to_string();
+        let modified: DateTime<Utc> = fs::metadata(&location)?.modified()?.into();
+        // a working tree of a git repository is pinned to its HEAD
+        let commit = git::git(&["-C", &location, "rev-parse", "--verify", "HEAD"]).ok();
+        let version = match location.rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => location.clone(),
+        };
+        Ok(ProvidedSource {
+            version,
+            published_date: modified.to_string(),
+            location,
+            commit,
+            sha256: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DirectoryProvider;
+    use crate::sources::SourceProvider;
+    use std::env;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_directory_provider_uses_tree_in_place() {
+        let dir = env::temp_dir().join(format!("shran-dir-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let provider = DirectoryProvider::new(dir.to_string_lossy().to_string());
+        let source = provider.provide().await.unwrap();
+        assert_eq!(
+            source.location,
+            fs::canonicalize(&dir).unwrap().to_string_lossy()
+        );
+        assert_eq!(source.commit, None);
+        let _ = fs::remove_dir_all(&dir);
+
+        let missing = DirectoryProvider::new(dir.to_string_lossy().to_string());
+        assert!(missing.provide().await.is_err());
+    }
+}
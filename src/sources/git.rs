@@ -0,0 +1,377 @@
+use super::{ProvidedSource, SourceProvider};
+use crate::config::ShranDefault;
+use crate::error::ShranError;
+use crate::github::{GitRef, ResolvedRef};
+use async_trait::async_trait;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Keeps a single bare mirror clone of an upstream repository in the cache,
+/// and checks out versions from it as worktrees or shallow clones.
+///
+/// Every checkout shares the objects of the mirror, so switching between
+/// tags is cheap, and `git log v24.0..v25.0` works inside any worktree.
+/// Updating the mirror only transfers what changed since the last sync.
+pub struct GitMirror {
+    /// Anything `git clone` understands, including local paths
+    remote: String,
+    mirror_dir: String,
+    checkout_root: String,
+}
+
+impl GitMirror {
+    /// Build a new GitMirror, the mirror lives at
+    /// `~/.cache/shran/<blockchain>/<repo>.git` next to the extracted archives.
+    ///
+    /// # Params
+    ///
+    /// 1. remote: The url of the upstream repository
+    /// 2. blockchain: The blockchain the repository belongs to [e.g.] bitcoin
+    /// 3. repo: The name of the repository, used to name the mirror and checkouts
+    pub fn new(remote: String, blockchain: &str, repo: &str) -> Self {
+        let checkout_root = format!("{}/{}", ShranDefault::cache_dir(), blockchain);
+        Self::with_root(remote, &checkout_root, repo)
+    }
+
+    /// Same as GitMirror::new, but keeps the mirror and its checkouts in `root`
+    pub fn with_root(remote: String, root: &str, repo: &str) -> Self {
+        Self {
+            remote,
+            mirror_dir: format!("{}/{}.git", root, repo),
+            checkout_root: format!("{}/{}", root, repo),
+        }
+    }
+
+    /// Clone the mirror if it does not exist yet, otherwise fetch
+    /// whatever changed upstream since the last sync. The mirror is named
+    /// after the repository, so a mirror cloned from another remote, [e.g.]
+    /// a fork of the same name, is pointed at this remote first and its refs
+    /// are replaced by the ones of this remote.
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::GitError if git fails, or an io::Error if
+    /// git can not be run at all.
+    pub fn sync(&self) -> Result<(), Box<dyn Error>> {
+        if Path::new(&self.mirror_dir).exists() {
+            let origin = git(&[
+                "-C",
+                &self.mirror_dir,
+                "config",
+                "--get",
+                "remote.origin.url",
+            ])
+            .unwrap_or_default();
+            if origin != self.remote {
+                git(&[
+                    "-C",
+                    &self.mirror_dir,
+                    "remote",
+                    "set-url",
+                    "origin",
+                    "--",
+                    &self.remote,
+                ])?;
+            }
+            git(&["-C", &self.mirror_dir, "fetch", "--prune", "origin"])?;
+        } else {
+            if let Some(parent) = Path::new(&self.mirror_dir).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            git(&["clone", "--mirror", "--", &self.remote, &self.mirror_dir])?;
+        }
+        Ok(())
+    }
+
+    /// Resolve a git ref to the commit it points at in the mirror.
+    /// Pull requests are resolved through the `refs/pull/<number>/head`
+    /// refs github publishes, so no api call is needed.
+    pub fn resolve(&self, git_ref: &GitRef) -> Result<ResolvedRef, Box<dyn Error>> {
+        let commitish = format!("{}^{{commit}}", revision(git_ref));
+        // rev-parse echoes a `--` instead of ending its options with it
+        let sha = git(&[
+            "-C",
+            &self.mirror_dir,
+            "rev-parse",
+            "--verify",
+            "--end-of-options",
+            &commitish,
+        ])?;
+        let committed_at = git(&["-C", &self.mirror_dir, "show", "-s", "--format=%cI", &sha])?;
+        Ok(ResolvedRef {
+            git_ref: git_ref.to_owned(),
+            sha,
+            committed_at,
+            title: None,
+        })
+    }
+
+    /// Check out a resolved commit, returning the directory it lives in,
+    /// `<repo>-git-<sha>` next to the mirror. A worktree of the mirror is
+    /// created by default, a shallow clone containing only that commit if
+    /// `shallow` is set. An existing checkout is reused only if its HEAD is
+    /// that commit, anything else at its path, [e.g.] what an interrupted
+    /// checkout left behind, is replaced.
+    pub fn checkout(
+        &self,
+        resolved: &ResolvedRef,
+        shallow: bool,
+    ) -> Result<String, Box<dyn Error>> {
+        let path = format!("{}-git-{}", self.checkout_root, resolved.sha);
+        if Path::new(&path).exists() {
+            // without its own .git, rev-parse would answer for a repository above
+            let head = if Path::new(&path).join(".git").exists() {
+                git(&["-C", &path, "rev-parse", "HEAD"]).ok()
+            } else {
+                None
+            };
+            if head.as_deref() == Some(resolved.sha.as_str()) {
+                return Ok(path);
+            }
+            self.remove_checkout(&path)?;
+        }
+        let checked_out = self.checkout_into(&path, resolved, shallow);
+        if checked_out.is_err() {
+            let _ = self.remove_checkout(&path);
+        }
+        checked_out?;
+        Ok(path)
+    }
+
+    fn checkout_into(
+        &self,
+        path: &str,
+        resolved: &ResolvedRef,
+        shallow: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if shallow {
+            git(&["init", "--quiet", path])?;
+            let mirror_url = format!("file://{}", fs::canonicalize(&self.mirror_dir)?.display());
+            git(&[
+                "-C",
+                path,
+                "fetch",
+                "--depth",
+                "1",
+                &mirror_url,
+                &resolved.sha,
+            ])?;
+            git(&["-C", path, "checkout", "--quiet", "--detach", "FETCH_HEAD"])?;
+        } else {
+            git(&[
+                "-C",
+                &self.mirror_dir,
+                "worktree",
+                "add",
+                "--detach",
+                path,
+                &resolved.sha,
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Delete a checkout, and unregister it from the mirror if it was a worktree
+    fn remove_checkout(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        if Path::new(path).exists() {
+            fs::remove_dir_all(path)?;
+        }
+        if Path::new(&self.mirror_dir).exists() {
+            git(&["-C", &self.mirror_dir, "worktree", "prune"])?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks a ref of any git repository out of a mirror clone in the cache
+pub struct GitProvider {
+    url: String,
+    git_ref: GitRef,
+    shallow: bool,
+}
+
+impl GitProvider {
+    pub fn new(url: String, git_ref: GitRef, shallow: bool) -> Self {
+        Self {
+            url,
+            git_ref,
+            shallow,
+        }
+    }
+
+    /// The name of the repository, [e.g.] `bitcoin` for `https://github.com/bitcoin/bitcoin.git`
+    fn repo(&self) -> &str {
+        let url = self.url.trim_end_matches('/').trim_end_matches(".git");
+        url.rsplit(['/', ':']).next().unwrap_or(url)
+    }
+}
+
+#[async_trait(?Send)]
+impl SourceProvider for GitProvider {
+    async fn provide(&self) -> Result<ProvidedSource, Box<dyn Error>> {
+        let mirror = GitMirror::new(
+            self.url.clone(),
+            ShranDefault::SUPPORTED_BLOCKCHAINS[0],
+            self.repo(),
+        );
+        mirror.sync()?;
+        let resolved = mirror.resolve(&self.git_ref)?;
+        let location = mirror.checkout(&resolved, self.shallow)?;
+        Ok(ProvidedSource {
+            version: resolved.git_ref.to_string(),
+            published_date: resolved.committed_at,
+            location,
+            commit: Some(resolved.sha),
+            sha256: None,
+        })
+    }
+}
+
+/// The revision git understands for a ref
+fn revision(git_ref: &GitRef) -> String {
+    match git_ref {
+        GitRef::Ref(name) => name.to_owned(),
+        GitRef::PullRequest(number) => format!("refs/pull/{}/head", number),
+    }
+}
+
+/// Run git, returning its trimmed stdout
+pub(crate) fn git(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(Box::new(ShranError::GitError {
+            msg: format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{git, GitMirror, GitProvider};
+    use crate::github::GitRef;
+    use std::env;
+    use std::fs;
+
+    /// Commit a file and tag the commit in a scratch repository
+    fn commit_and_tag(work: &str, contents: &str, tag: &str) {
+        fs::write(format!("{}/VERSION", work), contents).unwrap();
+        git(&["-C", work, "add", "VERSION"]).unwrap();
+        git(&[
+            "-C",
+            work,
+            "-c",
+            "user.name=shran",
+            "-c",
+            "user.email=shran@localhost",
+            "commit",
+            "--quiet",
+            "-m",
+            contents,
+        ])
+        .unwrap();
+        git(&["-C", work, "tag", tag]).unwrap();
+    }
+
+    /// A local bare repository standing in for github
+    fn upstream(root: &str) -> (String, String) {
+        let work = format!("{}/work", root);
+        let bare = format!("{}/upstream.git", root);
+        git(&["init", "--quiet", &work]).unwrap();
+        commit_and_tag(&work, "24.0", "v24.0");
+        commit_and_tag(&work, "25.0", "v25.0");
+        git(&["clone", "--quiet", "--bare", &work, &bare]).unwrap();
+        git(&["-C", &work, "remote", "add", "origin", &bare]).unwrap();
+        (work, bare)
+    }
+
+    #[test]
+    fn test_git_provider_repo_name() {
+        let https = GitProvider::new(
+            "https://github.com/bitcoin/bitcoin.git".to_string(),
+            GitRef::Ref("HEAD".to_string()),
+            false,
+        );
+        assert_eq!(https.repo(), "bitcoin");
+        let scp = GitProvider::new(
+            "git@github.com:fork/bitcoin".to_string(),
+            GitRef::Ref("HEAD".to_string()),
+            false,
+        );
+        assert_eq!(scp.repo(), "bitcoin");
+    }
+
+    #[test]
+    fn test_git_mirror_worktrees_and_incremental_sync() {
+        let root = env::temp_dir().join(format!("shran-git-{}", std::process::id()));
+        let root = root.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(&root);
+        let (work, bare) = upstream(&root);
+
+        let mirror = GitMirror::with_root(bare, &format!("{}/cache", root), "bitcoin");
+        mirror.sync().unwrap();
+        let v24 = mirror.resolve(&GitRef::Ref("v24.0".to_string())).unwrap();
+        let v25 = mirror.resolve(&GitRef::Ref("v25.0".to_string())).unwrap();
+        assert_ne!(v24.sha, v25.sha);
+
+        let path = mirror.checkout(&v24, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/VERSION", path)).unwrap(),
+            "24.0"
+        );
+        // the worktree shares the mirror's history
+        let log = git(&["-C", &path, "log", "--format=%s", "v24.0..v25.0"]).unwrap();
+        assert_eq!(log, "25.0");
+        assert_eq!(mirror.checkout(&v24, false).unwrap(), path);
+        assert!(path.ends_with(&format!("bitcoin-git-{}", v24.sha)));
+
+        // an interrupted checkout is replaced instead of reused
+        let broken = format!("{}/cache/bitcoin-git-{}", root, v25.sha);
+        fs::create_dir_all(&broken).unwrap();
+        fs::write(format!("{}/VERSION", broken), "partial").unwrap();
+        assert_eq!(mirror.checkout(&v25, false).unwrap(), broken);
+        assert_eq!(
+            fs::read_to_string(format!("{}/VERSION", broken)).unwrap(),
+            "25.0"
+        );
+
+        // new upstream commits arrive with the next sync
+        commit_and_tag(&work, "26.0", "v26.0");
+        git(&["-C", &work, "push", "--quiet", "origin", "HEAD", "v26.0"]).unwrap();
+        assert!(mirror.resolve(&GitRef::Ref("v26.0".to_string())).is_err());
+        mirror.sync().unwrap();
+        let v26 = mirror.resolve(&GitRef::Ref("v26.0".to_string())).unwrap();
+
+        let shallow = mirror.checkout(&v26, true).unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/VERSION", shallow)).unwrap(),
+            "26.0"
+        );
+        assert_eq!(
+            git(&["-C", &shallow, "rev-list", "--count", "HEAD"]).unwrap(),
+            "1"
+        );
+
+        // a fork of the same name replaces the refs of the mirror
+        let (fork_work, fork) = upstream(&format!("{}/fork", root));
+        commit_and_tag(&fork_work, "fork", "v27.0");
+        git(&[
+            "-C", &fork_work, "push", "--quiet", "origin", "HEAD", "v27.0",
+        ])
+        .unwrap();
+        let forked = GitMirror::with_root(fork, &format!("{}/cache", root), "bitcoin");
+        forked.sync().unwrap();
+        assert!(forked.resolve(&GitRef::Ref("v27.0".to_string())).is_ok());
+        assert!(forked.resolve(&GitRef::Ref("v26.0".to_string())).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
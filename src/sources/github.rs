@@ -0,0 +1,77 @@
+use super::{ProvidedSource, SourceProvider};
+use crate::config::Upstream;
+use crate::github::{FetchedRelease, GithubClient};
+use crate::utils::FileSystemManager;
+use crate::verify::ThresholdVerifier;
+use async_trait::async_trait;
+use std::error::Error;
+
+/// Downloads a release through the github releases api, the latest
+/// release if no tag is given.
+pub struct GithubProvider {
+    token: Option<String>,
+    upstream: Upstream,
+    tag: Option<String>,
+    verifier: Option<ThresholdVerifier>,
+    checksum: Option<String>,
+    force: bool,
+}
+
+impl GithubProvider {
+    pub fn new(token: Option<String>, upstream: Upstream, tag: Option<String>) -> Self {
+        Self {
+            token,
+            upstream,
+            tag,
+            verifier: None,
+            checksum: None,
+            force: false,
+        }
+    }
+
+    /// Require the release to be signed by enough trusted builders
+    pub fn with_verifier(mut self, verifier: ThresholdVerifier) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Pin the expected sha256 digest of the source archive
+    pub fn with_checksum(mut self, sha256: String) -> Self {
+        self.checksum = Some(sha256);
+        self
+    }
+
+    /// Replace the tree of the release if it was fetched before
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl SourceProvider for GithubProvider {
+    async fn provide(&self) -> Result<ProvidedSource, Box<dyn Error>> {
+        let mut gclient = GithubClient::new(
+            self.token.clone(),
+            self.upstream.clone(),
+            FileSystemManager::new()?.with_force(self.force),
+        )?;
+        if let Some(verifier) = &self.verifier {
+            gclient = gclient.with_verifier(verifier.clone());
+        }
+        if let Some(sha256) = &self.checksum {
+            gclient = gclient.with_checksum(sha256.to_owned());
+        }
+        let fetched: FetchedRelease = match &self.tag {
+            Some(tag) => gclient.get_tagged_release(tag).await?,
+            None => gclient.get_latest_release().await?,
+        };
+        Ok(ProvidedSource {
+            version: fetched.release.tag_name,
+            published_date: fetched.release.published_at,
+            location: fetched.archive.location,
+            commit: None,
+            sha256: Some(fetched.archive.sha256),
+        })
+    }
+}
@@ -0,0 +1,15 @@
+pub mod depends;
+pub mod directory;
+pub mod git;
+pub mod github;
+pub mod provider;
+pub mod spec;
+pub mod tarball;
+
+pub use depends::DependsFetcher;
+pub use directory::DirectoryProvider;
+pub use git::{GitMirror, GitProvider};
+pub use github::GithubProvider;
+pub use provider::{ProvidedSource, SourceProvider};
+pub use spec::SourceSpec;
+pub use tarball::TarballProvider;
@@ -0,0 +1,42 @@
+use async_trait::async_trait;
+use std::error::Error;
+use std::fmt;
+
+/// Source code that has been acquired by a SourceProvider
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProvidedSource {
+    /// The release tag, git ref, or a name derived from the archive or directory
+    pub version: String,
+    pub published_date: String,
+    /// The directory containing the source code
+    pub location: String,
+    /// The commit the sources were checked out at, if known
+    pub commit: Option<String>,
+    /// Hex encoded sha256 digest of the archive the sources were extracted from
+    pub sha256: Option<String>,
+}
+
+impl fmt::Display for ProvidedSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Version: {}\nPublished: {}\nLocation: {}",
+            self.version, self.published_date, self.location
+        )?;
+        if let Some(commit) = &self.commit {
+            write!(f, "\nCommit: {}", commit)?;
+        }
+        if let Some(sha256) = &self.sha256 {
+            write!(f, "\nSHA256: {}", sha256)?;
+        }
+        Ok(())
+    }
+}
+
+/// Anything shran can acquire blockchain source code from, see
+/// SourceSpec for the spec each implementation is chosen by.
+#[async_trait(?Send)]
+pub trait SourceProvider {
+    /// Acquire the source code, returning where it can be found
+    async fn provide(&self) -> Result<ProvidedSource, Box<dyn Error>>;
+}
@@ -0,0 +1,199 @@
+use crate::error::ShranError;
+use crate::github::GitRef;
+use std::fmt;
+use std::str::FromStr;
+
+/// Where `fetch` and `build` should take the source code from, parsed
+/// from a URI like spec
+///
+/// | spec                                         | source                          |
+/// |----------------------------------------------|---------------------------------|
+/// | `github:bitcoin/bitcoin@v25.0`               | a github release, latest if no `@tag` |
+/// | `https://example.com/bitcoin-25.0.tar.gz`    | a tarball served over http(s)   |
+/// | `file:///tmp/bitcoin-25.0.tar.gz`            | a local tarball                 |
+/// | `dir:../bitcoin`                             | a local working tree, used in place |
+/// | `git:https://github.com/bitcoin/bitcoin@v25.0` | a git repository, HEAD if no `@ref` |
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceSpec {
+    Github {
+        owner: String,
+        repo: String,
+        tag: Option<String>,
+    },
+    /// A `.tar.gz` archive at any url curl understands, including `file://`
+    Tarball {
+        url: String,
+    },
+    Directory {
+        path: String,
+    },
+    Git {
+        url: String,
+        git_ref: GitRef,
+    },
+}
+
+impl FromStr for SourceSpec {
+    type Err = ShranError<'static>;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| ShranError::SourceSpecError {
+            msg: format!("{}: {}", spec, reason),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        };
+
+        if let Some(rest) = spec.strip_prefix("github:") {
+            let (repository, tag) = match rest.split_once('@') {
+                Some((repository, tag)) => (repository, Some(tag.to_string())),
+                None => (rest, None),
+            };
+            return match repository.split_once('/') {
+                Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() => {
+                    Ok(SourceSpec::Github {
+                        owner: owner.to_string(),
+                        repo: repo.to_string(),
+                        tag,
+                    })
+                }
+                _ => Err(invalid("expected github:<owner>/<repo>[@<tag>]")),
+            };
+        }
+        if let Some(path) = spec.strip_prefix("dir:") {
+            if path.is_empty() {
+                return Err(invalid("expected dir:<path>"));
+            }
+            return Ok(SourceSpec::Directory {
+                path: path.to_string(),
+            });
+        }
+        if let Some(rest) = spec.strip_prefix("git:") {
+            // the ref follows an @ in the last path segment, which keeps the
+            // user part of urls (ssh://git@github.com/bitcoin/bitcoin) intact,
+            // and a ref never contains a colon, which does the same for scp
+            // like urls (git@github.com:bitcoin/bitcoin)
+            let segment = rest.rfind('/').map_or(0, |slash| slash + 1);
+            let at = rest[segment..].rfind('@').map(|at| segment + at);
+            return match at.map(|at| (&rest[..at], &rest[at + 1..])) {
+                Some((url, name)) if !name.contains(':') && !url.is_empty() && !name.is_empty() => {
+                    Ok(SourceSpec::Git {
+                        url: url.to_string(),
+                        git_ref: GitRef::Ref(name.to_string()),
+                    })
+                }
+                _ if !rest.is_empty() => Ok(SourceSpec::Git {
+                    url: rest.to_string(),
+                    git_ref: GitRef::Ref(String::from("HEAD")),
+                }),
+                _ => Err(invalid("expected git:<url>[@<ref>]")),
+            };
+        }
+        if spec.starts_with("https://")
+            || spec.starts_with("http://")
+            || spec.starts_with("file://")
+        {
+            return Ok(SourceSpec::Tarball {
+                url: spec.to_string(),
+            });
+        }
+        Err(invalid(
+            "unknown source, expected one of github:, dir:, git:, file://, http:// or https://",
+        ))
+    }
+}
+
+impl fmt::Display for SourceSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceSpec::Github { owner, repo, tag } => match tag {
+                Some(tag) => write!(f, "github:{}/{}@{}", owner, repo, tag),
+                None => write!(f, "github:{}/{}", owner, repo),
+            },
+            SourceSpec::Tarball { url } => write!(f, "{}", url),
+            SourceSpec::Directory { path } => write!(f, "dir:{}", path),
+            SourceSpec::Git { url, git_ref } => write!(f, "git:{}@{}", url, git_ref),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceSpec;
+    use crate::github::GitRef;
+
+    #[test]
+    fn test_parse_github_spec() {
+        let spec: SourceSpec = "github:bitcoin/bitcoin@v25.0".parse().unwrap();
+        assert_eq!(
+            spec,
+            SourceSpec::Github {
+                owner: "bitcoin".to_string(),
+                repo: "bitcoin".to_string(),
+                tag: Some("v25.0".to_string()),
+            }
+        );
+        let latest: SourceSpec = "github:fork/bitcoin".parse().unwrap();
+        assert_eq!(latest.to_string(), "github:fork/bitcoin");
+        assert!("github:bitcoin".parse::<SourceSpec>().is_err());
+    }
+
+    #[test]
+    fn test_parse_local_specs() {
+        assert_eq!(
+            "file:///tmp/bitcoin-25.0.tar.gz"
+                .parse::<SourceSpec>()
+                .unwrap(),
+            SourceSpec::Tarball {
+                url: "file:///tmp/bitcoin-25.0.tar.gz".to_string()
+            }
+        );
+        assert_eq!(
+            "dir:../bitcoin".parse::<SourceSpec>().unwrap(),
+            SourceSpec::Directory {
+                path: "../bitcoin".to_string()
+            }
+        );
+        assert!("/tmp/bitcoin-25.0.tar.gz".parse::<SourceSpec>().is_err());
+    }
+
+    #[test]
+    fn test_parse_git_spec() {
+        assert_eq!(
+            "git:git@github.com:bitcoin/bitcoin.git@v25.0"
+                .parse::<SourceSpec>()
+                .unwrap(),
+            SourceSpec::Git {
+                url: "git@github.com:bitcoin/bitcoin.git".to_string(),
+                git_ref: GitRef::Ref("v25.0".to_string()),
+            }
+        );
+        assert_eq!(
+            "git:git@github.com:bitcoin/bitcoin.git"
+                .parse::<SourceSpec>()
+                .unwrap(),
+            SourceSpec::Git {
+                url: "git@github.com:bitcoin/bitcoin.git".to_string(),
+                git_ref: GitRef::Ref("HEAD".to_string()),
+            }
+        );
+        assert_eq!(
+            "git:ssh://git@github.com/bitcoin/bitcoin.git"
+                .parse::<SourceSpec>()
+                .unwrap(),
+            SourceSpec::Git {
+                url: "ssh://git@github.com/bitcoin/bitcoin.git".to_string(),
+                git_ref: GitRef::Ref("HEAD".to_string()),
+            }
+        );
+        assert_eq!(
+            "git:https://user@git.example/bitcoin.git@v25.0"
+                .parse::<SourceSpec>()
+                .unwrap(),
+            SourceSpec::Git {
+                url: "https://user@git.example/bitcoin.git".to_string(),
+                git_ref: GitRef::Ref("v25.0".to_string()),
+            }
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use super::{ProvidedSource, SourceProvider};
+use crate::config::ShranDefault;
+use crate::utils::{BlockchainKind, Downloader, FileSystemManager, ManifestKey};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::error::Error;
+
+/// Downloads a `.tar.gz` source archive from a url, `file://` urls
+/// work as well, and extracts it into the cache.
+pub struct TarballProvider {
+    url: String,
+    expected_sha256: Option<String>,
+    version: Option<String>,
+    force: bool,
+}
+
+impl TarballProvider {
+    /// # Params
+    ///
+    /// 1. url: Where the archive lives, http(s) or file
+    /// 2. expected_sha256: The hex encoded digest the archive must match, if any
+    pub fn new(url: String, expected_sha256: Option<String>) -> Self {
+        Self {
+            url,
+            expected_sha256,
+            version: None,
+            force: false,
+        }
+    }
+
+    /// Use `version` instead of guessing it from the file name
+    pub fn with_version(mut self, version: String) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Replace the tree of the version if it was fetched before
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// The name of the archive, the last segment of its url
+    fn file_name(&self) -> &str {
+        let path = self.url.split(['?', '#']).next().unwrap_or("");
+        path.rsplit('/').next().unwrap_or(path)
+    }
+
+    /// Download the archive and extract it into the cache of `fs`
+    pub fn provide_into(&self, fs: &FileSystemManager) -> Result<ProvidedSource, Box<dyn Error>> {
+        let mut downloader = Downloader::new(vec![self.url.clone()], self.expected_sha256.clone());
+        let download = downloader.fetch()?;
+        println!("{}", download);
+
+        let version = self
+            .version
+            .clone()
+            .unwrap_or_else(|| version_from_file_name(self.file_name()));
+        let location = fs.write_and_extract_blockchain_archive(
+            self.file_name(),
+            download.bytes,
+            BlockchainKind::Bitcoin,
+            &ManifestKey::new(ShranDefault::SUPPORTED_BLOCKCHAINS[0], &version),
+        )?;
+        Ok(ProvidedSource {
+            version,
+            published_date: Utc::now().to_string(),
+            location,
+            commit: None,
+            sha256: Some(download.sha256),
+        })
+    }
+}
+
+/// Guess the version of an archive from its file name,
+/// [e.g.] `bitcoin-25.0.tar.gz` becomes `v25.0`
+pub fn version_from_file_name(file_name: &str) -> String {
+    let stem = file_name.trim_end_matches(ShranDefault::FILE_EXTENSION);
+    match stem.rsplit_once('-') {
+        Some((_, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => {
+            format!("v{}", version)
+        }
+        _ => stem.to_string(),
+    }
+}
+
+#[async_trait(?Send)]
+impl SourceProvider for TarballProvider {
+    async fn provide(&self) -> Result<ProvidedSource, Box<dyn Error>> {
+        self.provide_into(&FileSystemManager::new()?.with_force(self.force))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{version_from_file_name, TarballProvider};
+    use crate::utils::downloader::sha256_hex;
+    use crate::utils::mock_archive::gzip_tarball;
+    use crate::utils::FileSystemManager;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_import_local_tarball() {
+        let dir = env::temp_dir().join(format!("shran-import-{}", std::process::id()));
+        let cache = FileSystemManager::in_dir(dir.to_str().unwrap()).unwrap();
+        let root = "bitcoin-25.0";
+        let bytes = gzip_tarball(&[(&format!("{}/VERSION", root), b"25.0")]);
+        let file = dir.join("bitcoin-25.0.tar.gz");
+        fs::write(&file, &bytes).unwrap();
+        let url = format!("file://{}", file.display());
+
+        let tampered = TarballProvider::new(url.clone(), Some(sha256_hex(b"other")));
+        assert!(tampered.provide_into(&cache).is_err());
+
+        let provider =
+            TarballProvider::new(url, Some(sha256_hex(&bytes))).with_version("v25.0".to_string());
+        let source = provider.provide_into(&cache).unwrap();
+        assert!(source.location.starts_with(dir.to_str().unwrap()));
+        assert_eq!(source.version, "v25.0");
+        assert!(source.location.ends_with(&root));
+        assert_eq!(
+            fs::read_to_string(format!("{}/VERSION", source.location)).unwrap(),
+            "25.0"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_version_from_file_name() {
+        assert_eq!(version_from_file_name("bitcoin-25.0.tar.gz"), "v25.0");
+        assert_eq!(version_from_file_name("v24.1.tar.gz"), "v24.1");
+        assert_eq!(version_from_file_name("snapshot.tar.gz"), "snapshot");
+    }
+
+    #[test]
+    fn test_tarball_file_name() {
+        let provider = TarballProvider::new(
+            "https://bitcoincore.org/bin/bitcoin-core-25.0/bitcoin-25.0.tar.gz?mirror=1"
+                .to_string(),
+            None,
+        );
+        assert_eq!(provider.file_name(), "bitcoin-25.0.tar.gz");
+    }
+}
@@ -1,7 +1,7 @@
 //! Defualt build strategy structures for Bitcoin
 
 use crate::error::ShranError;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 
 /// Hardcoded build option names from bitcoins configure.ac file
@@ -450,6 +450,59 @@ impl<'f, 'e> BuildStrategy<'f> {
         Self { build_options }
     }
 
+    /// Builds a strategy from one of the named profiles
+    ///
+    /// * `default` bitcoins own defaults, same as BuildStrategy::new
+    /// * `rpc` a node only used for rpc calls, without wallet, sqlite and bdb
+    ///
+    /// ```no_run
+    /// let strategy = strategies::bitcoin::BuildStrategy::from_profile("rpc")?;
+    /// ```
+    pub fn from_profile(profile: &str) -> Result<Self, Box<dyn Error>> {
+        let mut strategy = Self::new();
+        match profile {
+            "default" => {}
+            "rpc" => {
+                for name in [
+                    BuildOptionName::WALLET,
+                    BuildOptionName::SQLITE,
+                    BuildOptionName::BDB,
+                ] {
+                    strategy.update_build_option(name, OptionEnabled::No)?;
+                }
+            }
+            _ => {
+                return Err(Box::new(ShranError::BuildFileError {
+                    msg: format!("unknown build profile {}, expected default or rpc", profile),
+                    file: file!(),
+                    line: line!(),
+                    column: column!(),
+                }))
+            }
+        }
+        Ok(strategy)
+    }
+
+    /// Serialize the strategy to a build.yaml document, one `yes`, `no`
+    /// or `auto` per build option, sorted by name.
+    pub fn to_yaml(&self) -> Result<String, Box<dyn Error>> {
+        let options: BTreeMap<&str, &str> = self
+            .build_options
+            .iter()
+            .map(|(name, option)| {
+                let enabled = match option.enabled() {
+                    OptionEnabled::Yes => "yes",
+                    OptionEnabled::No => "no",
+                    OptionEnabled::Auto => "auto",
+                };
+                (*name, enabled)
+            })
+            .collect();
+        let mut document: BTreeMap<&str, BTreeMap<&str, &str>> = BTreeMap::new();
+        document.insert("build_options", options);
+        Ok(serde_yaml::to_string(&document)?)
+    }
+
     /// Getter for the BuildOptions hash map
     /// Mostly for testing purposes
     pub fn build_options(&self) -> &BuildOptions<'f> {
@@ -1001,6 +1054,16 @@ mod tests {
         assert_eq!(option.enabled(), &OptionEnabled::No);
     }
 
+    #[test]
+    fn test_rpc_profile_disables_wallet() {
+        let b = BuildStrategy::from_profile("rpc").unwrap();
+        let wallet = b.build_options().get(BuildOptionName::WALLET).unwrap();
+        assert_eq!(wallet.enabled(), &OptionEnabled::No);
+        let yaml = b.to_yaml().unwrap();
+        assert!(yaml.contains("wallet: 'no'") || yaml.contains("wallet: \"no\""));
+        assert!(BuildStrategy::from_profile("does not exist").is_err());
+    }
+
     #[test]
     fn test_updating_non_existant_build_option() {
         let mut b = BuildStrategy::new();
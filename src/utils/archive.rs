@@ -1,7 +1,10 @@
 extern crate tar;
 use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use std::error::Error;
 use std::fs::File;
+use std::path::Component;
 
 /// Archiver is a trait that a zip or tar struct must implement
 pub trait Archiver<T> {
@@ -34,13 +37,54 @@ impl<'archive> TapeArchive<'archive> {
             destination_dir,
         }
     }
+
+    /// The reverse of unpack, packs the contents of the destination
+    /// directory into the archive file, beneath a single top level
+    /// directory named `prefix`.
+    pub fn pack(&self, prefix: &str) -> Result<(), Box<dyn Error>> {
+        let file: File = File::create(self.archive)?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        builder.append_dir_all(prefix, self.destination_dir)?;
+        builder.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    /// The top level directory of the archive, [e.g.] `bitcoin-25.0` for
+    /// a bitcoin source tarball. Returns None if the archive has no single
+    /// top level directory.
+    pub fn root_dir(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let file: File = File::open(self.archive)?;
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        let mut root: Option<String> = None;
+        for entry in archive.entries()? {
+            let entry = entry?;
+            // github archives start with a pax header holding the commit id
+            if entry.header().entry_type().is_pax_global_extensions() {
+                continue;
+            }
+            let path = entry.path()?;
+            // `./bitcoin-25.0/...` has the root `bitcoin-25.0`, the entry `./`
+            // itself has none, and `..` or `/` are never a root
+            let first = match path.components().find(|c| c != &Component::CurDir) {
+                Some(Component::Normal(name)) => name.to_string_lossy().to_string(),
+                Some(_) => return Ok(None),
+                None => continue,
+            };
+            match &root {
+                Some(dir) if dir != &first => return Ok(None),
+                Some(_) => {}
+                None => root = Some(first),
+            }
+        }
+        Ok(root)
+    }
 }
 
 impl<'archive> Archiver<()> for TapeArchive<'archive> {
     fn unpack(&self) -> Result<(), Box<dyn Error>> {
-        let file: File = File::open(&self.archive)?;
+        let file: File = File::open(self.archive)?;
         let tar: GzDecoder<File> = GzDecoder::new(file);
-        tar::Archive::new(tar).unpack(&self.destination_dir)?;
+        tar::Archive::new(tar).unpack(self.destination_dir)?;
         Ok(())
     }
 }
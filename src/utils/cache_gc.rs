@@ -0,0 +1,676 @@
+use super::fs_manager::{disk_usage, human_size};
+use super::manifest_manager::{ManifestEntry, ManifestKey};
+use super::manifest_query::ManifestQuery;
+use super::uninstall::{is_version_dir, Removal, Uninstaller};
+use crate::config::ShranDefault;
+use crate::error::ShranError;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What the directories of the cache hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CacheCategory {
+    /// Source trees along with their in tree builds
+    Sources,
+    /// Official release binaries
+    Prebuilt,
+    /// Bare git mirrors that checkouts are made from
+    Mirrors,
+    /// SHA256SUMS and their signatures
+    Signatures,
+    /// Source tarballs of the depends system
+    Depends,
+    /// Release metadata, cached api responses and tree indexes
+    Metadata,
+    /// Anything shran does not know about
+    Other,
+}
+
+impl fmt::Display for CacheCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CacheCategory::Sources => "sources",
+            CacheCategory::Prebuilt => "prebuilt",
+            CacheCategory::Mirrors => "mirrors",
+            CacheCategory::Signatures => "signatures",
+            CacheCategory::Depends => "depends",
+            CacheCategory::Metadata => "metadata",
+            CacheCategory::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Disk usage of the cache per category, and per installed version
+#[derive(Debug, Default)]
+pub struct CacheUsage {
+    pub categories: BTreeMap<CacheCategory, u64>,
+    /// Versions inside of the cache, largest first
+    pub versions: Vec<(ManifestKey, u64)>,
+}
+
+impl CacheUsage {
+    pub fn total(&self) -> u64 {
+        self.categories.values().sum()
+    }
+}
+
+impl fmt::Display for CacheUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .versions
+            .iter()
+            .map(|(key, _)| key.to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max("signatures".len());
+        for (category, size) in &self.categories {
+            writeln!(
+                f,
+                "{:<width$}  {}",
+                category.to_string(),
+                human_size(*size),
+                width = width
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<width$}  {}",
+            "total",
+            human_size(self.total()),
+            width = width
+        )?;
+        if !self.versions.is_empty() {
+            writeln!(f)?;
+        }
+        for (key, size) in &self.versions {
+            writeln!(
+                f,
+                "{:<width$}  {}",
+                key.to_string(),
+                human_size(*size),
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Limits the cache is brought back within, every limit that is set applies.
+/// Pinned versions, the active version, and trees outside of the cache are
+/// never collected.
+///
+/// # Example
+///
+/// ```ignore
+/// let policy = GcPolicy::new()
+///     .with_keep_last(3)
+///     .with_max_size(parse_size("20G")?)
+///     .with_max_age(parse_age("90d")?);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GcPolicy {
+    keep_last: Option<usize>,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+}
+
+impl GcPolicy {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Keep the `count` newest versions of every blockchain
+    pub fn with_keep_last(mut self, count: usize) -> Self {
+        self.keep_last = Some(count);
+        self
+    }
+
+    /// Collect the least recently modified versions until the whole cache
+    /// takes at most `bytes`
+    pub fn with_max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Collect versions that were not modified for longer than `age`
+    pub fn with_max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keep_last.is_none() && self.max_size.is_none() && self.max_age.is_none()
+    }
+}
+
+/// Why a version is collected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcReason {
+    /// Older than the newest n versions of its blockchain
+    KeepLast(usize),
+    /// Not modified for this many days
+    MaxAge(i64),
+    /// The cache is larger than allowed
+    MaxSize,
+}
+
+impl fmt::Display for GcReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GcReason::KeepLast(count) => write!(f, "older than the last {} versions", count),
+            GcReason::MaxAge(days) => write!(f, "not modified for {} days", days),
+            GcReason::MaxSize => write!(f, "the cache is over its size limit"),
+        }
+    }
+}
+
+/// A version that is collected, and the space it frees
+#[derive(Debug, Clone, PartialEq)]
+pub struct GcCandidate {
+    pub removal: Removal,
+    pub reason: GcReason,
+    pub size: u64,
+}
+
+/// Everything a garbage collection deletes
+#[derive(Debug, Default)]
+pub struct GcPlan {
+    pub candidates: Vec<GcCandidate>,
+    /// Tree indexes and signatures no remaining version refers to
+    pub stale: Vec<String>,
+    /// Size of the cache before the collection
+    pub cache_size: u64,
+    /// Space freed by the collection
+    pub freed: u64,
+}
+
+impl fmt::Display for GcPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        for candidate in &self.candidates {
+            writeln!(
+                f,
+                "{} ({}, {})",
+                candidate.removal.key,
+                candidate.reason,
+                human_size(candidate.size)
+            )?;
+            for path in &candidate.removal.paths {
+                writeln!(f, "  {}", path)?;
+            }
+        }
+        for path in &self.stale {
+            writeln!(f, "stale {}", path)?;
+        }
+        write!(
+            f,
+            "Frees {} of {}",
+            human_size(self.freed),
+            human_size(self.cache_size)
+        )
+    }
+}
+
+/// Reports and reclaims the space taken by the cache, versions are deleted
+/// the same way `shran remove` deletes them.
+pub struct CacheCollector {
+    cache_root: String,
+    prebuilt_root: String,
+    uninstaller: Uninstaller,
+}
+
+impl CacheCollector {
+    /// # Params
+    ///
+    /// 1. working_dir: The version whose tree contains this directory is
+    ///    the active one, and is never collected
+    pub fn new(working_dir: PathBuf) -> Self {
+        Self {
+            cache_root: ShranDefault::cache_dir(),
+            prebuilt_root: ShranDefault::prebuilt_dir(),
+            uninstaller: Uninstaller::new(working_dir),
+        }
+    }
+
+    /// Treat `cache_root` as the cache instead of the default cache directory,
+    /// prebuilt binaries are expected in its `prebuilt` directory
+    pub fn with_cache_root(mut self, cache_root: String) -> Self {
+        self.uninstaller = self.uninstaller.with_cache_root(cache_root.clone());
+        self.prebuilt_root = format!("{}/prebuilt", cache_root);
+        self.cache_root = cache_root;
+        self
+    }
+
+    /// Prebuilt binaries are installed to `prebuilt_root`, the install prefix
+    pub fn with_prebuilt_root(mut self, prebuilt_root: String) -> Self {
+        self.uninstaller = self.uninstaller.with_prebuilt_root(prebuilt_root.clone());
+        self.prebuilt_root = prebuilt_root;
+        self
+    }
+
+    /// Measure the cache by category, and the versions of `entries` inside of it
+    pub fn usage(&self, entries: &[(&ManifestKey, &ManifestEntry)]) -> CacheUsage {
+        let mut usage = CacheUsage::default();
+        for (path, category) in self.categorized() {
+            *usage.categories.entry(category).or_insert(0) += disk_usage(path);
+        }
+        usage.versions = entries
+            .iter()
+            .filter(|(_, entry)| self.is_cached(entry))
+            .map(|(key, entry)| {
+                (
+                    key.to_owned().to_owned(),
+                    disk_usage(&entry.installation_location),
+                )
+            })
+            .collect();
+        usage
+            .versions
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        usage
+    }
+
+    /// The top level directories of the cache, and what they hold
+    fn categorized(&self) -> Vec<(PathBuf, CacheCategory)> {
+        let mut categorized = Vec::new();
+        for dir in list_dir(Path::new(&self.cache_root)) {
+            let name = file_name(&dir);
+            if ShranDefault::SUPPORTED_BLOCKCHAINS.contains(&name.as_str()) && dir.is_dir() {
+                for child in list_dir(&dir) {
+                    let category = match file_name(&child) {
+                        name if name == "signatures" => CacheCategory::Signatures,
+                        name if name.ends_with(".git") => CacheCategory::Mirrors,
+                        _ => CacheCategory::Sources,
+                    };
+                    categorized.push((child, category));
+                }
+                continue;
+            }
+            let category = match name.as_str() {
+                "prebuilt" => CacheCategory::Prebuilt,
+                "depends" => CacheCategory::Depends,
+                "releases" | "etags" | "trees" => CacheCategory::Metadata,
+                _ => CacheCategory::Other,
+            };
+            categorized.push((dir, category));
+        }
+        categorized
+    }
+
+    /// Select the versions `policy` collects. Versions past `keep_last` or
+    /// `max_age` go first, the least recently modified of the remaining ones
+    /// follow while the cache is larger than `max_size`.
+    ///
+    /// # Params
+    ///
+    /// 1. entries: Every entry of the manifest
+    /// 2. policy: The limits to apply
+    /// 3. now: The time ages are measured at
+    pub fn plan(
+        &self,
+        entries: &[(&ManifestKey, &ManifestEntry)],
+        policy: &GcPolicy,
+        now: DateTime<Utc>,
+    ) -> GcPlan {
+        let collectable: Vec<(&ManifestKey, &ManifestEntry)> = entries
+            .iter()
+            .filter(|(_, entry)| {
+                !entry.pinned && self.is_cached(entry) && !self.uninstaller.is_active(entry)
+            })
+            .cloned()
+            .collect();
+        let mut doomed: Vec<(&ManifestKey, &ManifestEntry, GcReason)> = Vec::new();
+        let is_doomed = |doomed: &[(&ManifestKey, &ManifestEntry, GcReason)], key: &ManifestKey| {
+            doomed.iter().any(|(other, _, _)| *other == key)
+        };
+
+        if let Some(count) = policy.keep_last {
+            let blockchains: BTreeSet<&String> =
+                entries.iter().map(|(key, _)| &key.blockchain).collect();
+            for blockchain in blockchains {
+                let newest_first = ManifestQuery::new()
+                    .with_blockchain(blockchain)
+                    .descending()
+                    .apply(entries.to_vec());
+                for (key, entry) in newest_first.into_iter().skip(count) {
+                    if collectable.contains(&(key, entry)) {
+                        doomed.push((key, entry, GcReason::KeepLast(count)));
+                    }
+                }
+            }
+        }
+        if let Some(max_age) = policy.max_age {
+            for (key, entry) in &collectable {
+                let age = match last_modified(entry) {
+                    Some(modified) => now - modified,
+                    None => continue,
+                };
+                if age > max_age && !is_doomed(&doomed, key) {
+                    doomed.push((key, entry, GcReason::MaxAge(age.num_days())));
+                }
+            }
+        }
+
+        let mut plan = GcPlan {
+            cache_size: disk_usage(&self.cache_root),
+            ..Default::default()
+        };
+        for (key, entry, reason) in &doomed {
+            let candidate = self.candidate(key, entry, *reason, entries);
+            plan.freed += candidate.size;
+            plan.candidates.push(candidate);
+        }
+        if let Some(max_size) = policy.max_size {
+            let mut oldest_first: Vec<&(&ManifestKey, &ManifestEntry)> = collectable
+                .iter()
+                .filter(|(key, _)| !is_doomed(&doomed, key))
+                .collect();
+            oldest_first.sort_by_key(|(key, entry)| (last_modified(entry), key.to_owned()));
+            for (key, entry) in oldest_first {
+                if plan.cache_size.saturating_sub(plan.freed) <= max_size {
+                    break;
+                }
+                let candidate = self.candidate(key, entry, GcReason::MaxSize, entries);
+                if candidate.size == 0 {
+                    continue;
+                }
+                plan.freed += candidate.size;
+                plan.candidates.push(candidate);
+            }
+        }
+
+        plan.stale = self.stale(entries, &plan.candidates);
+        plan.freed += plan.stale.iter().map(disk_usage).sum::<u64>();
+        plan
+    }
+
+    fn candidate(
+        &self,
+        key: &ManifestKey,
+        entry: &ManifestEntry,
+        reason: GcReason,
+        entries: &[(&ManifestKey, &ManifestEntry)],
+    ) -> GcCandidate {
+        let removal = self.uninstaller.removal(key, entry, entries);
+        let size = removal.paths.iter().map(disk_usage).sum();
+        GcCandidate {
+            removal,
+            reason,
+            size,
+        }
+    }
+
+    /// Tree indexes and signatures that none of the versions kept after the
+    /// collection refer to, and that are not deleted along with a candidate
+    fn stale(
+        &self,
+        entries: &[(&ManifestKey, &ManifestEntry)],
+        candidates: &[GcCandidate],
+    ) -> Vec<String> {
+        let kept: Vec<&ManifestEntry> = entries
+            .iter()
+            .filter(|(key, _)| {
+                !candidates
+                    .iter()
+                    .any(|candidate| &candidate.removal.key == *key)
+            })
+            .map(|(_, entry)| *entry)
+            .collect();
+        let deleted: BTreeSet<&String> = candidates
+            .iter()
+            .flat_map(|candidate| candidate.removal.paths.iter())
+            .collect();
+
+        let mut stale = Vec::new();
+        let trees = Path::new(&self.cache_root).join("trees");
+        for index in list_dir(&trees) {
+            let name = file_name(&index);
+            let tree_hash = name.strip_suffix(".sha256").unwrap_or(&name);
+            if !kept
+                .iter()
+                .any(|entry| entry.tree_hash.as_deref() == Some(tree_hash))
+            {
+                stale.push(index.to_string_lossy().to_string());
+            }
+        }
+        let signatures = Path::new(&self.cache_root).join("bitcoin/signatures");
+        for dir in list_dir(&signatures) {
+            let version = file_name(&dir);
+            if !kept.iter().any(|entry| entry.version == version) {
+                stale.push(dir.to_string_lossy().to_string());
+            }
+        }
+        stale.retain(|path| !deleted.contains(path));
+        stale.sort();
+        stale
+    }
+
+    /// Delete the stale artifacts of a plan, the candidates are deleted with
+    /// Uninstaller::remove, see CacheCollector::uninstaller
+    pub fn remove_stale(&self, plan: &GcPlan) -> io::Result<()> {
+        for path in &plan.stale {
+            if Path::new(path).is_dir() {
+                fs::remove_dir_all(path)?;
+            } else if Path::new(path).exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The uninstaller deleting the candidates of a plan
+    pub fn uninstaller(&self) -> &Uninstaller {
+        &self.uninstaller
+    }
+
+    /// True for the version directories of the cache, only those are ever
+    /// deleted, see is_version_dir
+    fn is_cached(&self, entry: &ManifestEntry) -> bool {
+        let location = entry.installation_location.as_path();
+        is_version_dir(&self.cache_root, &self.prebuilt_root, location) && location.exists()
+    }
+}
+
+/// When the tree of an entry was last modified, [e.g.] by a build
+fn last_modified(entry: &ManifestEntry) -> Option<DateTime<Utc>> {
+    let modified = fs::metadata(&entry.installation_location)
+        .and_then(|meta| meta.modified())
+        .ok()?;
+    Some(DateTime::<Utc>::from(modified))
+}
+
+fn list_dir(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    paths
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Parse a size limit, a number of bytes with an optional binary unit
+/// [e.g.] `500M`, `20G` or `1.5GiB`
+///
+/// # Errors
+/// ShranError::CachePolicyError if the size does not parse
+pub fn parse_size(size: &str) -> Result<u64, ShranError<'static>> {
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let exponent = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        _ => return Err(policy_error(format!("{} has an unknown unit", size))),
+    };
+    match number.parse::<f64>() {
+        Ok(number) => Ok((number * 1024f64.powi(exponent)) as u64),
+        Err(_) => Err(policy_error(format!("{} is not a size", size))),
+    }
+}
+
+/// Parse an age limit in hours, days or weeks [e.g.] `12h`, `90d` or `2w`
+///
+/// # Errors
+/// ShranError::CachePolicyError if the age does not parse
+pub fn parse_age(age: &str) -> Result<Duration, ShranError<'static>> {
+    let not_an_age = || policy_error(format!("{} is not an age, [e.g.] 90d", age));
+    let (number, unit) = match age.char_indices().last() {
+        Some((at, _)) => age.split_at(at),
+        None => return Err(not_an_age()),
+    };
+    // unsigned, so that an age is never negative nor out of range of Duration
+    let number: u32 = number.parse().map_err(|_| not_an_age())?;
+    let number = i64::from(number);
+    match unit {
+        "h" => Ok(Duration::hours(number)),
+        "d" => Ok(Duration::days(number)),
+        "w" => Ok(Duration::weeks(number)),
+        _ => Err(policy_error(format!(
+            "{} has an unknown unit, expected h, d or w",
+            age
+        ))),
+    }
+}
+
+fn policy_error(msg: String) -> ShranError<'static> {
+    ShranError::CachePolicyError {
+        msg,
+        file: file!(),
+        line: line!(),
+        column: column!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_age, parse_size, CacheCategory, CacheCollector, GcPolicy, GcReason};
+    use crate::utils::manifest_manager::{ManifestEntry, ManifestKey};
+    use chrono::{Duration, Utc};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("20G").unwrap(), 20 << 30);
+        assert_eq!(parse_size("1.5KiB").unwrap(), 1536);
+        assert!(parse_size("20 parsecs").is_err());
+        assert_eq!(parse_age("90d").unwrap(), Duration::days(90));
+        assert_eq!(parse_age("2w").unwrap(), Duration::weeks(2));
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3y").is_err());
+        assert!(parse_age("9ä").is_err());
+        assert!(parse_age("ä").is_err());
+        assert!(parse_age("").is_err());
+        assert!(parse_age("-5d").is_err());
+    }
+
+    #[test]
+    fn test_gc_respects_policies_and_protected_versions() {
+        let root = env::temp_dir().join(format!("shran-gc-{}", std::process::id()));
+        let root_str = root.to_string_lossy().to_string();
+        let versions = ["v22.0", "v23.0", "v24.0", "v25.0"];
+        for (size, version) in versions.iter().enumerate() {
+            let tree = root.join(format!("bitcoin/bitcoin-{}", &version[1..]));
+            fs::create_dir_all(&tree).unwrap();
+            fs::write(tree.join("COPYING"), vec![b'x'; 1000 * (size + 1)]).unwrap();
+        }
+        fs::create_dir_all(root.join("bitcoin/signatures/v21.0")).unwrap();
+        fs::create_dir_all(root.join("bitcoin/bitcoin.git")).unwrap();
+        fs::create_dir_all(root.join("trees")).unwrap();
+        fs::write(root.join("trees/stale.sha256"), "").unwrap();
+
+        let keys: Vec<ManifestKey> = versions
+            .iter()
+            .map(|version| ManifestKey::new("bitcoin", version))
+            .collect();
+        let mut entries: Vec<ManifestEntry> = versions
+            .iter()
+            .map(|version| {
+                ManifestEntry::new(
+                    version.to_string(),
+                    Utc::now(),
+                    root.join(format!("bitcoin/bitcoin-{}", &version[1..])),
+                )
+            })
+            .collect();
+        entries[0].pinned = true;
+        // a broken entry pointing at the blockchain directory itself
+        let broken_key = ManifestKey::new("bitcoin", "v0.1");
+        let broken = ManifestEntry::new("v0.1".to_string(), Utc::now(), root.join("bitcoin"));
+        let mut listing: Vec<(&ManifestKey, &ManifestEntry)> =
+            keys.iter().zip(entries.iter()).collect();
+        listing.push((&broken_key, &broken));
+
+        // working inside of v23.0 makes it the active version
+        let collector = CacheCollector::new(root.join("bitcoin/bitcoin-23.0"))
+            .with_cache_root(root_str.clone());
+        let usage = collector.usage(&listing);
+        assert_eq!(usage.categories[&CacheCategory::Sources], 10_000);
+        assert!(usage.categories.contains_key(&CacheCategory::Mirrors));
+        assert_eq!(usage.versions[0], (keys[3].clone(), 4000));
+
+        let collected = |policy: &GcPolicy, now| {
+            collector
+                .plan(&listing, policy, now)
+                .candidates
+                .iter()
+                .map(|candidate| (candidate.removal.key.version.clone(), candidate.reason))
+                .collect::<Vec<(String, GcReason)>>()
+        };
+        // pinned v22.0 and active v23.0 stay, however old they are
+        assert_eq!(
+            collected(&GcPolicy::new().with_keep_last(1), Utc::now()),
+            vec![("v24.0".to_string(), GcReason::KeepLast(1))]
+        );
+        let later = Utc::now() + Duration::days(100);
+        assert_eq!(
+            collected(&GcPolicy::new().with_max_age(Duration::days(30)), later).len(),
+            2
+        );
+        assert!(collected(
+            &GcPolicy::new().with_max_age(Duration::days(30)),
+            Utc::now()
+        )
+        .is_empty());
+        assert_eq!(
+            collected(&GcPolicy::new().with_max_size(5000), Utc::now()).len(),
+            2
+        );
+
+        let plan = collector.plan(&listing, &GcPolicy::new().with_keep_last(1), Utc::now());
+        assert_eq!(
+            plan.stale,
+            vec![
+                format!("{}/bitcoin/signatures/v21.0", root_str),
+                format!("{}/trees/stale.sha256", root_str)
+            ]
+        );
+        for candidate in &plan.candidates {
+            collector.uninstaller().remove(&candidate.removal).unwrap();
+        }
+        collector.remove_stale(&plan).unwrap();
+        assert!(!root.join("bitcoin/bitcoin-24.0").exists());
+        assert!(!root.join("trees/stale.sha256").exists());
+        assert!(root.join("bitcoin/bitcoin-22.0").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,198 @@
+use super::token_store::redact;
+use crate::config::ShranDefault;
+use crate::error::ShranError;
+use curl::easy::Easy;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// A file that was successfully downloaded and verified
+pub struct Download {
+    /// The url of the candidate which served the file
+    pub source: String,
+    /// Hex encoded sha256 digest of `bytes`
+    pub sha256: String,
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Display for Download {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Downloaded {} bytes from {}\nSHA256: {}",
+            self.bytes.len(),
+            redact(&self.source),
+            self.sha256
+        )
+    }
+}
+
+/// Hex encoded sha256 digest of some bytes
+#[inline(always)]
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Downloads a single file from an ordered list of candidate urls,
+/// anything curl understands works, so `file://` mirrors are supported as well.
+///
+/// Each candidate is tried in order until one of them delivers a file matching
+/// the expected checksum. Slow or unreachable candidates are abandoned after a
+/// timeout instead of stalling the whole fetch.
+pub struct Downloader {
+    easy: Easy,
+    candidates: Vec<String>,
+    expected_sha256: Option<String>,
+}
+
+impl Downloader {
+    /// Build a new Downloader
+    ///
+    /// # Params
+    ///
+    /// 1. candidates: The urls to try, in order of preference
+    /// 2. expected_sha256: The hex encoded digest every candidate must match,
+    ///    if None the first successful download is accepted
+    pub fn new(candidates: Vec<String>, expected_sha256: Option<String>) -> Self {
+        Self {
+            easy: Easy::new(),
+            candidates,
+            expected_sha256: expected_sha256.map(|sum| sum.to_lowercase()),
+        }
+    }
+
+    /// Try every candidate in order
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::DownloadError listing why each candidate was rejected
+    /// if none of them could deliver the file.
+    pub fn fetch(&mut self) -> Result<Download, Box<dyn Error>> {
+        let mut failures: Vec<String> = Vec::new();
+        for url in self.candidates.clone() {
+            match self.fetch_one(&url) {
+                Ok(bytes) => {
+                    let sha256 = sha256_hex(&bytes);
+                    if let Some(expected) = &self.expected_sha256 {
+                        if &sha256 != expected {
+                            eprintln!(
+                                "Checksum mismatch from {}, trying next mirror",
+                                redact(&url)
+                            );
+                            failures.push(format!("{}: checksum mismatch ({})", url, sha256));
+                            continue;
+                        }
+                    }
+                    return Ok(Download {
+                        source: url,
+                        sha256,
+                        bytes,
+                    });
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Failed to download from {}, trying next mirror",
+                        redact(&url)
+                    );
+                    failures.push(format!("{}: {}", url, e));
+                }
+            }
+        }
+
+        Err(Box::new(ShranError::DownloadError {
+            msg: format!("no mirror could serve the file\n{}", failures.join("\n")),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        }))
+    }
+
+    fn fetch_one(&mut self, url: &str) -> Result<Vec<u8>, curl::Error> {
+        let mut file_bytes: Vec<u8> = Vec::new();
+        self.easy.url(url)?;
+        self.easy.follow_location(true)?;
+        self.easy.fail_on_error(true)?;
+        self.easy.connect_timeout(Duration::from_secs(
+            ShranDefault::MIRROR_CONNECT_TIMEOUT_SECS,
+        ))?;
+        self.easy
+            .low_speed_limit(ShranDefault::MIRROR_LOW_SPEED_BYTES)?;
+        self.easy
+            .low_speed_time(Duration::from_secs(ShranDefault::MIRROR_LOW_SPEED_SECS))?;
+        {
+            let mut transfer = self.easy.transfer();
+            transfer.write_function(|data| {
+                file_bytes.extend_from_slice(data);
+                Ok(data.len())
+            })?;
+            transfer.perform()?;
+        }
+        Ok(file_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sha256_hex, Downloader};
+    use crate::utils::mock_http::{self, MockResponse};
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+
+    const GOOD: &[u8] = b"pristine source archive";
+    const BAD: &[u8] = b"tampered source archive";
+
+    fn mirror_dir(name: &str, contents: &[u8]) -> String {
+        let dir = env::temp_dir().join(format!("shran-mirror-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("v23.0.tar.gz"), contents).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_downloader_falls_back_to_next_mirror() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert("/tampered/v23.0.tar.gz".to_string(), MockResponse::ok(BAD));
+        let base = mock_http::serve(routes);
+        let dir = mirror_dir("fallback", GOOD);
+
+        let mut downloader = Downloader::new(
+            vec![
+                format!("{}/missing/v23.0.tar.gz", base),
+                format!("{}/tampered/v23.0.tar.gz", base),
+                format!("file://{}/v23.0.tar.gz", dir),
+            ],
+            Some(sha256_hex(GOOD).to_uppercase()),
+        );
+        let download = downloader.fetch().unwrap();
+        assert_eq!(download.source, format!("file://{}/v23.0.tar.gz", dir));
+        assert_eq!(download.bytes, GOOD);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_downloader_without_checksum_takes_first() {
+        let mut routes: HashMap<String, MockResponse> = HashMap::new();
+        routes.insert("/v23.0.tar.gz".to_string(), MockResponse::ok(BAD));
+        let base = mock_http::serve(routes);
+
+        let mut downloader = Downloader::new(vec![format!("{}/v23.0.tar.gz", base)], None);
+        let download = downloader.fetch().unwrap();
+        assert_eq!(download.sha256, sha256_hex(BAD));
+    }
+
+    #[test]
+    fn test_downloader_all_mirrors_fail() {
+        let dir = mirror_dir("fail", BAD);
+        let mut downloader = Downloader::new(
+            vec![format!("file://{}/v23.0.tar.gz", dir)],
+            Some(sha256_hex(GOOD)),
+        );
+        match downloader.fetch() {
+            Ok(_) => panic!("tampered archive was accepted"),
+            Err(e) => assert!(e.to_string().contains("checksum mismatch")),
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,134 @@
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// An advisory lock on a file next to the one it protects, held until it is
+/// dropped. Every shran process takes it before a read-modify-write, so
+/// concurrent processes take turns instead of overwriting each other.
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// Block until the exclusive lock on `path` is acquired, the lock file
+    /// is created if it does not exist yet
+    ///
+    /// # Errors
+    /// Returns an io::Error if the lock file can not be opened or locked
+    pub fn exclusive<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        file.lock_exclusive()?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // fs2's unlock, newer toolchains have an inherent File::unlock of their own
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Temporary files of concurrent writers within one process must not collide
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace the contents of `path` without readers ever seeing a partial file.
+/// The contents are written to a temporary file in the same directory,
+/// flushed to disk, and renamed over `path`.
+///
+/// # Errors
+/// Returns an io::Error if the temporary file can not be written or renamed,
+/// the temporary file is removed again
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> io::Result<()> {
+    write_atomic_with(path.as_ref(), contents, OpenOptions::new())
+}
+
+/// Same as write_atomic for files only their owner may read, the file is
+/// never visible to anyone else, not even while it is written
+///
+/// # Errors
+/// Returns an io::Error if the temporary file can not be written or renamed
+pub fn write_private<P: AsRef<Path>>(path: P, contents: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    write_atomic_with(path.as_ref(), contents, options)
+}
+
+fn write_atomic_with(path: &Path, contents: &[u8], mut options: OpenOptions) -> io::Result<()> {
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    // a leftover of a crashed writer would keep its own permissions
+    let _ = fs::remove_file(&temp_path);
+    options.write(true).create_new(true);
+    let written = options.open(&temp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    match written.and_then(|_| fs::rename(&temp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_atomic, FileLock};
+    use std::env;
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_lock_is_exclusive_and_writes_are_atomic() {
+        let dir = env::temp_dir().join(format!("shran-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let lock_path = dir.join("counter.lock");
+        let counter_path = dir.join("counter");
+        write_atomic(&counter_path, b"0").unwrap();
+
+        // unlocked read-modify-write cycles would lose increments
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let (lock_path, counter_path) = (lock_path.clone(), counter_path.clone());
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        let _lock = FileLock::exclusive(&lock_path).unwrap();
+                        let count: u32 =
+                            fs::read_to_string(&counter_path).unwrap().parse().unwrap();
+                        thread::sleep(Duration::from_micros(100));
+                        write_atomic(&counter_path, (count + 1).to_string().as_bytes()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(fs::read_to_string(&counter_path).unwrap(), "80");
+
+        // no temporary files are left behind
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["counter", "counter.lock"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
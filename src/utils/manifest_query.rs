@@ -0,0 +1,349 @@
+use super::fs_manager::disk_usage;
+use super::manifest_manager::{EntryKind, ManifestEntry, ManifestKey};
+use crate::error::ShranError;
+use crate::github::version::{Version, VersionConstraint};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Where the contents of an installation came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceType {
+    /// Extracted from an archive, [e.g.] a github release or a bundle
+    Archive,
+    /// Checked out of a git repository at a pinned commit
+    Git,
+    /// A local working tree used in place
+    Directory,
+    /// Official release binaries
+    Prebuilt,
+}
+
+impl SourceType {
+    pub fn of(entry: &ManifestEntry) -> Self {
+        if entry.kind == EntryKind::Prebuilt {
+            return SourceType::Prebuilt;
+        }
+        if entry
+            .source
+            .as_ref()
+            .is_some_and(|source| source.starts_with("dir:"))
+        {
+            return SourceType::Directory;
+        }
+        if entry.commit.is_some() {
+            return SourceType::Git;
+        }
+        SourceType::Archive
+    }
+}
+
+impl fmt::Display for SourceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SourceType::Archive => "archive",
+            SourceType::Git => "git",
+            SourceType::Directory => "dir",
+            SourceType::Prebuilt => "prebuilt",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SourceType {
+    type Err = ShranError<'static>;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "archive" => Ok(SourceType::Archive),
+            "git" => Ok(SourceType::Git),
+            "dir" => Ok(SourceType::Directory),
+            "prebuilt" => Ok(SourceType::Prebuilt),
+            _ => Err(ShranError::ManifestEntryError {
+                msg: format!(
+                    "{} is not a source type, expected archive, git, dir or prebuilt",
+                    name
+                ),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }),
+        }
+    }
+}
+
+/// What the results of a query are ordered by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Oldest version first, versions that do not parse come last
+    #[default]
+    Version,
+    /// Oldest publish date first
+    Published,
+    /// Smallest installation first
+    Size,
+}
+
+impl FromStr for SortKey {
+    type Err = ShranError<'static>;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "version" => Ok(SortKey::Version),
+            "published" => Ok(SortKey::Published),
+            "size" => Ok(SortKey::Size),
+            _ => Err(ShranError::ManifestEntryError {
+                msg: format!(
+                    "{} is not a sort key, expected version, published or size",
+                    name
+                ),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }),
+        }
+    }
+}
+
+/// Selects manifest entries, every filter that is set must match
+///
+/// # Example
+///
+/// ```ignore
+/// let query = ManifestQuery::new()
+///     .with_blockchain("bitcoin")
+///     .with_versions(">=24.0".parse()?)
+///     .with_built(true)
+///     .sorted_by(SortKey::Published);
+/// for (key, entry) in manifest.query(&query) {
+///     println!("{} {}", key, entry.installation_location.display());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManifestQuery {
+    blockchain: Option<String>,
+    versions: Option<VersionConstraint>,
+    source_type: Option<SourceType>,
+    built: Option<bool>,
+    sort: SortKey,
+    descending: bool,
+}
+
+impl ManifestQuery {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_blockchain(mut self, blockchain: &str) -> Self {
+        self.blockchain = Some(blockchain.to_string());
+        self
+    }
+
+    /// Only entries whose version parses and satisfies the constraint
+    pub fn with_versions(mut self, versions: VersionConstraint) -> Self {
+        self.versions = Some(versions);
+        self
+    }
+
+    pub fn with_source_type(mut self, source_type: SourceType) -> Self {
+        self.source_type = Some(source_type);
+        self
+    }
+
+    /// Only entries that have, or have not, been built, see ManifestEntry::is_built
+    pub fn with_built(mut self, built: bool) -> Self {
+        self.built = Some(built);
+        self
+    }
+
+    pub fn sorted_by(mut self, sort: SortKey) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Reverse the order of the results
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+
+    pub fn matches(&self, key: &ManifestKey, entry: &ManifestEntry) -> bool {
+        if let Some(blockchain) = &self.blockchain {
+            if &key.blockchain != blockchain {
+                return false;
+            }
+        }
+        if let Some(versions) = &self.versions {
+            match entry.version.parse::<Version>() {
+                Ok(version) if versions.matches(&version) => {}
+                _ => return false,
+            }
+        }
+        if let Some(source_type) = self.source_type {
+            if SourceType::of(entry) != source_type {
+                return false;
+            }
+        }
+        if let Some(built) = self.built {
+            if entry.is_built() != built {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Filter and order `entries`, ties are broken by key
+    pub fn apply<'m>(
+        &self,
+        entries: Vec<(&'m ManifestKey, &'m ManifestEntry)>,
+    ) -> Vec<(&'m ManifestKey, &'m ManifestEntry)> {
+        let mut selected: Vec<(&ManifestKey, &ManifestEntry)> = entries
+            .into_iter()
+            .filter(|(key, entry)| self.matches(key, entry))
+            .collect();
+        match self.sort {
+            SortKey::Version => selected.sort_by(|a, b| {
+                compare_versions(&a.1.version, &b.1.version).then_with(|| a.0.cmp(b.0))
+            }),
+            SortKey::Published => selected.sort_by(|a, b| {
+                a.1.published_date
+                    .cmp(&b.1.published_date)
+                    .then_with(|| a.0.cmp(b.0))
+            }),
+            SortKey::Size => {
+                // measure every installation once, not once per comparison
+                let mut sized: Vec<(u64, (&ManifestKey, &ManifestEntry))> = selected
+                    .into_iter()
+                    .map(|(key, entry)| (disk_usage(&entry.installation_location), (key, entry)))
+                    .collect();
+                sized.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| (a.1).0.cmp((b.1).0)));
+                selected = sized.into_iter().map(|(_, entry)| entry).collect();
+            }
+        }
+        if self.descending {
+            selected.reverse();
+        }
+        selected
+    }
+}
+
+/// The entries a command line selector refers to, a manifest key, an exact
+/// version, or a version constraint [e.g.] `<24.0`
+///
+/// # Errors
+///
+/// Returns ShranError::ManifestEntryError if nothing matches,
+/// ShranError::VersionConstraintError if the selector is neither a version
+/// nor a constraint
+pub fn select<'m>(
+    entries: &[(&'m ManifestKey, &'m ManifestEntry)],
+    selector: &str,
+) -> Result<Vec<(&'m ManifestKey, &'m ManifestEntry)>, ShranError<'static>> {
+    let exact: Vec<(&ManifestKey, &ManifestEntry)> = entries
+        .iter()
+        .filter(|(key, entry)| key.to_string() == selector || entry.version == selector)
+        .cloned()
+        .collect();
+    let selected = if exact.is_empty() {
+        let constraint: VersionConstraint = selector.parse()?;
+        entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .version
+                    .parse::<Version>()
+                    .is_ok_and(|version| constraint.matches(&version))
+            })
+            .cloned()
+            .collect()
+    } else {
+        exact
+    };
+    if selected.is_empty() {
+        return Err(ShranError::ManifestEntryError {
+            msg: format!("no installed version matches {}", selector),
+            file: file!(),
+            line: line!(),
+            column: column!(),
+        });
+    }
+    Ok(selected)
+}
+
+/// Release versions in numeric order, anything else after them by name
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (a.parse::<Version>(), b.parse::<Version>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ManifestQuery, SortKey, SourceType};
+    use crate::utils::manifest_manager::{parse_timestamp, EntryKind, ManifestEntry, ManifestKey};
+
+    fn entry(version: &str, published: &str) -> ManifestEntry {
+        ManifestEntry::new(
+            version.to_string(),
+            parse_timestamp(published).unwrap(),
+            format!("/nonexistent/bitcoin-{}", version),
+        )
+    }
+
+    #[test]
+    fn test_query_filters_and_sorts() {
+        let entries = [
+            entry("v9.0", "2015-01-01T00:00:00Z"),
+            entry("v25.0", "2023-05-26T10:00:00Z"),
+            entry("master", "2024-02-01T00:00:00Z").with_commit(
+                "master".to_string(),
+                "1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e".to_string(),
+            ),
+            entry("v24.0.1", "2022-12-12T00:00:00Z"),
+            entry("v25.0", "2023-05-26T10:00:00Z").with_kind(EntryKind::Prebuilt),
+        ];
+        let keys: Vec<ManifestKey> = entries
+            .iter()
+            .map(|entry| ManifestKey::for_entry("bitcoin", entry))
+            .collect();
+        let listing = || keys.iter().zip(entries.iter()).collect::<Vec<_>>();
+        let names = |query: &ManifestQuery| {
+            query
+                .apply(listing())
+                .iter()
+                .map(|(key, _)| key.to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            names(&ManifestQuery::new()),
+            vec![
+                "bitcoin:v9.0",
+                "bitcoin:v24.0.1",
+                "bitcoin:v25.0",
+                "bitcoin:v25.0#prebuilt",
+                "bitcoin:master#1b2c3d4e5f60"
+            ]
+        );
+        assert_eq!(
+            names(
+                &ManifestQuery::new()
+                    .with_versions(">=24.0".parse().unwrap())
+                    .with_source_type(SourceType::Archive)
+                    .sorted_by(SortKey::Published)
+                    .descending()
+            ),
+            vec!["bitcoin:v25.0", "bitcoin:v24.0.1"]
+        );
+        assert_eq!(
+            names(&ManifestQuery::new().with_source_type(SourceType::Git)),
+            vec!["bitcoin:master#1b2c3d4e5f60"]
+        );
+        assert!(names(&ManifestQuery::new().with_built(true)).is_empty());
+        assert!(names(&ManifestQuery::new().with_blockchain("litecoin")).is_empty());
+        assert!("tarball".parse::<SourceType>().is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use super::file_lock::write_atomic;
+use super::manifest_manager::{parse_timestamp, Manifest, ManifestKey};
+use crate::config::ShranDefault;
+use crate::error::ShranError;
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::fs;
+
+/// The layout of manifest.yaml since schema version 2, the entries are
+/// preceded by the version of the schema they were written with
+///
+/// ```yaml
+/// schema_version: 2
+/// entries:
+///   "bitcoin:v25.0":
+///     version: v25.0
+///     ...
+/// ```
+#[derive(Serialize, Deserialize)]
+struct ManifestFile<E> {
+    schema_version: u32,
+    entries: E,
+}
+
+type Migration = fn(Value) -> Result<Value, ShranError<'static>>;
+
+/// `MIGRATIONS[n]` upgrades a manifest of schema version n + 1 to n + 2
+const MIGRATIONS: &[Migration] = &[migrate_v1];
+
+/// Read the manifest at `path`, an empty or missing file is an empty manifest.
+/// A manifest of an older schema is upgraded in memory only, see upgrade.
+///
+/// # Errors
+///
+/// Returns ShranError::ManifestSchemaError if the manifest was written by a
+/// newer shran or does not match its schema, ShranError::FileSystemError if
+/// it can not be read
+pub fn load(path: &str) -> Result<Manifest, ShranError<'static>> {
+    read(path).map(|(entries, _)| entries)
+}
+
+/// Same as load, a manifest of an older schema is also rewritten in place,
+/// after the previous file was copied to `manifest.yaml.v<version>.bak`.
+/// The caller has to hold the lock of FileSystemManager::lock_manifest, so
+/// that no other shran process writes the manifest in between.
+///
+/// # Errors
+///
+/// Same as load, and ShranError::FileSystemError if the manifest can not be
+/// backed up or rewritten
+pub fn upgrade(path: &str) -> Result<Manifest, ShranError<'static>> {
+    let (entries, version) = read(path)?;
+    let current = ShranDefault::MANIFEST_SCHEMA_VERSION;
+    if version < current {
+        let backup = format!("{}.v{}.bak", path, version);
+        fs::copy(path, &backup).map_err(|e| fs_error(format!("{}: {}", backup, e)))?;
+        store(path, &entries)?;
+        eprintln!(
+            "Upgraded {} from schema version {} to {}, the previous manifest was saved to {}",
+            path, version, current, backup
+        );
+    }
+    Ok(entries)
+}
+
+/// The entries of the manifest at `path`, and the schema version it was written with
+fn read(path: &str) -> Result<(Manifest, u32), ShranError<'static>> {
+    let current = ShranDefault::MANIFEST_SCHEMA_VERSION;
+    let yaml = match fs::read_to_string(path) {
+        Ok(yaml) => yaml,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(fs_error(format!("{}: {}", path, e))),
+    };
+    if yaml.trim().is_empty() {
+        return Ok((Manifest::new(), current));
+    }
+    let value: Value = serde_yaml::from_str(&yaml)
+        .map_err(|e| schema_error(format!("{} is not valid yaml: {}", path, e)))?;
+    let version =
+        schema_version(&value).map_err(|msg| schema_error(format!("{}: {}", path, msg)))?;
+    if version > current {
+        return Err(schema_error(format!(
+            "{} was written by a newer shran (schema version {}), this shran understands up to version {}, please upgrade shran",
+            path, version, current
+        )));
+    }
+
+    let mut migrated = value;
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migrated = migration(migrated)
+            .map_err(|e| schema_error(format!("{} could not be upgraded: {}", path, e)))?;
+    }
+    let file: ManifestFile<Manifest> = serde_yaml::from_value(migrated).map_err(|e| {
+        schema_error(format!(
+            "{} does not match schema version {}: {}",
+            path, version, e
+        ))
+    })?;
+    Ok((file.entries, version))
+}
+
+/// Write `entries` to `path` with the current schema version, atomically
+/// replacing the previous manifest
+///
+/// # Errors
+///
+/// Returns ShranError::FileSystemError if the manifest can not be serialized or written
+pub fn store(path: &str, entries: &Manifest) -> Result<(), ShranError<'static>> {
+    let file = ManifestFile {
+        schema_version: ShranDefault::MANIFEST_SCHEMA_VERSION,
+        entries,
+    };
+    let yaml = serde_yaml::to_string(&file).map_err(|e| fs_error(format!("{}", e)))?;
+    write_atomic(path, yaml.as_bytes()).map_err(|e| fs_error(format!("{}: {}", path, e)))
+}
+
+/// Manifests without a `schema_version` predate versioning, and are version 1
+fn schema_version(value: &Value) -> Result<u32, String> {
+    let version = match value {
+        Value::Mapping(mapping) => mapping.get(&Value::from("schema_version")),
+        _ => return Err("expected a mapping of manifest entries".to_string()),
+    };
+    match version {
+        None => Ok(1),
+        Some(Value::Number(number)) => match number.as_u64() {
+            Some(version) if version >= 1 && version <= u32::MAX as u64 => Ok(version as u32),
+            _ => Err(format!("{} is not a schema version", number)),
+        },
+        Some(_) => Err("schema_version has to be a number".to_string()),
+    }
+}
+
+/// Version 1 is a bare mapping of free form descriptions like
+/// `Bitcoin core v25.0` to entries, version 2 moves the entries under
+/// `entries`, keyed by ManifestKey, with RFC 3339 publish dates
+fn migrate_v1(value: Value) -> Result<Value, ShranError<'static>> {
+    let mut entries = Mapping::new();
+    if let Value::Mapping(legacy) = value {
+        for (key, mut entry) in legacy {
+            let key: ManifestKey = match key.as_str() {
+                Some(key) => key.parse()?,
+                None => return Err(schema_error(format!("{:?} is not a manifest key", key))),
+            };
+            if let Value::Mapping(fields) = &mut entry {
+                if let Some(Value::String(date)) = fields.get(&Value::from("published_date")) {
+                    let date = parse_timestamp(date)?.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+                    fields.insert(Value::from("published_date"), Value::from(date));
+                }
+            }
+            entries.insert(Value::from(key.to_string()), entry);
+        }
+    }
+    let mut file = Mapping::new();
+    file.insert(Value::from("schema_version"), Value::from(2));
+    file.insert(Value::from("entries"), Value::Mapping(entries));
+    Ok(Value::Mapping(file))
+}
+
+fn schema_error(msg: String) -> ShranError<'static> {
+    ShranError::ManifestSchemaError {
+        msg,
+        file: file!(),
+        line: line!(),
+        column: column!(),
+    }
+}
+
+fn fs_error(msg: String) -> ShranError<'static> {
+    ShranError::FileSystemError {
+        msg,
+        file: file!(),
+        line: line!(),
+        column: column!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, store, upgrade, MIGRATIONS};
+    use crate::config::ShranDefault;
+    use crate::utils::manifest_manager::{parse_timestamp, ManifestKey};
+    use std::env;
+    use std::fs;
+
+    const LEGACY_MANIFEST: &str = "\
+Bitcoin core v23.0:
+  version: v23.0
+  published_date: 2022-04-25 14:17:32 UTC
+  installation_location: /tmp/bitcoin-23.0
+Bitcoin core v25.0 prebuilt:
+  version: v25.0
+  published_date: 2023-05-26 10:00:00 UTC
+  installation_location: /tmp/prebuilt/v25.0-x86_64-linux-gnu
+  kind: prebuilt
+";
+
+    #[test]
+    fn test_every_schema_version_has_a_migration() {
+        assert_eq!(
+            MIGRATIONS.len() as u32 + 1,
+            ShranDefault::MANIFEST_SCHEMA_VERSION
+        );
+    }
+
+    #[test]
+    fn test_load_upgrades_legacy_manifest_with_backup() {
+        let dir = env::temp_dir().join(format!("shran-schema-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("manifest.yaml").to_string_lossy().to_string();
+        fs::write(&path, LEGACY_MANIFEST).unwrap();
+
+        // reading alone leaves the manifest as it is
+        let manifest = load(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), LEGACY_MANIFEST);
+        assert_eq!(upgrade(&path).unwrap(), manifest);
+        let release = &manifest[&ManifestKey::new("bitcoin", "v23.0")];
+        assert_eq!(
+            release.published_date,
+            parse_timestamp("2022-04-25T14:17:32Z").unwrap()
+        );
+        assert!(
+            manifest.contains_key(&ManifestKey::new("bitcoin", "v25.0").with_variant("prebuilt"))
+        );
+
+        // the original is kept, and the manifest is rewritten in place
+        assert_eq!(
+            fs::read_to_string(format!("{}.v1.bak", path)).unwrap(),
+            LEGACY_MANIFEST
+        );
+        let upgraded = fs::read_to_string(&path).unwrap();
+        assert!(upgraded.contains("schema_version: 2\nentries:\n"));
+        assert_eq!(load(&path).unwrap(), manifest);
+
+        store(&path, &manifest).unwrap();
+        assert_eq!(load(&path).unwrap(), manifest);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_rejects_newer_and_invalid_manifests() {
+        let dir = env::temp_dir().join(format!("shran-schema-new-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("manifest.yaml").to_string_lossy().to_string();
+
+        fs::write(&path, "schema_version: 99\nentries: {}\n").unwrap();
+        let error = load(&path).unwrap_err().to_string();
+        assert!(error.contains("newer shran"));
+        assert!(error.contains("schema version 99"));
+
+        fs::write(
+            &path,
+            "schema_version: 2\nentries:\n  \"bitcoin:v25.0\":\n    version: v25.0\n",
+        )
+        .unwrap();
+        assert!(load(&path)
+            .unwrap_err()
+            .to_string()
+            .contains("schema version 2"));
+
+        fs::write(&path, "").unwrap();
+        assert!(load(&path).unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,13 +1,33 @@
+use super::token_store::EncryptedToken;
+use crate::error::ShranError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 
 /// Used to easily read and write github auth information
-/// to disk in yaml format. Currently only supports a token
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// to disk in yaml format. Holds either the plain token, or the
+/// token encrypted with a passphrase:
+///
+/// ```yaml
+/// github_authentication:
+///   encrypted_token: 5e0b...c2
+///   salt: 9f1c...4d
+///   nonce: 27aa...e0
+///   iterations: "600000"
+/// ```
+#[derive(Serialize, Deserialize, PartialEq)]
 pub struct GithubAuth {
     github_authentication: HashMap<String, String>,
 }
 
+impl fmt::Debug for GithubAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        let mut keys: Vec<&String> = self.github_authentication.keys().collect();
+        keys.sort();
+        write!(f, "GithubAuth {{ keys: {:?} }}", keys)
+    }
+}
+
 impl GithubAuth {
     /// Build a serializable/deserializable Token structure
     pub fn new(token: &String) -> Self {
@@ -19,10 +39,76 @@ impl GithubAuth {
         }
     }
 
-    pub fn extract_token(&self) -> String {
-        if let Some(t) = self.github_authentication.get("token") {
-            return t.to_owned();
+    /// Same as GithubAuth::new, storing the token encrypted with `passphrase`
+    ///
+    /// # Errors
+    /// Returns ShranError::GithubTokenError if the token can not be encrypted
+    pub fn encrypted(token: &str, passphrase: &str) -> Result<Self, ShranError<'static>> {
+        let encrypted = EncryptedToken::encrypt(token, passphrase)?;
+        let github_authentication = HashMap::from([
+            (String::from("encrypted_token"), encrypted.ciphertext),
+            (String::from("salt"), encrypted.salt),
+            (String::from("nonce"), encrypted.nonce),
+            (String::from("iterations"), encrypted.iterations.to_string()),
+        ]);
+        Ok(Self {
+            github_authentication,
+        })
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.github_authentication.contains_key("encrypted_token")
+    }
+
+    /// The stored token, `passphrase` is only needed if it is encrypted
+    ///
+    /// # Errors
+    /// Returns ShranError::GithubTokenError if no token is stored, or an
+    /// encrypted token can not be decrypted with the passphrase
+    pub fn extract_token(&self, passphrase: Option<&str>) -> Result<String, ShranError<'static>> {
+        let field = |name: &str| self.github_authentication.get(name).cloned();
+        let token = match (field("encrypted_token"), passphrase) {
+            (Some(ciphertext), Some(passphrase)) => EncryptedToken {
+                ciphertext,
+                salt: field("salt").unwrap_or_default(),
+                nonce: field("nonce").unwrap_or_default(),
+                iterations: field("iterations")
+                    .and_then(|iterations| iterations.parse().ok())
+                    .unwrap_or_default(),
+            }
+            .decrypt(passphrase)?,
+            (Some(_), None) => {
+                return Err(token_error(
+                    "the stored token is encrypted, a passphrase is needed".to_string(),
+                ))
+            }
+            (None, _) => field("token").unwrap_or_default(),
+        };
+        if token.trim().is_empty() {
+            return Err(token_error(
+                "no github token is stored, run `shran auth --token <token>`".to_string(),
+            ));
         }
-        String::from("")
+        Ok(token)
+    }
+}
+
+fn token_error(msg: String) -> ShranError<'static> {
+    ShranError::GithubTokenError {
+        msg,
+        file: file!(),
+        line: line!(),
+        column: column!(),
     }
 }
+
+/// A cached github api response, stored next to the ETag github sent with it.
+/// Revalidating it with `If-None-Match` costs nothing against the rate limit
+/// when the resource has not changed.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EtagEntry {
+    pub etag: String,
+    pub body: String,
+    /// The url of the next page, if the response was paginated
+    pub next: Option<String>,
+}
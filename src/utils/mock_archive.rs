@@ -0,0 +1,22 @@
+//! Gzipped tarballs built in memory, so that everything extracting a
+//! downloaded archive can be exercised without fixtures on disk.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// A gzipped tarball holding `files`, given as path and contents. Paths are
+/// stored exactly as given, without the checks tar::Builder applies to them,
+/// so archives escaping their destination can be built as well. Every file
+/// is executable, like the binaries of a release.
+pub fn gzip_tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+    for (path, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append(&header, *contents).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
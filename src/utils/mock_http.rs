@@ -0,0 +1,103 @@
+//! A tiny blocking http server which only exists for the tests, so that
+//! everything talking to github or a download mirror can be exercised
+//! without any network access.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// A canned response for a single request path
+#[derive(Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// When set, a request carrying this value in `If-None-Match`
+    /// is answered with a 304 and an empty body
+    pub etag: Option<String>,
+}
+
+impl MockResponse {
+    pub fn ok(body: &[u8]) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: body.to_vec(),
+            etag: None,
+        }
+    }
+
+    pub fn json(body: &str) -> Self {
+        Self::ok(body.as_bytes()).header("Content-Type", "application/json")
+    }
+
+    pub fn etag(mut self, etag: &str) -> Self {
+        self.etag = Some(etag.to_string());
+        self.header("ETag", etag)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Serve `routes` on a random local port from a background thread, any
+/// unknown path is answered with a 404. A `{base}` inside of a header value
+/// is replaced with the servers own url, which is needed for pagination links.
+/// Returns the base url of the server.
+pub fn serve(routes: HashMap<String, MockResponse>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let server_base = base.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut request: Vec<u8> = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let path = path.split('?').next().unwrap_or(path);
+            let mut response = routes.get(path).cloned().unwrap_or(MockResponse {
+                status: 404,
+                headers: Vec::new(),
+                body: b"{\"message\": \"Not Found\"}".to_vec(),
+                etag: None,
+            });
+            if let Some(etag) = &response.etag {
+                let revalidated = request.lines().any(|line| {
+                    line.to_lowercase().starts_with("if-none-match:")
+                        && line.split_once(':').map(|(_, v)| v.trim()) == Some(etag.as_str())
+                });
+                if revalidated {
+                    response.status = 304;
+                    response.body.clear();
+                }
+            }
+
+            let mut head = format!(
+                "HTTP/1.1 {} MOCK\r\nContent-Length: {}\r\nConnection: close\r\n",
+                response.status,
+                response.body.len()
+            );
+            for (name, value) in &response.headers {
+                let value = value.replace("{base}", &server_base);
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            head.push_str("\r\n");
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&response.body);
+        }
+    });
+    base
+}
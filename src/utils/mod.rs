@@ -1,8 +1,27 @@
-pub use fs_manager::{BlockchainKind, FileSystemManager};
-pub use manifest_manager::ManifestManager;
-pub use misc_serde::GithubAuth;
+pub use archive::{Archiver, TapeArchive};
+pub use cache_gc::{parse_age, parse_size, CacheCollector, GcPolicy};
+pub use downloader::Downloader;
+pub use fs_manager::{disk_usage, human_size, BlockchainKind, FileSystemManager};
+pub use manifest_manager::{ManifestKey, ManifestManager};
+pub use manifest_query::{select, ManifestQuery, SortKey, SourceType};
+pub use misc_serde::{EtagEntry, GithubAuth};
+pub use rescan::ManifestScanner;
+pub use token_store::redact;
+pub use uninstall::Uninstaller;
 
 mod archive;
+pub mod cache_gc;
+pub mod downloader;
+pub mod file_lock;
 pub mod fs_manager;
 pub mod manifest_manager;
+pub mod manifest_query;
+mod manifest_schema;
 pub mod misc_serde;
+#[cfg(test)]
+pub mod mock_archive;
+#[cfg(test)]
+pub mod mock_http;
+pub mod rescan;
+pub mod token_store;
+pub mod uninstall;
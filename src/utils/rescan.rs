@@ -0,0 +1,448 @@
+use super::manifest_manager::{EntryKind, ManifestEntry, ManifestKey};
+use crate::config::ShranDefault;
+use crate::sources::git;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// The outcome of comparing the cache directories against the manifest
+#[derive(Debug, Default)]
+pub struct RescanReport {
+    /// Entries rebuilt from source trees the manifest does not know about
+    pub recovered: Vec<(ManifestKey, ManifestEntry)>,
+    /// Entries whose installation location no longer exists
+    pub orphaned: Vec<ManifestKey>,
+    /// Directories in the cache that are not in the manifest, and could not
+    /// be recovered
+    pub unlisted: Vec<String>,
+}
+
+impl RescanReport {
+    pub fn is_consistent(&self) -> bool {
+        self.recovered.is_empty() && self.orphaned.is_empty() && self.unlisted.is_empty()
+    }
+}
+
+impl fmt::Display for RescanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_consistent() {
+            return writeln!(f, "The manifest matches the cache");
+        }
+        for (key, entry) in &self.recovered {
+            writeln!(
+                f,
+                "recovered {} at {}",
+                key,
+                entry.installation_location.display()
+            )?;
+        }
+        for key in &self.orphaned {
+            writeln!(f, "orphaned  {}", key)?;
+        }
+        for dir in &self.unlisted {
+            writeln!(f, "unlisted  {}", dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rebuilds manifest entries from the source trees and prebuilt binaries
+/// found in the cache, for when the manifest was lost or edited by hand.
+pub struct ManifestScanner {
+    cache_root: String,
+    prebuilt_root: String,
+}
+
+impl Default for ManifestScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManifestScanner {
+    pub fn new() -> Self {
+        Self {
+            cache_root: ShranDefault::cache_dir(),
+            prebuilt_root: ShranDefault::prebuilt_dir(),
+        }
+    }
+
+    /// Same as ManifestScanner::new, but scans `cache_root` instead of the
+    /// default cache directory
+    pub fn with_root(cache_root: String) -> Self {
+        let prebuilt_root = format!("{}/prebuilt", cache_root);
+        Self {
+            cache_root,
+            prebuilt_root,
+        }
+    }
+
+    /// Also look for prebuilt binaries in `prebuilt_root`, the install prefix
+    pub fn with_prebuilt_root(mut self, prebuilt_root: String) -> Self {
+        self.prebuilt_root = prebuilt_root;
+        self
+    }
+
+    /// Walk the cache directory of every supported blockchain, and the
+    /// prebuilt binaries in the cache and the install prefix, comparing what
+    /// is found with `entries`
+    pub fn scan(&self, entries: &[(&ManifestKey, &ManifestEntry)]) -> RescanReport {
+        let mut report = RescanReport::default();
+        let listed: Vec<&Path> = entries
+            .iter()
+            .map(|(_, entry)| entry.installation_location.as_path())
+            .collect();
+        for (key, entry) in entries {
+            if !entry.installation_location.exists() {
+                report.orphaned.push(key.to_owned().to_owned());
+            }
+        }
+        // a key can only be recovered if no entry with an existing tree uses it
+        let mut taken: Vec<ManifestKey> = entries
+            .iter()
+            .filter(|(_, entry)| entry.installation_location.exists())
+            .map(|(key, _)| key.to_owned().to_owned())
+            .collect();
+
+        let mut found: Vec<(String, Option<(ManifestKey, ManifestEntry)>)> = Vec::new();
+        for blockchain in ShranDefault::SUPPORTED_BLOCKCHAINS {
+            for dir in subdirectories(&format!("{}/{}", self.cache_root, blockchain)) {
+                let name = dir.rsplit('/').next().unwrap_or_default();
+                // signatures and git mirrors are not source trees
+                if name == "signatures" || name.ends_with(".git") {
+                    continue;
+                }
+                found.push((dir.clone(), source_entry(blockchain, &dir)));
+            }
+        }
+        let mut prebuilt_roots = vec![format!("{}/prebuilt", self.cache_root)];
+        if Path::new(&self.prebuilt_root) != Path::new(&prebuilt_roots[0]) {
+            prebuilt_roots.push(self.prebuilt_root.clone());
+        }
+        for dir in prebuilt_roots.iter().flat_map(|root| subdirectories(root)) {
+            found.push((
+                dir.clone(),
+                prebuilt_entry(ShranDefault::SUPPORTED_BLOCKCHAINS[0], &dir),
+            ));
+        }
+
+        for (dir, recovered) in found {
+            if listed.iter().any(|location| *location == Path::new(&dir)) {
+                continue;
+            }
+            match recovered {
+                Some((key, entry)) if !taken.contains(&key) => {
+                    taken.push(key.clone());
+                    report.recovered.push((key, entry));
+                }
+                _ => report.unlisted.push(dir),
+            }
+        }
+        report
+    }
+}
+
+/// Every directory directly inside `path` except hidden staging directories,
+/// sorted by name
+fn subdirectories(path: &str) -> Vec<String> {
+    let mut dirs: Vec<String> = match fs::read_dir(path) {
+        Ok(read_dir) => read_dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| entry.path().to_string_lossy().to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    dirs.sort();
+    dirs
+}
+
+/// When a directory was last modified, standing in for the publish date
+fn modified_date(path: &str) -> DateTime<Utc> {
+    match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified.into(),
+        Err(_) => Utc::now(),
+    }
+}
+
+/// Identify an extracted source tree, or a checkout of the git mirror
+fn source_entry(blockchain: &str, dir: &str) -> Option<(ManifestKey, ManifestEntry)> {
+    let tag = tree_version(dir)?;
+    let mut entry = ManifestEntry::new(tag.clone(), modified_date(dir), dir);
+    if Path::new(&format!("{}/.git", dir)).exists() {
+        let commit = git::git(&["-C", dir, "rev-parse", "--verify", "HEAD"]).ok()?;
+        entry = entry.with_commit(tag, commit);
+    }
+    Some((ManifestKey::for_entry(blockchain, &entry), entry))
+}
+
+/// Prebuilt binaries are unpacked into `{tag}-{platform}`
+fn prebuilt_entry(blockchain: &str, dir: &str) -> Option<(ManifestKey, ManifestEntry)> {
+    let name = dir.rsplit('/').next()?;
+    let (tag, _platform) = name.split_once('-')?;
+    let entry =
+        ManifestEntry::new(tag.to_string(), modified_date(dir), dir).with_kind(EntryKind::Prebuilt);
+    Some((ManifestKey::for_entry(blockchain, &entry), entry))
+}
+
+/// The release tag of a source tree, [e.g.] v24.0.1, read from the version
+/// in configure.ac, or CMakeLists.txt for trees without autotools
+pub fn tree_version(dir: &str) -> Option<String> {
+    let version = fs::read_to_string(format!("{}/configure.ac", dir))
+        .ok()
+        .and_then(|text| autoconf_version(&text))
+        .or_else(|| {
+            fs::read_to_string(format!("{}/CMakeLists.txt", dir))
+                .ok()
+                .and_then(|text| cmake_version(&text))
+        })?;
+    Some(release_tag(&version))
+}
+
+/// Bitcoin core leaves a zero build number out of its tags since v22.0,
+/// 24.0.0 is tagged v24.0 while 0.21.0 keeps every component
+fn release_tag(version: &str) -> String {
+    let (number, rc) = match version.find("rc") {
+        Some(at) => version.split_at(at),
+        None => (version, ""),
+    };
+    let mut parts: Vec<&str> = number.split('.').collect();
+    if parts.len() == 3 && parts[0] != "0" && parts[2] == "0" {
+        parts.pop();
+    }
+    format!("v{}{}", parts.join("."), rc)
+}
+
+/// The version argument of AC_INIT, with `define`d macros expanded
+fn autoconf_version(text: &str) -> Option<String> {
+    let mut defines: HashMap<String, String> = HashMap::new();
+    let mut version: Option<String> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(args) = line.strip_prefix("define(") {
+            let args = m4_args(args);
+            if args.len() == 2 {
+                defines.insert(args[0].clone(), args[1].clone());
+            }
+        } else if let Some(args) = line.strip_prefix("AC_INIT(") {
+            version = m4_args(args).get(1).cloned();
+            break;
+        }
+    }
+    let version = version?;
+    // the release candidate suffix is added by an m4_if, expanded below
+    let expression = version.split("m4_if").next().unwrap_or_default();
+    let mut components: Vec<String> = Vec::new();
+    for token in expression.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        if let Some(value) = defines.get(token) {
+            components.push(value.clone());
+        }
+    }
+    let mut version = if components.is_empty() {
+        expression.trim().to_string()
+    } else {
+        components.join(".")
+    };
+    if version.is_empty() || !version.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if let Some(rc) = release_candidate(&defines) {
+        version.push_str(&format!("rc{}", rc));
+    }
+    Some(version)
+}
+
+/// Split the arguments of an m4 call, `text` starts after its opening
+/// parenthesis. Quotes are removed and nested calls are kept verbatim.
+fn m4_args(text: &str) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    let mut quoted = 0usize;
+    for c in text.chars() {
+        match c {
+            '[' => {
+                quoted += 1;
+                if quoted > 1 {
+                    current.push(c);
+                }
+            }
+            ']' => {
+                quoted = quoted.saturating_sub(1);
+                if quoted > 0 {
+                    current.push(c);
+                }
+            }
+            '(' if quoted == 0 => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' if quoted == 0 && depth == 0 => break,
+            ')' if quoted == 0 => {
+                depth -= 1;
+                current.push(c);
+            }
+            ',' if quoted == 0 && depth == 0 => {
+                args.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    args.push(current.trim().to_string());
+    args
+}
+
+/// The VERSION of the `project` command, with `set` variables expanded
+fn cmake_version(text: &str) -> Option<String> {
+    let mut variables: HashMap<String, String> = HashMap::new();
+    let mut rest = text;
+    while let Some(at) = rest.find("set(") {
+        rest = &rest[at + "set(".len()..];
+        let end = rest.find(')')?;
+        let mut words = rest[..end].split_whitespace();
+        if let (Some(name), Some(value)) = (words.next(), words.next()) {
+            variables.insert(name.to_string(), value.trim_matches('"').to_string());
+        }
+    }
+    let project = &text[text.find("project(")?..];
+    let project = &project[..project.find(')')?];
+    let mut words = project.split_whitespace();
+    words.find(|word| *word == "VERSION")?;
+    let mut version = words.next()?.to_string();
+    for (name, value) in &variables {
+        version = version.replace(&format!("${{{}}}", name), value);
+    }
+    if version.contains('$') || !version.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if let Some(rc) = release_candidate(&variables) {
+        version.push_str(&format!("rc{}", rc));
+    }
+    Some(version)
+}
+
+/// A non zero `*VERSION_RC` macro or variable
+fn release_candidate(values: &HashMap<String, String>) -> Option<&String> {
+    values
+        .iter()
+        .find(|(name, value)| name.ends_with("VERSION_RC") && value.as_str() != "0")
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{autoconf_version, cmake_version, release_tag, ManifestScanner};
+    use crate::utils::manifest_manager::{ManifestEntry, ManifestKey};
+    use chrono::Utc;
+    use std::env;
+    use std::fs;
+
+    const CONFIGURE_AC: &str = "\
+AC_PREREQ([2.69])
+define(_CLIENT_VERSION_MAJOR, 24)
+define(_CLIENT_VERSION_MINOR, 0)
+define(_CLIENT_VERSION_BUILD, 1)
+define(_CLIENT_VERSION_RC, 0)
+define(_CLIENT_VERSION_IS_RELEASE, true)
+AC_INIT([Bitcoin Core],m4_join([.], _CLIENT_VERSION_MAJOR, _CLIENT_VERSION_MINOR, _CLIENT_VERSION_BUILD)m4_if(_CLIENT_VERSION_RC, [0], [], [rc]_CLIENT_VERSION_RC),[https://github.com/bitcoin/bitcoin/issues],[bitcoin],[https://bitcoincore.org/])
+";
+
+    const CMAKE_LISTS: &str = "\
+cmake_minimum_required(VERSION 3.22)
+set(CLIENT_NAME \"Bitcoin Core\")
+set(CLIENT_VERSION_MAJOR 29)
+set(CLIENT_VERSION_MINOR 0)
+set(CLIENT_VERSION_BUILD 0)
+set(CLIENT_VERSION_RC 2)
+project(BitcoinCore
+  VERSION ${CLIENT_VERSION_MAJOR}.${CLIENT_VERSION_MINOR}.${CLIENT_VERSION_BUILD}
+  LANGUAGES NONE
+)
+";
+
+    #[test]
+    fn test_tree_versions() {
+        assert_eq!(autoconf_version(CONFIGURE_AC).unwrap(), "24.0.1");
+        assert_eq!(
+            autoconf_version("AC_INIT([litecoin], [0.18.1], [bugs])").unwrap(),
+            "0.18.1"
+        );
+        assert_eq!(cmake_version(CMAKE_LISTS).unwrap(), "29.0.0rc2");
+        assert_eq!(release_tag("24.0.1"), "v24.0.1");
+        assert_eq!(release_tag("29.0.0rc2"), "v29.0rc2");
+        assert_eq!(release_tag("0.21.0"), "v0.21.0");
+    }
+
+    #[test]
+    fn test_scan_recovers_reports_orphans_and_unlisted() {
+        let root = env::temp_dir().join(format!("shran-rescan-{}", std::process::id()));
+        let root_str = root.to_string_lossy().to_string();
+        for dir in [
+            "bitcoin/bitcoin-24.0.1",
+            "bitcoin/bitcoin-29.0rc2",
+            "bitcoin/notes",
+            "bitcoin/signatures/v24.0.1",
+            "prebuilt/v25.0-x86_64-linux-gnu",
+            "prefix/v26.0-x86_64-linux-gnu",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(
+            root.join("bitcoin/bitcoin-24.0.1/configure.ac"),
+            CONFIGURE_AC,
+        )
+        .unwrap();
+        fs::write(
+            root.join("bitcoin/bitcoin-29.0rc2/CMakeLists.txt"),
+            CMAKE_LISTS,
+        )
+        .unwrap();
+
+        let keys: Vec<ManifestKey> = vec![
+            ManifestKey::new("bitcoin", "v29.0rc2"),
+            ManifestKey::new("bitcoin", "v23.0"),
+        ];
+        let entries = [
+            ManifestEntry::new(
+                "v29.0rc2".to_string(),
+                Utc::now(),
+                format!("{}/bitcoin/bitcoin-29.0rc2", root_str),
+            ),
+            ManifestEntry::new(
+                "v23.0".to_string(),
+                Utc::now(),
+                format!("{}/bitcoin/bitcoin-23.0", root_str),
+            ),
+        ];
+        let listing: Vec<(&ManifestKey, &ManifestEntry)> =
+            keys.iter().zip(entries.iter()).collect();
+
+        let report = ManifestScanner::with_root(root_str.clone())
+            .with_prebuilt_root(format!("{}/prefix", root_str))
+            .scan(&listing);
+        let recovered: Vec<String> = report
+            .recovered
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .collect();
+        assert_eq!(
+            recovered,
+            vec![
+                "bitcoin:v24.0.1",
+                "bitcoin:v25.0#prebuilt",
+                "bitcoin:v26.0#prebuilt"
+            ]
+        );
+        assert_eq!(
+            report.recovered[0].1.installation_location,
+            root.join("bitcoin/bitcoin-24.0.1")
+        );
+        assert_eq!(report.orphaned, vec![ManifestKey::new("bitcoin", "v23.0")]);
+        assert_eq!(report.unlisted, vec![format!("{}/bitcoin/notes", root_str)]);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,397 @@
+use super::manifest_manager::{ManifestEntry, ManifestKey};
+use super::manifest_query::select;
+use crate::config::ShranDefault;
+use crate::error::ShranError;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+/// Everything that goes away when a single manifest entry is removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Removal {
+    pub key: ManifestKey,
+    pub entry: ManifestEntry,
+    /// Paths deleted from disk, the source tree with its in tree build
+    /// directories first, followed by artifacts like signatures and the
+    /// index of the tree's content
+    pub paths: Vec<String>,
+}
+
+impl fmt::Display for Removal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.key)?;
+        if self.paths.is_empty() {
+            write!(
+                f,
+                "\n  {} is not a version directory of the cache and is kept",
+                self.entry.installation_location.display()
+            )?;
+        }
+        for path in &self.paths {
+            write!(f, "\n  {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes fetched versions from disk. Only directories inside of the cache
+/// are ever deleted, a working tree used in place through `dir:` merely
+/// loses its manifest entry.
+pub struct Uninstaller {
+    cache_root: String,
+    prebuilt_root: String,
+    working_dir: PathBuf,
+}
+
+impl Uninstaller {
+    /// The active version is the one whose installation contains
+    /// `working_dir`, see Uninstaller::is_active. It is refused by
+    /// Uninstaller::plan, so a tree is never deleted out from under a shell
+    /// or build working in it.
+    ///
+    /// # Params
+    ///
+    /// 1. working_dir: The version whose tree contains this directory is
+    ///    the active one, and can not be removed
+    pub fn new(working_dir: PathBuf) -> Self {
+        Self {
+            cache_root: ShranDefault::cache_dir(),
+            prebuilt_root: ShranDefault::prebuilt_dir(),
+            working_dir,
+        }
+    }
+
+    /// Treat `cache_root` as the cache instead of the default cache directory,
+    /// prebuilt binaries are expected in its `prebuilt` directory
+    pub fn with_cache_root(mut self, cache_root: String) -> Self {
+        self.prebuilt_root = format!("{}/prebuilt", cache_root);
+        self.cache_root = cache_root;
+        self
+    }
+
+    /// Prebuilt binaries are installed to `prebuilt_root`, the install prefix
+    pub fn with_prebuilt_root(mut self, prebuilt_root: String) -> Self {
+        self.prebuilt_root = prebuilt_root;
+        self
+    }
+
+    /// Select the entries to remove by manifest key, exact version, or
+    /// version constraint [e.g.] `<24.0`
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::ManifestEntryError if nothing matches, or if one of
+    /// the matches is the active version, ShranError::VersionConstraintError
+    /// if the selector is neither a version nor a constraint, see select
+    pub fn plan(
+        &self,
+        entries: &[(&ManifestKey, &ManifestEntry)],
+        selector: &str,
+    ) -> Result<Vec<Removal>, ShranError<'static>> {
+        let selected = select(entries, selector)?;
+        let mut removals: Vec<Removal> = Vec::new();
+        for (key, entry) in selected {
+            if self.is_active(entry) {
+                return Err(ShranError::ManifestEntryError {
+                    msg: format!(
+                        "{} is the active version, {} is inside of it",
+                        key,
+                        self.working_dir.display()
+                    ),
+                    file: file!(),
+                    line: line!(),
+                    column: column!(),
+                });
+            }
+            removals.push(self.removal(key, entry, entries));
+        }
+        Ok(removals)
+    }
+
+    /// Everything that goes away when `key` is removed, `entries` are all
+    /// entries of the manifest, artifacts shared with them are kept
+    pub fn removal(
+        &self,
+        key: &ManifestKey,
+        entry: &ManifestEntry,
+        entries: &[(&ManifestKey, &ManifestEntry)],
+    ) -> Removal {
+        Removal {
+            key: key.to_owned(),
+            entry: entry.to_owned(),
+            paths: self.paths(entry, entries),
+        }
+    }
+
+    /// Delete the directories of a planned removal. A checkout that is a
+    /// worktree of a git mirror is unregistered from the mirror as well.
+    pub fn remove(&self, removal: &Removal) -> Result<(), Box<dyn Error>> {
+        for path in &removal.paths {
+            let mirror = worktree_mirror(path);
+            if Path::new(path).is_dir() {
+                fs::remove_dir_all(path)?;
+            } else if Path::new(path).exists() {
+                fs::remove_file(path)?;
+            }
+            if let Some(mirror) = mirror {
+                Command::new("git")
+                    .args(["-C", &mirror, "worktree", "prune"])
+                    .output()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// True if the working directory lies inside of the entry's tree, both
+    /// paths are canonicalized first so symlinks into the cache count too
+    pub fn is_active(&self, entry: &ManifestEntry) -> bool {
+        match (
+            fs::canonicalize(&entry.installation_location),
+            fs::canonicalize(&self.working_dir),
+        ) {
+            (Ok(location), Ok(working_dir)) => working_dir.starts_with(location),
+            _ => false,
+        }
+    }
+
+    /// The paths belonging to an entry which are safe to delete
+    fn paths(
+        &self,
+        entry: &ManifestEntry,
+        entries: &[(&ManifestKey, &ManifestEntry)],
+    ) -> Vec<String> {
+        let mut paths: Vec<String> = Vec::new();
+        let location = entry.installation_location.as_path();
+        if is_version_dir(&self.cache_root, &self.prebuilt_root, location) {
+            paths.push(location.to_string_lossy().to_string());
+        }
+        // the signatures of a release are shared by its source and prebuilt entries
+        let shared = entries
+            .iter()
+            .filter(|(_, other)| other.version == entry.version)
+            .count()
+            > 1;
+        if entry.commit.is_none() && !shared {
+            let signatures = format!("{}/bitcoin/signatures/{}", self.cache_root, entry.version);
+            if Path::new(&signatures).is_dir() {
+                paths.push(signatures);
+            }
+        }
+        // identical trees share their index
+        if let Some(tree_hash) = &entry.tree_hash {
+            let index = format!("{}/trees/{}.sha256", self.cache_root, tree_hash);
+            let shared = entries
+                .iter()
+                .filter(|(_, other)| other.tree_hash.as_ref() == Some(tree_hash))
+                .count()
+                > 1;
+            if !shared && Path::new(&index).is_file() {
+                paths.push(index);
+            }
+        }
+        paths
+    }
+}
+
+/// True only for the directory of a single version inside of the cache,
+/// [e.g.] `<cache>/bitcoin/bitcoin-25.0` or `<cache>/prebuilt/v25.0-x86_64-linux-gnu`,
+/// or of a single prebuilt version inside of `prebuilt_root`, the install
+/// prefix. The cache itself, a blockchain directory with every other
+/// version in it, git mirrors, signatures, staging directories and anything
+/// outside of the cache and the prefix are never the directory of a
+/// version, so they are never deleted.
+pub fn is_version_dir(cache_root: &str, prebuilt_root: &str, location: &Path) -> bool {
+    if let Some([name]) = relative_parts(prebuilt_root, location).as_deref() {
+        return !name.starts_with('.');
+    }
+    match relative_parts(cache_root, location).as_deref() {
+        Some([parent, name]) => {
+            (ShranDefault::SUPPORTED_BLOCKCHAINS.contains(&parent.as_str()) || parent == "prebuilt")
+                && !name.starts_with('.')
+                && name != "signatures"
+                && !name.ends_with(".git")
+        }
+        _ => false,
+    }
+}
+
+/// The plain components of `location` below `root`, None if it is not
+/// below `root` or climbs out of it
+fn relative_parts(root: &str, location: &Path) -> Option<Vec<String>> {
+    location
+        .strip_prefix(root)
+        .ok()?
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The git mirror a worktree checkout belongs to, read from its `.git` file
+/// which holds `gitdir: <mirror>/worktrees/<name>`
+fn worktree_mirror(path: &str) -> Option<String> {
+    let gitdir = fs::read_to_string(format!("{}/.git", path)).ok()?;
+    let gitdir = gitdir.trim().strip_prefix("gitdir:")?.trim();
+    let worktrees = Path::new(gitdir).parent()?;
+    if worktrees.file_name()? != "worktrees" {
+        return None;
+    }
+    Some(worktrees.parent()?.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_version_dir, Uninstaller};
+    use crate::utils::manifest_manager::{ManifestEntry, ManifestKey};
+    use chrono::Utc;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    fn entry(version: &str, location: &str) -> ManifestEntry {
+        ManifestEntry::new(version.to_string(), Utc::now(), location)
+    }
+
+    #[test]
+    fn test_plan_and_remove_versions() {
+        let root = env::temp_dir().join(format!("shran-remove-{}", std::process::id()));
+        let root_str = root.to_string_lossy().to_string();
+        for dir in [
+            "bitcoin/bitcoin-23.0/src",
+            "bitcoin/bitcoin-24.0",
+            "bitcoin/signatures/v23.0",
+            "trees",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let tree_hash = "ab".repeat(32);
+        let index = format!("{}/trees/{}.sha256", root_str, tree_hash);
+        fs::write(&index, "").unwrap();
+        let keys: Vec<ManifestKey> = ["v23.0", "v24.0", "dev"]
+            .iter()
+            .map(|version| ManifestKey::new("bitcoin", version))
+            .collect();
+        let entries = [
+            entry("v23.0", &format!("{}/bitcoin/bitcoin-23.0", root_str))
+                .with_tree_hash(tree_hash.clone()),
+            entry("v24.0", &format!("{}/bitcoin/bitcoin-24.0", root_str)),
+            entry("dev", "/home/dev/bitcoin"),
+        ];
+        let listing: Vec<(&ManifestKey, &ManifestEntry)> =
+            keys.iter().zip(entries.iter()).collect();
+
+        // working inside of v24.0 makes it the active version
+        let uninstaller =
+            Uninstaller::new(root.join("bitcoin/bitcoin-24.0")).with_cache_root(root_str.clone());
+        assert!(uninstaller.plan(&listing, "v24.0").is_err());
+        assert!(uninstaller.plan(&listing, "v22.0").is_err());
+
+        let removals = uninstaller.plan(&listing, "<24.0").unwrap();
+        assert_eq!(removals.len(), 1);
+        assert_eq!(
+            removals[0].paths,
+            vec![
+                format!("{}/bitcoin/bitcoin-23.0", root_str),
+                format!("{}/bitcoin/signatures/v23.0", root_str),
+                index.clone()
+            ]
+        );
+        uninstaller.remove(&removals[0]).unwrap();
+        assert!(!root.join("bitcoin/bitcoin-23.0").exists());
+        assert!(!root.join("bitcoin/signatures/v23.0").exists());
+        assert!(!std::path::Path::new(&index).exists());
+
+        // a tree outside of the cache is never deleted
+        let outside = uninstaller.plan(&listing, "dev").unwrap();
+        assert!(outside[0].paths.is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_prebuilt_versions_in_the_install_prefix() {
+        let root = env::temp_dir().join(format!("shran-remove-prefix-{}", std::process::id()));
+        let root_str = root.to_string_lossy().to_string();
+        let prefix = root.join("prefix");
+        let prefix_str = prefix.to_string_lossy().to_string();
+        fs::create_dir_all(prefix.join("v25.0-x86_64-linux-gnu/bin")).unwrap();
+
+        let key = ManifestKey::new("bitcoin", "v25.0");
+        let prebuilt = entry("v25.0", &format!("{}/v25.0-x86_64-linux-gnu", prefix_str));
+        let listing = [(&key, &prebuilt)];
+        let uninstaller = Uninstaller::new(env::temp_dir())
+            .with_cache_root(format!("{}/cache", root_str))
+            .with_prebuilt_root(prefix_str.clone());
+        let removals = uninstaller.plan(&listing, "v25.0").unwrap();
+        assert_eq!(
+            removals[0].paths,
+            vec![format!("{}/v25.0-x86_64-linux-gnu", prefix_str)]
+        );
+        uninstaller.remove(&removals[0]).unwrap();
+        assert!(!prefix.join("v25.0-x86_64-linux-gnu").exists());
+        assert!(prefix.is_dir());
+
+        for location in ["", ".v25.0-x86_64-linux-gnu.tar.gz", "v25.0/bin", ".."] {
+            assert!(
+                !is_version_dir(
+                    &format!("{}/cache", root_str),
+                    &prefix_str,
+                    &prefix.join(location)
+                ),
+                "{}",
+                location
+            );
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_only_version_dirs_are_deleted() {
+        let root = env::temp_dir().join(format!("shran-remove-loose-{}", std::process::id()));
+        let root_str = root.to_string_lossy().to_string();
+        fs::create_dir_all(root.join("bitcoin/bitcoin-25.0")).unwrap();
+
+        // a tarball without a single top level directory was once recorded
+        // at the blockchain directory, removing it must not empty the cache
+        let key = ManifestKey::new("bitcoin", "v24.0");
+        let loose = entry("v24.0", &format!("{}/bitcoin", root_str));
+        let listing = [(&key, &loose)];
+        let uninstaller = Uninstaller::new(env::temp_dir()).with_cache_root(root_str.clone());
+        let removals = uninstaller.plan(&listing, "v24.0").unwrap();
+        assert!(removals[0].paths.is_empty());
+        uninstaller.remove(&removals[0]).unwrap();
+        assert!(root.join("bitcoin/bitcoin-25.0").is_dir());
+
+        let prebuilt = format!("{}/prebuilt", root_str);
+        for location in ["bitcoin/bitcoin-25.0", "prebuilt/v25.0-x86_64-linux-gnu"] {
+            assert!(is_version_dir(&root_str, &prebuilt, &root.join(location)));
+        }
+        for location in [
+            "",
+            "bitcoin",
+            "bitcoin/.",
+            "bitcoin/signatures",
+            "bitcoin/bitcoin.git",
+            "bitcoin/.bitcoin-25.0.tar.gz",
+            "bitcoin/bitcoin-25.0/src",
+            "bitcoin/../prebuilt",
+            "trees",
+            "prebuilt",
+        ] {
+            assert!(
+                !is_version_dir(&root_str, &prebuilt, &root.join(location)),
+                "{}",
+                location
+            );
+        }
+        assert!(!is_version_dir(
+            &root_str,
+            &prebuilt,
+            Path::new("/home/dev/bitcoin")
+        ));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
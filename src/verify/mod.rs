@@ -0,0 +1,5 @@
+pub mod signatures;
+pub mod tree;
+
+pub use signatures::ThresholdVerifier;
+pub use tree::TreeVerifier;
@@ -0,0 +1,377 @@
+use crate::config::{ShranDefault, Upstream};
+use crate::error::ShranError;
+use crate::utils::FileSystemManager;
+use curl::easy::Easy;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The outcome of checking a detached SHA256SUMS.asc signature file
+/// against a keyring of trusted builder keys.
+#[derive(Debug, PartialEq)]
+pub struct SignatureReport {
+    pub tag: String,
+    pub threshold: usize,
+    pub trusted_fingerprints: Vec<String>,
+    /// The signed checksums, file name to hex encoded sha256 digest
+    pub sums: HashMap<String, String>,
+}
+
+impl SignatureReport {
+    /// Number of distinct trusted keys that produced a valid signature
+    #[inline(always)]
+    pub fn valid(&self) -> usize {
+        self.trusted_fingerprints.len()
+    }
+
+    #[inline(always)]
+    pub fn threshold_met(&self) -> bool {
+        self.valid() >= self.threshold
+    }
+
+    /// The signed checksum of the official source tarball of this release
+    pub fn source_sha256(&self) -> Option<String> {
+        let version = self.tag.trim_start_matches('v');
+        self.sums
+            .get(&format!(
+                "bitcoin-{}{}",
+                version,
+                ShranDefault::FILE_EXTENSION
+            ))
+            .cloned()
+    }
+
+    /// The checksum the source tarball of this release has to match, the
+    /// signed one. A checksum given on the command line has to agree with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::SignatureThresholdError if the source tarball is
+    /// not listed in the signed SHA256SUMS, or `given` is a different checksum
+    pub fn bind_source_sha256(&self, given: Option<&str>) -> Result<String, ShranError<'static>> {
+        let signed = self
+            .source_sha256()
+            .ok_or_else(|| ShranError::SignatureThresholdError {
+                msg: format!(
+                    "the source tarball of {} is not listed in the signed SHA256SUMS",
+                    self.tag
+                ),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            })?;
+        match given {
+            Some(given) if !given.eq_ignore_ascii_case(&signed) => {
+                Err(ShranError::SignatureThresholdError {
+                    msg: format!(
+                        "{} is not the signed checksum {} of {}",
+                        given, signed, self.tag
+                    ),
+                    file: file!(),
+                    line: line!(),
+                    column: column!(),
+                })
+            }
+            _ => Ok(signed),
+        }
+    }
+}
+
+impl fmt::Display for SignatureReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Signatures: {} of {} required trusted builder signatures for {}",
+            self.valid(),
+            self.threshold,
+            self.tag
+        )?;
+        for fingerprint in &self.trusted_fingerprints {
+            write!(f, "\n  {}", fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies that a release has been attested by at least `threshold` distinct
+/// builders from a local keyring, the same way bitcoin cores guix attestations
+/// are meant to be checked before a binary is trusted.
+///
+/// The keyring must be a binary OpenPGP keyring (`gpg --export > builders.gpg`),
+/// only keys inside of it are considered trusted, signatures from any other key
+/// are ignored.
+#[derive(Clone)]
+pub struct ThresholdVerifier {
+    keyring: String,
+    threshold: usize,
+}
+
+impl ThresholdVerifier {
+    /// Build a new ThresholdVerifier
+    ///
+    /// # Params
+    ///
+    /// 1. keyring: Path to the keyring containing the trusted builder keys
+    /// 2. threshold: The minimum amount of distinct trusted signatures required
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::FileSystemError if the keyring does not exist
+    ///
+    /// Returns ShranError::SignatureThresholdError if the threshold is zero
+    pub fn new(keyring: &str, threshold: usize) -> Result<Self, ShranError<'static>> {
+        if !Path::new(keyring).exists() {
+            return Err(ShranError::FileSystemError {
+                msg: format!("{} not found", keyring),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            });
+        }
+        if threshold == 0 {
+            return Err(ShranError::SignatureThresholdError {
+                msg: String::from("a signature threshold of 0 would accept unsigned releases"),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            });
+        }
+        // gpgv resolves keyrings without a slash relative to its home directory
+        let keyring = match fs::canonicalize(keyring) {
+            Ok(abs) => abs.to_string_lossy().to_string(),
+            Err(_) => keyring.to_string(),
+        };
+
+        Ok(Self { keyring, threshold })
+    }
+
+    /// Download SHA256SUMS and SHA256SUMS.asc for a given release tag from the
+    /// `binaries_url` of the upstream and verify the signatures on them.
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::SignatureThresholdError if fewer than `threshold`
+    /// trusted builders signed the release, curl errors if the files can not be
+    /// downloaded, and io errors if gpgv can not be executed.
+    pub fn verify_release(
+        &self,
+        upstream: &Upstream,
+        tag: &str,
+    ) -> Result<SignatureReport, Box<dyn Error>> {
+        let fs = FileSystemManager::new()?;
+        let base = upstream.binaries_url(tag);
+
+        let mut paths: Vec<String> = Vec::new();
+        for filename in [
+            ShranDefault::SHA256SUMS_FILENAME,
+            ShranDefault::SHA256SUMS_SIGNATURE_FILENAME,
+        ] {
+            let bytes = download(&format!("{}/{}", base, filename))?;
+            paths.push(fs.write_signature_file(tag, filename, bytes)?);
+        }
+
+        self.verify_files(tag, &paths[0], &paths[1])
+    }
+
+    /// Verify an already present SHA256SUMS file against its detached signature.
+    ///
+    /// # Errors
+    ///
+    /// Returns ShranError::SignatureThresholdError if fewer than `threshold`
+    /// trusted builders signed the file.
+    pub fn verify_files(
+        &self,
+        tag: &str,
+        sums: &str,
+        signature: &str,
+    ) -> Result<SignatureReport, Box<dyn Error>> {
+        // gpgv exits non zero as soon as a single signature can not be checked,
+        // which is expected when the keyring only holds a subset of the builders,
+        // so the status output is the only thing we can rely on.
+        let output = Command::new(ShranDefault::GPG_VERIFY_PROGRAM)
+            .args([
+                "--keyring",
+                &self.keyring,
+                "--status-fd",
+                "1",
+                signature,
+                sums,
+            ])
+            .output()?;
+        let status = String::from_utf8_lossy(&output.stdout);
+
+        let report = SignatureReport {
+            tag: tag.to_string(),
+            threshold: self.threshold,
+            trusted_fingerprints: valid_signers(&status),
+            sums: parse_sha256sums(&fs::read_to_string(sums)?),
+        };
+        if !report.threshold_met() {
+            return Err(Box::new(ShranError::SignatureThresholdError {
+                msg: format!(
+                    "{} has {} of {} required trusted signatures",
+                    tag,
+                    report.valid(),
+                    self.threshold
+                ),
+                file: file!(),
+                line: line!(),
+                column: column!(),
+            }));
+        }
+        Ok(report)
+    }
+}
+
+/// Collect the distinct primary key fingerprints of every good signature
+/// reported in gpg's machine readable status output.
+///
+/// A VALIDSIG line looks like
+/// `[GNUPG:] VALIDSIG <fpr> <date> <ts> <expire> <ver> <reserved> <pkalgo> <hashalgo> <class> <primary-fpr>`
+/// the primary key fingerprint is used so that a builder signing with
+/// two different subkeys is only counted once.
+fn valid_signers(status: &str) -> Vec<String> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut fingerprints: Vec<String> = Vec::new();
+    for line in status.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || fields[0] != "[GNUPG:]" || fields[1] != "VALIDSIG" {
+            continue;
+        }
+        let fingerprint = fields.get(11).unwrap_or(&fields[2]).to_string();
+        if seen.insert(fingerprint.clone()) {
+            fingerprints.push(fingerprint);
+        }
+    }
+    fingerprints
+}
+
+/// Parse the output of `sha256sum`, one `<digest>  <file name>` pair per line,
+/// into a map of file name to digest.
+pub fn parse_sha256sums(contents: &str) -> HashMap<String, String> {
+    let mut sums: HashMap<String, String> = HashMap::new();
+    for line in contents.lines() {
+        if let Some((digest, filename)) = line.trim().split_once(char::is_whitespace) {
+            // sha256sum marks files hashed in binary mode with a leading '*'
+            let filename = filename.trim().trim_start_matches('*');
+            sums.insert(filename.to_string(), digest.to_lowercase());
+        }
+    }
+    sums
+}
+
+fn download(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut easy = Easy::new();
+    let mut file_bytes: Vec<u8> = Vec::new();
+    easy.url(url)?;
+    easy.follow_location(true)?;
+    easy.fail_on_error(true)?;
+    {
+        let mut transfer = easy.transfer();
+        transfer.write_function(|data| {
+            file_bytes.extend_from_slice(data);
+            Ok(data.len())
+        })?;
+        transfer.perform()?;
+    }
+    Ok(file_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_sha256sums, valid_signers, SignatureReport};
+    use std::collections::HashMap;
+
+    const PRIMARY_A: &str = "E777299FC265DD04793070EB944D35F9AC3DB76A";
+    const PRIMARY_B: &str = "D1DBF2C4B96F2DEBF4C16654410108112E7EA81F";
+
+    fn validsig(subkey: &str, primary: &str) -> String {
+        format!(
+            "[GNUPG:] VALIDSIG {} 2023-05-26 1685110800 0 4 0 1 10 00 {}",
+            subkey, primary
+        )
+    }
+
+    #[test]
+    fn test_valid_signers_counts_distinct_primary_keys() {
+        let status = [
+            "[GNUPG:] NEWSIG".to_string(),
+            validsig("1111111111111111111111111111111111111111", PRIMARY_A),
+            "[GNUPG:] GOODSIG 944D35F9AC3DB76A builder a".to_string(),
+            validsig("2222222222222222222222222222222222222222", PRIMARY_A),
+            "[GNUPG:] ERRSIG 3333333333333333 1 10 00 1685110800 9".to_string(),
+            "[GNUPG:] NO_PUBKEY 3333333333333333".to_string(),
+            validsig(PRIMARY_B, PRIMARY_B),
+        ]
+        .join("\n");
+
+        let signers = valid_signers(&status);
+        assert_eq!(signers, vec![PRIMARY_A.to_string(), PRIMARY_B.to_string()]);
+    }
+
+    #[test]
+    fn test_valid_signers_ignores_bad_signatures() {
+        let status =
+            "[GNUPG:] BADSIG 944D35F9AC3DB76A builder a\n[GNUPG:] NO_PUBKEY 410108112E7EA81F";
+        assert!(valid_signers(status).is_empty());
+    }
+
+    #[test]
+    fn test_signature_report_threshold() {
+        let mut report = SignatureReport {
+            tag: "v25.0".to_string(),
+            threshold: 3,
+            trusted_fingerprints: vec![PRIMARY_A.to_string(), PRIMARY_B.to_string()],
+            sums: HashMap::new(),
+        };
+        assert!(!report.threshold_met());
+        report.threshold = 2;
+        assert!(report.threshold_met());
+    }
+
+    #[test]
+    fn test_parse_sha256sums() {
+        let contents = "\
+0a1d4aa4d6d9ab4e8bd3bb1a8ab3d8bd2a5c0a9c0c8b0c8b8a8f8e8d8c8b8a8f  bitcoin-25.0-x86_64-linux-gnu.tar.gz
+5708FC639CDFC27347CCFA50DB9B1D0C4F0D0F3A7DD5A3E4A0B1C2D3E4F5A6B7 *bitcoin-25.0.tar.gz
+";
+        let sums = parse_sha256sums(contents);
+        assert_eq!(sums.len(), 2);
+        let report = SignatureReport {
+            tag: "v25.0".to_string(),
+            threshold: 1,
+            trusted_fingerprints: Vec::new(),
+            sums,
+        };
+        assert_eq!(
+            report.source_sha256(),
+            Some("5708fc639cdfc27347ccfa50db9b1d0c4f0d0f3a7dd5a3e4a0b1c2d3e4f5a6b7".to_string())
+        );
+    }
+
+    #[test]
+    fn test_source_checksum_is_bound_to_the_signed_sums() {
+        let signed = "5708fc639cdfc27347ccfa50db9b1d0c4f0d0f3a7dd5a3e4a0b1c2d3e4f5a6b7";
+        let mut report = SignatureReport {
+            tag: "v25.0".to_string(),
+            threshold: 1,
+            trusted_fingerprints: vec![PRIMARY_A.to_string()],
+            sums: HashMap::from([("bitcoin-25.0.tar.gz".to_string(), signed.to_string())]),
+        };
+        assert_eq!(report.bind_source_sha256(None).unwrap(), signed);
+        assert_eq!(
+            report
+                .bind_source_sha256(Some(&signed.to_uppercase()))
+                .unwrap(),
+            signed
+        );
+        assert!(report.bind_source_sha256(Some(&"0".repeat(64))).is_err());
+
+        // signatures over sums without the source tarball vouch for nothing
+        report.sums.clear();
+        assert!(report.bind_source_sha256(None).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+use crate::config::ShranDefault;
+use crate::error::ShranError;
+use crate::utils::file_lock::write_atomic;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Prefix of the digest input of a symlink, see TreeIndex
+const SYMLINK_PREFIX: &[u8] = b"symlink:";
+
+/// The sha256 digest of every file of a tree, keyed by its path relative to
+/// the root of the tree. Written one file per line, ordered by path, in the
+/// format of `sha256sum`:
+///
+/// ```text
+/// 3b1f...e0a2  COPYING
+/// 9c4d...71f5  src/init.cpp
+/// ```
+///
+/// The content hash of the tree is the sha256 digest of that listing, a two
+/// level Merkle hash over the sorted paths and contents of its files. The
+/// digest of a symlink covers `symlink:` followed by its target, so replacing
+/// a file by a link to a path spelling out the same bytes is a modification.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TreeIndex {
+    files: BTreeMap<String, String>,
+}
+
+impl TreeIndex {
+    /// Hash every file below `tree`. A symlink is hashed by its type and
+    /// target and never followed, git metadata of a checkout is left out.
+    ///
+    /// # Errors
+    /// Returns an io::Error if a directory or file of the tree can not be read
+    pub fn of<P: AsRef<Path>>(tree: P) -> io::Result<Self> {
+        let mut index = Self::default();
+        index.add_dir(tree.as_ref(), "")?;
+        Ok(index)
+    }
+
+    fn add_dir(&mut self, dir: &Path, prefix: &str) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if prefix.is_empty() && name == ".git" {
+                continue;
+            }
+            let path = format!("{}{}", prefix, name);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                self.add_dir(&entry.path(), &format!("{}/", path))?;
+            } else if file_type.is_symlink() {
+                let target = fs::read_link(entry.path())?;
+                let mut hasher = Sha256::new();
+                hasher.update(SYMLINK_PREFIX);
+                hasher.update(target.to_string_lossy().as_bytes());
+                self.files.insert(path, hex::encode(hasher.finalize()));
+            } else {
+                let mut hasher = Sha256::new();
+                io::copy(&mut File::open(entry.path())?, &mut hasher)?;
+                self.files.insert(path, hex::encode(hasher.finalize()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The content hash of the tree, see TreeIndex
+    pub fn root(&self) -> String {
+        hex::encode(Sha256::digest(self.to_string().as_bytes()))
+    }
+
+    /// The files of `current` that were added, removed or modified compared
+    /// to this index
+    pub fn diff(&self, current: &TreeIndex) -> TreeDiff {
+        let mut diff = TreeDiff::default();
+        for (path, digest) in &self.files {
+            match current.files.get(path) {
+                None => diff.removed.push(path.clone()),
+                Some(other) if other != digest => diff.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.added = current
+            .files
+            .keys()
+            .filter(|path| !self.files.contains_key(*path))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+impl fmt::Display for TreeIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        for (path, digest) in &self.files {
+            writeln!(f, "{}  {}", digest, path)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TreeIndex {
+    type Err = ShranError<'static>;
+
+    fn from_str(listing: &str) -> Result<Self, Self::Err> {
+        let mut index = Self::default();
+        for line in listing.lines() {
+            match line.split_once("  ") {
+                Some((digest, path)) if digest.len() == 64 && !path.is_empty() => {
+                    index.files.insert(path.to_string(), digest.to_string());
+                }
+                _ => {
+                    return Err(ShranError::TreeIntegrityError {
+                        msg: format!("{:?} is not a line of a tree index", line),
+                        file: file!(),
+                        line: line!(),
+                        column: column!(),
+                    })
+                }
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// How a tree differs from the tree that was recorded, paths are relative to
+/// the root of the tree
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TreeDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl TreeDiff {
+    /// True if the tree still holds exactly the files it was extracted with
+    pub fn is_pristine(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+impl fmt::Display for TreeDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> fmt::Result {
+        let changes = [
+            ("modified", &self.modified),
+            ("added", &self.added),
+            ("removed", &self.removed),
+        ];
+        for (change, paths) in changes {
+            for path in paths {
+                writeln!(f, "  {:<8} {}", change, path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records the content of extracted trees, and checks them against it later.
+/// The index of a tree is kept in the cache as `trees/<content hash>.sha256`,
+/// the manifest entry of the tree holds the content hash.
+///
+/// # Example
+///
+/// ```ignore
+/// let verifier = TreeVerifier::new();
+/// let tree_hash = verifier.record(&entry.installation_location)?;
+/// // ...
+/// let diff = verifier.verify(&entry.installation_location, &tree_hash)?;
+/// if !diff.is_pristine() {
+///     print!("{}", diff);
+/// }
+/// ```
+pub struct TreeVerifier {
+    index_dir: PathBuf,
+}
+
+impl Default for TreeVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TreeVerifier {
+    pub fn new() -> Self {
+        Self {
+            index_dir: PathBuf::from(ShranDefault::tree_index_dir()),
+        }
+    }
+
+    /// Keep the indexes in `index_dir` instead of the default directory
+    pub fn with_index_dir<P: Into<PathBuf>>(mut self, index_dir: P) -> Self {
+        self.index_dir = index_dir.into();
+        self
+    }
+
+    /// Where the index of the tree with `tree_hash` is kept
+    pub fn index_path(&self, tree_hash: &str) -> PathBuf {
+        self.index_dir.join(format!("{}.sha256", tree_hash))
+    }
+
+    /// Hash `tree` and store its index, returning the content hash
+    ///
+    /// # Errors
+    /// Returns ShranError::FileSystemError if the tree can not be read, or
+    /// the index can not be written
+    pub fn record<P: AsRef<Path>>(&self, tree: P) -> Result<String, ShranError<'static>> {
+        let tree = tree.as_ref();
+        let index = TreeIndex::of(tree).map_err(|e| fs_error(tree, e))?;
+        let tree_hash = index.root();
+        let index_path = self.index_path(&tree_hash);
+        fs::create_dir_all(&self.index_dir).map_err(|e| fs_error(&self.index_dir, e))?;
+        write_atomic(&index_path, index.to_string().as_bytes())
+            .map_err(|e| fs_error(&index_path, e))?;
+        Ok(tree_hash)
+    }
+
+    /// Hash `tree` again and compare it with the index recorded as `tree_hash`
+    ///
+    /// # Errors
+    /// Returns ShranError::TreeIntegrityError if the index is missing or no
+    /// longer matches its content hash, ShranError::FileSystemError if the
+    /// tree can not be read
+    pub fn verify<P: AsRef<Path>>(
+        &self,
+        tree: P,
+        tree_hash: &str,
+    ) -> Result<TreeDiff, ShranError<'static>> {
+        let tree = tree.as_ref();
+        let index_path = self.index_path(tree_hash);
+        let listing = fs::read_to_string(&index_path).map_err(|e| {
+            integrity_error(format!(
+                "the index of {} can not be read from {}: {}",
+                tree.display(),
+                index_path.display(),
+                e
+            ))
+        })?;
+        let recorded: TreeIndex = listing.parse()?;
+        if recorded.root() != tree_hash {
+            return Err(integrity_error(format!(
+                "{} was changed, it no longer matches the content hash {}",
+                index_path.display(),
+                tree_hash
+            )));
+        }
+        if !tree.is_dir() {
+            return Err(integrity_error(format!(
+                "{} does not exist",
+                tree.display()
+            )));
+        }
+        let current = TreeIndex::of(tree).map_err(|e| fs_error(tree, e))?;
+        Ok(recorded.diff(&current))
+    }
+}
+
+fn integrity_error(msg: String) -> ShranError<'static> {
+    ShranError::TreeIntegrityError {
+        msg,
+        file: file!(),
+        line: line!(),
+        column: column!(),
+    }
+}
+
+fn fs_error(path: &Path, e: io::Error) -> ShranError<'static> {
+    ShranError::FileSystemError {
+        msg: format!("{}: {}", path.display(), e),
+        file: file!(),
+        line: line!(),
+        column: column!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TreeIndex, TreeVerifier};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_verify_reports_changed_files() {
+        let dir = env::temp_dir().join(format!("shran-tree-{}", std::process::id()));
+        let tree = dir.join("bitcoin-25.0");
+        fs::create_dir_all(tree.join("src/wallet")).unwrap();
+        fs::create_dir_all(tree.join(".git")).unwrap();
+        fs::write(tree.join("COPYING"), "MIT").unwrap();
+        fs::write(tree.join("src/init.cpp"), "int main() {}").unwrap();
+        fs::write(tree.join("src/wallet/wallet.cpp"), "").unwrap();
+        fs::write(tree.join(".git/HEAD"), "ref: refs/heads/master").unwrap();
+
+        let verifier = TreeVerifier::new().with_index_dir(dir.join("trees"));
+        let tree_hash = verifier.record(&tree).unwrap();
+        let index = TreeIndex::of(&tree).unwrap();
+        assert_eq!(index.to_string().lines().count(), 3);
+        assert_eq!(index.to_string().parse::<TreeIndex>().unwrap(), index);
+        assert_eq!(tree_hash, index.root());
+        assert!(verifier.verify(&tree, &tree_hash).unwrap().is_pristine());
+
+        // git metadata is not part of the tree
+        fs::write(tree.join(".git/HEAD"), "ref: refs/heads/evil").unwrap();
+        assert!(verifier.verify(&tree, &tree_hash).unwrap().is_pristine());
+
+        fs::write(tree.join("src/init.cpp"), "int main() { steal(); }").unwrap();
+        fs::write(tree.join("src/wallet/backdoor.cpp"), "").unwrap();
+        fs::remove_file(tree.join("COPYING")).unwrap();
+        let diff = verifier.verify(&tree, &tree_hash).unwrap();
+        assert_eq!(diff.modified, vec!["src/init.cpp"]);
+        assert_eq!(diff.added, vec!["src/wallet/backdoor.cpp"]);
+        assert_eq!(diff.removed, vec!["COPYING"]);
+        assert!(!diff.is_pristine());
+
+        // a tampered index is refused rather than trusted
+        fs::write(
+            verifier.index_path(&tree_hash),
+            index.to_string().replace("COPYING", "README"),
+        )
+        .unwrap();
+        assert!(verifier.verify(&tree, &tree_hash).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_symlink_differs_from_file() {
+        let dir = env::temp_dir().join(format!("shran-tree-link-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("build.sh"), "../evil.sh").unwrap();
+        let file = TreeIndex::of(&dir).unwrap();
+
+        // a link whose target spells out the contents the file had
+        fs::remove_file(dir.join("build.sh")).unwrap();
+        std::os::unix::fs::symlink("../evil.sh", dir.join("build.sh")).unwrap();
+        let link = TreeIndex::of(&dir).unwrap();
+        assert_eq!(file.diff(&link).modified, vec!["build.sh"]);
+        assert_ne!(file.root(), link.root());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}